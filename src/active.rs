@@ -0,0 +1,284 @@
+use color_eyre::eyre::{bail, Context, Result};
+
+use crate::{
+    namespace::list_namespaces,
+    settings::{Settings, SingleActive},
+    storage::open_storage,
+    task::{load_task, start_task, stop_task, Task, TaskError},
+};
+
+/// Task that is being tracked, along with the namespace it is in
+#[derive(Debug, Clone)]
+pub struct RunningTask {
+    /// Namespace of the task
+    pub namespace: String,
+    /// The running task itself
+    pub task: Task,
+}
+
+impl RunningTask {
+    /// Settings pointing to the namespace of the task
+    pub fn settings(&self, settings: &Settings) -> Settings {
+        Settings {
+            namespace: self.namespace.clone(),
+            ..settings.clone()
+        }
+    }
+}
+
+/// Tasks being tracked in the namespace, or in every namespace if asked for
+pub fn running_tasks(all_namespaces: bool, settings: &Settings) -> Result<Vec<RunningTask>> {
+    let namespaces = if all_namespaces {
+        list_namespaces(settings)?
+            .into_iter()
+            .map(|namespace| namespace.name)
+            .collect()
+    } else {
+        vec![settings.namespace.clone()]
+    };
+
+    let mut running = vec![];
+    for namespace in namespaces {
+        let namespace_settings = Settings {
+            namespace: namespace.clone(),
+            ..settings.clone()
+        };
+        for task in open_storage(&namespace_settings)?
+            .load_open_tasks()
+            .with_context(|| format!("while loading tasks of namespace '{}'", namespace))?
+        {
+            if task.is_running() {
+                running.push(RunningTask {
+                    namespace: namespace.clone(),
+                    task,
+                });
+            }
+        }
+    }
+    Ok(running)
+}
+
+/// Tasks other than the given one that are running where only one task is allowed to run
+fn other_running_tasks(
+    task: &Task,
+    all_namespaces: bool,
+    settings: &Settings,
+) -> Result<Vec<RunningTask>> {
+    Ok(running_tasks(all_namespaces, settings)?
+        .into_iter()
+        .filter(|running| !(running.task.id == task.id && running.namespace == settings.namespace))
+        .collect())
+}
+
+/// Refuse to start the task if the settings allow only one running task and another task is
+/// already running
+pub(crate) fn check_single_active(task: &Task, settings: &Settings) -> Result<()> {
+    let all_namespaces = match settings.task.singleactive {
+        SingleActive::Off => return Ok(()),
+        SingleActive::Namespace => false,
+        SingleActive::Global => true,
+    };
+    if let Some(running) = other_running_tasks(task, all_namespaces, settings)?.first() {
+        let name = if running.namespace == settings.namespace {
+            running.task.description.clone()
+        } else {
+            format!(
+                "{}` in namespace `{}",
+                running.task.description, running.namespace
+            )
+        };
+        bail!(TaskError::AnotherTaskRunning(name));
+    }
+    Ok(())
+}
+
+/// Stop the tasks that are running and start the given one. The running tasks of the namespace
+/// are stopped, or the ones of every namespace if only one task is allowed to run globally. The
+/// task is checked to be startable before anything is stopped. Returns the stopped tasks and the
+/// started task.
+pub fn switch_task(
+    id: &str,
+    annotation: &Option<String>,
+    settings: &Settings,
+) -> Result<(Vec<RunningTask>, Task)> {
+    let task = load_task(id, settings)?;
+    if task.done {
+        bail!(TaskError::TaskAlreadyCompleted);
+    }
+    if !settings.task.blockedstart && task.is_blocked(settings)? {
+        bail!(TaskError::TaskBlocked);
+    }
+
+    let all_namespaces = settings.task.singleactive == SingleActive::Global;
+    let stopped = other_running_tasks(&task, all_namespaces, settings)?;
+    for running in &stopped {
+        stop_task(
+            &running.task.id.to_string(),
+            &false,
+            &running.settings(settings),
+        )
+        .with_context(|| format!("while stopping task '{}'", running.task.id))?;
+    }
+
+    // switching to the task that is already running just stops the others
+    if task.is_running() {
+        return Ok((stopped, task));
+    }
+    let task = start_task(id, annotation, settings)?;
+    Ok((stopped, task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{task::save_task, testing::TestData};
+
+    fn settings(singleactive: SingleActive) -> TestData {
+        TestData::with(|settings| {
+            settings.namespace = "work".to_string();
+            settings.task.singleactive = singleactive;
+        })
+    }
+
+    fn in_namespace(namespace: &str, settings: &Settings) -> Settings {
+        Settings {
+            namespace: namespace.to_string(),
+            ..settings.clone()
+        }
+    }
+
+    fn new_task(description: &str, settings: &Settings) -> String {
+        let mut task = Task::new(description.to_string()).unwrap();
+        save_task(&mut task, settings).unwrap();
+        task.id.to_string()
+    }
+
+    fn is_running(id: &str, settings: &Settings) -> bool {
+        load_task(id, settings).unwrap().is_running()
+    }
+
+    #[test]
+    fn singleactive_off_allows_second_start() {
+        let settings = settings(SingleActive::Off);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &settings);
+        start_task(&first, &None, &settings).unwrap();
+
+        start_task(&second, &None, &settings).unwrap();
+
+        assert!(is_running(&first, &settings));
+        assert!(is_running(&second, &settings));
+    }
+
+    #[test]
+    fn singleactive_namespace_refuses_second_start_in_namespace() {
+        let settings = settings(SingleActive::Namespace);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &settings);
+        start_task(&first, &None, &settings).unwrap();
+
+        assert_eq!(
+            start_task(&second, &None, &settings)
+                .unwrap_err()
+                .downcast::<TaskError>()
+                .unwrap(),
+            TaskError::AnotherTaskRunning("first".to_string())
+        );
+        assert!(!is_running(&second, &settings));
+    }
+
+    #[test]
+    fn singleactive_namespace_allows_start_in_other_namespace() {
+        let settings = settings(SingleActive::Namespace);
+        let home = in_namespace("home", &settings);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &home);
+        start_task(&first, &None, &settings).unwrap();
+
+        start_task(&second, &None, &home).unwrap();
+
+        assert!(is_running(&first, &settings));
+        assert!(is_running(&second, &home));
+    }
+
+    #[test]
+    fn singleactive_global_refuses_start_in_other_namespace() {
+        let settings = settings(SingleActive::Global);
+        let home = in_namespace("home", &settings);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &home);
+        start_task(&first, &None, &settings).unwrap();
+
+        assert_eq!(
+            start_task(&second, &None, &home)
+                .unwrap_err()
+                .downcast::<TaskError>()
+                .unwrap(),
+            TaskError::AnotherTaskRunning("first` in namespace `work".to_string())
+        );
+        assert!(!is_running(&second, &home));
+    }
+
+    #[test]
+    fn switch_task_stops_others_in_namespace() {
+        let settings = settings(SingleActive::Namespace);
+        let home = in_namespace("home", &settings);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &settings);
+        let elsewhere = new_task("elsewhere", &home);
+        start_task(&first, &None, &settings).unwrap();
+        start_task(&elsewhere, &None, &home).unwrap();
+
+        let (stopped, started) = switch_task(&second, &None, &settings).unwrap();
+
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].task.id.to_string(), first);
+        assert!(started.is_running());
+        assert!(!is_running(&first, &settings));
+        assert!(is_running(&elsewhere, &home));
+    }
+
+    #[test]
+    fn switch_task_stops_other_namespaces_in_global_mode() {
+        let settings = settings(SingleActive::Global);
+        let home = in_namespace("home", &settings);
+        let first = new_task("first", &home);
+        let second = new_task("second", &settings);
+        start_task(&first, &None, &home).unwrap();
+
+        let (stopped, started) = switch_task(&second, &None, &settings).unwrap();
+
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].namespace, "home");
+        assert!(started.is_running());
+        assert!(!is_running(&first, &home));
+    }
+
+    #[test]
+    fn switch_to_running_task_only_stops_others() {
+        let settings = settings(SingleActive::Off);
+        let first = new_task("first", &settings);
+        let second = new_task("second", &settings);
+        start_task(&first, &None, &settings).unwrap();
+        start_task(&second, &None, &settings).unwrap();
+        let start_times = |task: &Task| {
+            task.timetracker
+                .iter()
+                .flatten()
+                .map(|track| track.start_time)
+                .collect::<Vec<_>>()
+        };
+        let started_at = start_times(&load_task(&second, &settings).unwrap());
+
+        let (stopped, task) = switch_task(&second, &None, &settings).unwrap();
+
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].task.id.to_string(), first);
+        assert!(!is_running(&first, &settings));
+        // the running time track is kept as it was
+        assert_eq!(start_times(&task), started_at);
+        assert!(is_running(&second, &settings));
+    }
+}
+
+// eof
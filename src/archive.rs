@@ -0,0 +1,457 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, rename, write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Duration, Local};
+use color_eyre::eyre::{bail, Context, Result};
+use glob::glob;
+use nom::{character::complete::one_of, combinator::all_consuming, sequence::pair, IResult};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use crate::note::{delete_note, load_note, Note};
+use crate::{
+    parser::{filter_lexicon::Filter, track_time::number},
+    settings::Settings,
+    storage::{
+        index::{file_stamp, TaskSummary},
+        open_storage,
+    },
+    task::{delete_task, list_tasks_matching, Task},
+};
+
+/// Name of the file in the archive directory that keeps the summaries of the archived tasks
+const ARCHIVE_INDEX_FILE: &str = "tsk-rs-archive-index.json";
+
+/// Errors that can occur when working with the archive
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// Age was not a number of days or weeks
+    #[error("invalid age `{0}`, use days or weeks like 30d or 4w")]
+    InvalidAge(String),
+}
+
+/// Archived task along with its note
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveEntry {
+    task: Task,
+    #[cfg(feature = "note")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<Note>,
+}
+
+/// Summaries of the tasks in an archive file together with the file stamp they were read from
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedMonth {
+    modified: SystemTime,
+    size: u64,
+    summaries: Vec<TaskSummary>,
+}
+
+/// Summaries of the archived tasks by archive file. Entries are validated against the
+/// modification time and size of the archive files, so the archive files themselves are read only
+/// when they have changed since they were summarised.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ArchiveIndex {
+    months: BTreeMap<String, ArchivedMonth>,
+}
+
+fn age(input: &str) -> IResult<&str, (u32, char)> {
+    pair(number, one_of("dw"))(input)
+}
+
+/// Parse an age like `30d` or `4w` to a duration
+pub fn parse_age(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let text = input.to_lowercase();
+    let Ok((_, (amount, unit))) = all_consuming(age)(text.as_str()) else {
+        bail!(ArchiveError::InvalidAge(input.to_string()));
+    };
+    match unit {
+        'd' => Ok(Duration::days(amount as i64)),
+        _ => Ok(Duration::weeks(amount as i64)),
+    }
+}
+
+/// Returns the directory holding the archive of the namespace
+fn archive_pathbuf(settings: &Settings) -> Result<PathBuf> {
+    let pathbuf = settings.db_pathbuf()?.join("archive");
+    if !pathbuf.is_dir() {
+        create_dir_all(&pathbuf).with_context(|| "while creating archive directory")?;
+    }
+    Ok(pathbuf)
+}
+
+/// Returns the archive file of the month, e.g `2024-05.yaml`
+fn month_pathbuf(month: &str, settings: &Settings) -> Result<PathBuf> {
+    Ok(archive_pathbuf(settings)?.join(format!("{}.yaml", month)))
+}
+
+fn load_month(pathbuf: &Path) -> Result<Vec<ArchiveEntry>> {
+    if !pathbuf.is_file() {
+        return Ok(vec![]);
+    }
+    serde_yaml::from_str(&read_to_string(pathbuf).with_context(|| "while reading archive file")?)
+        .with_context(|| "while deserializing archive file")
+}
+
+/// Write the entries of the month to a temporary file first, so that an interrupted write does
+/// not destroy the archive
+fn save_month(pathbuf: &Path, entries: &[ArchiveEntry]) -> Result<()> {
+    let temporary = pathbuf.with_extension("yaml.tmp");
+    write(
+        &temporary,
+        serde_yaml::to_string(entries).with_context(|| "while serializing archive file")?,
+    )
+    .with_context(|| "while writing archive file")?;
+    rename(&temporary, pathbuf).with_context(|| "while replacing archive file")
+}
+
+/// Returns the archive files of the namespace, oldest month first
+fn month_pathbufs(settings: &Settings) -> Result<Vec<PathBuf>> {
+    let mut months: Vec<PathBuf> = vec![];
+    for dir_entry in
+        read_dir(archive_pathbuf(settings)?).with_context(|| "while reading archive directory")?
+    {
+        let pathbuf = dir_entry?.path();
+        if pathbuf
+            .extension()
+            .is_some_and(|extension| extension == "yaml")
+        {
+            months.push(pathbuf);
+        }
+    }
+    months.sort();
+    Ok(months)
+}
+
+/// Load all entries of the archive, oldest month first
+fn load_entries(settings: &Settings) -> Result<Vec<ArchiveEntry>> {
+    let mut entries: Vec<ArchiveEntry> = vec![];
+    for month in month_pathbufs(settings)? {
+        entries.append(&mut load_month(&month)?);
+    }
+    Ok(entries)
+}
+
+impl ArchiveIndex {
+    /// Load the index of the archive. A missing or unreadable index results in an empty index,
+    /// which is then rebuilt from the archive files on [ArchiveIndex::refresh].
+    fn load(index_pathbuf: &Path) -> Self {
+        read_to_string(index_pathbuf)
+            .ok()
+            .and_then(|index_json| serde_json::from_str(&index_json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, index_pathbuf: &Path) -> Result<()> {
+        let temporary = index_pathbuf.with_extension("json.tmp");
+        write(
+            &temporary,
+            serde_json::to_string(self).with_context(|| "while serializing archive index")?,
+        )
+        .with_context(|| "while writing archive index file")?;
+        rename(&temporary, index_pathbuf).with_context(|| "while replacing archive index file")
+    }
+
+    /// Bring the index up to date with the archive files. Returns true if the index was changed.
+    fn refresh(&mut self, months: &[PathBuf]) -> Result<bool> {
+        let names: Vec<String> = months
+            .iter()
+            .map(|month| month.file_stem().unwrap().to_string_lossy().to_string())
+            .collect();
+        let before = self.months.len();
+        self.months.retain(|name, _| names.contains(name));
+        let mut changed = self.months.len() != before;
+
+        for (name, pathbuf) in names.into_iter().zip(months) {
+            let (modified, size) = file_stamp(pathbuf)?;
+            if self
+                .months
+                .get(&name)
+                .is_some_and(|month| month.modified == modified && month.size == size)
+            {
+                continue;
+            }
+            let summaries = load_month(pathbuf)?
+                .iter()
+                .map(|entry| TaskSummary::from(&entry.task))
+                .collect();
+            self.months.insert(
+                name,
+                ArchivedMonth {
+                    modified,
+                    size,
+                    summaries,
+                },
+            );
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Returns the summaries of all archived tasks of the namespace without reading the archive
+/// files that have not changed since they were last summarised. Tasks brought back to the
+/// storage by other means, like undo, are left out.
+pub fn archived_summaries(settings: &Settings) -> Result<Vec<TaskSummary>> {
+    let index_pathbuf = archive_pathbuf(settings)?.join(ARCHIVE_INDEX_FILE);
+    let mut index = ArchiveIndex::load(&index_pathbuf);
+    if index.refresh(&month_pathbufs(settings)?)? {
+        index.save(&index_pathbuf)?;
+    }
+
+    let stored_ids: HashSet<Uuid> = open_storage(settings)?.task_ids()?.into_iter().collect();
+    Ok(index
+        .months
+        .into_values()
+        .flat_map(|month| month.summaries)
+        .filter(|summary| !stored_ids.contains(&summary.id))
+        .collect())
+}
+
+/// Load all archived tasks of the namespace. Tasks brought back to the storage by other means,
+/// like undo, are left out.
+pub fn archived_tasks(settings: &Settings) -> Result<Vec<Task>> {
+    let stored_ids: HashSet<Uuid> = open_storage(settings)?.task_ids()?.into_iter().collect();
+    Ok(load_entries(settings)?
+        .into_iter()
+        .map(|entry| entry.task)
+        .filter(|task| !stored_ids.contains(&task.id))
+        .collect())
+}
+
+/// List tasks like [crate::task::list_tasks] does. When completed tasks are included the
+/// archived tasks matching the search are listed after the tasks in the storage.
+pub fn list_tasks_with_archive(
+    search: &Option<String>,
+    include_done: &bool,
+    settings: &Settings,
+) -> Result<Vec<Task>> {
+    let filter = match search {
+        Some(search) => Some(Filter::from_str(search)?),
+        None => None,
+    };
+    let mut tasks = list_tasks_matching(&filter, include_done, settings)?;
+    if *include_done {
+        tasks.extend(
+            archived_tasks(settings)?
+                .into_iter()
+                .filter(|task| filter.as_ref().is_none_or(|filter| filter.matches(task))),
+        );
+    }
+    Ok(tasks)
+}
+
+/// When the task was completed. Tasks without a recorded completion time fall back to the time
+/// they were created.
+fn completed_time(task: &Task) -> Option<DateTime<Local>> {
+    task.metadata
+        .get("tsk-rs-task-completed-time")
+        .or_else(|| task.metadata.get("tsk-rs-task-create-time"))
+        .and_then(|time| DateTime::from_str(time).ok())
+}
+
+/// Remove the rotated backup files of the task or note from the directory
+fn remove_backups(id: &Uuid, pathbuf: &Path) -> Result<()> {
+    let pattern = pathbuf.join(format!("{}.*.yaml", id));
+    for backup in glob(pattern.to_str().unwrap()).with_context(|| "while searching backups")? {
+        remove_file(backup?).with_context(|| "while removing backup file")?;
+    }
+    Ok(())
+}
+
+/// Tasks that can be archived: completed before the given time, not a template of a recurring
+/// task and without open subtasks
+fn archivable_tasks(tasks: &[Task], before: DateTime<Local>) -> Vec<Task> {
+    let open_parents: HashSet<Uuid> = tasks
+        .iter()
+        .filter(|task| !task.done)
+        .filter_map(|task| task.parent)
+        .collect();
+    tasks
+        .iter()
+        .filter(|task| {
+            task.done
+                && completed_time(task).is_some_and(|completed| completed < before)
+                && !(task.recurrence.is_some() && task.recurrence_parent().is_none())
+                && !open_parents.contains(&task.id)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Move the tasks completed longer ago than the given age, along with their notes, to the archive
+/// of the month they were completed in. Backups of the tasks and notes are removed. Returns the
+/// archived tasks by month, nothing is changed on a dry run.
+pub fn archive_tasks(
+    older_than: Duration,
+    dry_run: bool,
+    settings: &Settings,
+) -> Result<BTreeMap<String, Vec<Task>>> {
+    let storage = open_storage(settings)?;
+    let tasks = storage
+        .load_tasks()
+        .with_context(|| "while loading tasks from storage")?;
+    // nothing can be older than the earliest time there is
+    let Some(before) = chrono::offset::Local::now().checked_sub_signed(older_than) else {
+        return Ok(BTreeMap::new());
+    };
+
+    let mut months: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    for task in archivable_tasks(&tasks, before) {
+        // archivable tasks always have a completion time
+        let month = completed_time(&task).unwrap().format("%Y-%m").to_string();
+        months.entry(month).or_default().push(task);
+    }
+    if dry_run {
+        return Ok(months);
+    }
+
+    for (month, tasks) in &months {
+        let pathbuf = month_pathbuf(month, settings)?;
+        let mut entries = load_month(&pathbuf)?;
+        // a task archived again after an undo replaces its earlier copy
+        entries.retain(|entry| !tasks.iter().any(|task| task.id == entry.task.id));
+        for task in tasks {
+            entries.push(ArchiveEntry {
+                task: task.clone(),
+                #[cfg(feature = "note")]
+                note: if storage.note_exists(&task.id)? {
+                    Some(load_note(&task.id.to_string(), settings)?)
+                } else {
+                    None
+                },
+            });
+        }
+        save_month(&pathbuf, &entries)?;
+
+        // tasks are removed from the storage only after the archive has been written
+        for task in tasks {
+            #[cfg(feature = "note")]
+            if storage.note_exists(&task.id)? {
+                delete_note(&task.id.to_string(), settings)?;
+                remove_backups(&task.id, &settings.note_db_pathbuf()?)?;
+            }
+            delete_task(&task.id.to_string(), settings)?;
+            remove_backups(&task.id, &settings.task_db_pathbuf()?)?;
+        }
+    }
+
+    Ok(months)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tag::scan_tags, task::save_task, testing::TestData};
+
+    fn completed(description: &str, days: i64, now: DateTime<Local>) -> Task {
+        let mut task = Task::new(description.to_string()).unwrap();
+        task.done = true;
+        task.metadata.insert(
+            "tsk-rs-task-completed-time".to_string(),
+            (now - Duration::days(days)).to_rfc3339(),
+        );
+        task
+    }
+
+    #[test]
+    fn ages_in_days_and_weeks() {
+        assert_eq!(parse_age("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_age("4W").unwrap(), Duration::weeks(4));
+        assert_eq!(parse_age(" 0d ").unwrap(), Duration::zero());
+    }
+
+    #[test]
+    fn invalid_ages() {
+        for age in [
+            "",
+            "d",
+            "30",
+            "-1d",
+            "3m",
+            "3ä",
+            "ä",
+            "30 d",
+            "99999999999d",
+        ] {
+            assert_eq!(
+                parse_age(age)
+                    .unwrap_err()
+                    .downcast::<ArchiveError>()
+                    .unwrap(),
+                ArchiveError::InvalidAge(age.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn old_completed_tasks_are_archivable() {
+        let now = Local::now();
+        let old = completed("old", 40, now);
+        let recent = completed("recent", 10, now);
+        let open = Task::new("open".to_string()).unwrap();
+
+        let archivable = archivable_tasks(&[old.clone(), recent, open], now - Duration::days(30));
+
+        assert_eq!(archivable.len(), 1);
+        assert_eq!(archivable[0].id, old.id);
+    }
+
+    #[test]
+    fn parent_of_open_subtask_is_not_archivable() {
+        let now = Local::now();
+        let parent = completed("parent", 40, now);
+        let mut open_subtask = Task::new("open subtask".to_string()).unwrap();
+        open_subtask.parent = Some(parent.id);
+
+        let archivable = archivable_tasks(&[parent, open_subtask], now - Duration::days(30));
+
+        assert!(archivable.is_empty());
+    }
+
+    #[test]
+    fn archived_summaries_follow_archive_files() {
+        let settings = TestData::new();
+        let mut task = completed("old #errand", 40, Local::now());
+        task.tags = Some(vec!["errand".to_string()]);
+        save_task(&mut task, &settings).unwrap();
+        let months = archive_tasks(Duration::days(30), false, &settings).unwrap();
+        assert_eq!(scan_tags(&settings).unwrap().get("errand"), Some(&1));
+
+        // the archive file is read again only once it has changed
+        let month = months.keys().next().unwrap();
+        let pathbuf = month_pathbuf(month, &settings).unwrap();
+        let mut entries = load_month(&pathbuf).unwrap();
+        entries[0].task.tags = Some(vec!["chore".to_string()]);
+        save_month(&pathbuf, &entries).unwrap();
+
+        let summaries = archived_summaries(&settings).unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].tags, Some(vec!["chore".to_string()]));
+        assert!(archive_pathbuf(&settings)
+            .unwrap()
+            .join(ARCHIVE_INDEX_FILE)
+            .is_file());
+    }
+
+    #[test]
+    fn age_beyond_earliest_time_archives_nothing() {
+        let settings = TestData::new();
+        save_task(&mut completed("old", 40, Local::now()), &settings).unwrap();
+
+        let months = archive_tasks(parse_age("4294967295w").unwrap(), true, &settings).unwrap();
+
+        assert!(months.is_empty());
+    }
+}
+
+// eof
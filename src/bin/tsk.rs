@@ -5,25 +5,71 @@
 //! Command line utility for creating and managing tasks and their metadata
 
 use bat::{Input, PrettyPrinter};
-use chrono::NaiveDateTime;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use cli_table::{
     format::{Border, Separator},
     print_stdout, Cell, Color, Style, Table,
 };
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{bail, Context, Result};
 use dotenv::dotenv;
 use hhmmss::Hhmmss;
 use question::{Answer, Question};
-use std::{fs::remove_file, path::PathBuf};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::read_to_string,
+    io::{stdin, stdout, Read, Write},
+    path::PathBuf,
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::sleep,
+};
+use termtree::Tree;
 use tsk_rs::{
+    active::{running_tasks, switch_task},
+    archive::{archive_tasks, archived_summaries, list_tasks_with_archive, parse_age},
+    bulk::{complete_tasks, modify_tasks, select_tasks, trash_tasks},
+    estimate::{estimate_accuracy, EstimateAccuracy},
+    focus::{finish_pomodoro, focus_report, start_pomodoro, validate_session},
+    export::{export_tasks, write_export, ExportFormat, ExportedTask},
+    import::{
+        ical::import_ical, taskwarrior::import_taskwarrior, ImportAction, ImportResult,
+    },
+    invoice::{build_invoice, parse_month, InvoiceFormat},
+    journal::{history, redo, undo, Operation},
     metadata::MetadataKeyValuePair,
+    namespace::{
+        create_namespace, delete_namespace, list_namespaces, move_task, rename_namespace,
+        set_current_namespace,
+    },
+    output::{print_structured, truncate_description, ExitStatus, OutputFormat},
+    parser::{
+        due_date::parse_due_date,
+        filter_lexicon::Filter,
+        track_time::{parse_duration, parse_estimate, parse_track_time},
+    },
+    project::scan_projects,
+    recurrence::{list_recurrence_templates, stop_recurrence, Recurrence},
+    report::{time_report, ReportError, ReportGrouping, ReportPeriod},
     settings::{default_config, show_config, Settings},
+    storage::{migrate, reindex, StorageBackend},
+    subtask::{load_task_trees, TaskNode},
     task::{
-        amount_of_tasks, list_tasks, load_task, new_task, save_task, start_task, stop_task,
-        task_pathbuf_from_task, Task, TaskPriority,
-    }, tag::scan_tags,
+        amount_of_tasks, explain_score, load_task, new_task, open_task_ids, resolve_task_id,
+        save_task, start_task, stop_task, total_score, Task, TaskPriority,
+    },
+    tag::scan_tags,
+    track::{delete_span, edit_span, local_time, log_span, merge_spans, split_span, TrackError},
+    trash::{list_trash, purge_trash, restore_from_trash},
 };
+#[cfg(feature = "tui")]
+use tsk_rs::tui::run_tui;
+use uuid::Uuid;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -32,17 +78,20 @@ struct Cli {
     #[clap(short, long, value_parser, env = "TSK_CONFIGFILE", value_name = "CONFIGFILE", default_value = default_config())]
     config: PathBuf,
 
-    /// Sets the namespace of tasks
+    /// Sets the namespace of tasks, defaults to the current namespace
     #[clap(
         short,
         long,
         value_parser,
         env = "TSK_NAMESPACE",
-        value_name = "NAMESPACE",
-        default_value = "default"
+        value_name = "NAMESPACE"
     )]
     namespace: Option<String>,
 
+    /// Sets the output format, overrides the format set in the configuration
+    #[clap(long, value_enum, global = true, value_name = "FORMAT")]
+    output: Option<OutputFormat>,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
@@ -50,8 +99,10 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Adds a new task from task description string
-    #[clap(trailing_var_arg = true)]
     New {
+        /// Make the new task a subtask of an existing task
+        #[clap(long, value_parser)]
+        parent: Option<String>,
         /// Task description string
         #[clap(value_parser)]
         descriptor: Vec<String>,
@@ -64,30 +115,42 @@ enum Commands {
     },
     /// List task(s)
     List {
-        /// Search a word or a part of word from description, project and/or tags. Empty will list all.
-        #[clap(value_parser)]
-        search: Option<String>,
+        /// Filter expression, for example `project:work +urgent or prio>=high`. A plain word is
+        /// searched from description, project and/or tags. Empty will list all.
+        #[clap(value_parser, allow_hyphen_values = true)]
+        search: Vec<String>,
         /// Include also completed tasks
         #[clap(short, long, value_parser)]
         include_done: bool,
+        /// List tasks from all namespaces
+        #[clap(short, long, value_parser)]
+        all_namespaces: bool,
     },
-    /// Mark task as done and stop time tracking if running
+    /// Mark task(s) as done and stop time tracking if running
     Done {
-        /// Existing task id
-        #[clap(value_parser)]
-        id: String,
+        #[clap(flatten)]
+        selection: TaskSelection,
     },
-    /// Delete task file permanently
+    /// Move task(s) and their notes to the trash
     Delete {
-        /// Existing task id
-        #[clap(value_parser)]
-        id: String,
-        /// Delete file silently
-        #[clap(short, long, value_parser)]
-        force: bool,
+        #[clap(flatten)]
+        selection: TaskSelection,
+    },
+    /// Manage deleted tasks and notes
+    Trash {
+        #[clap(subcommand)]
+        command: TrashCommands,
+    },
+    /// Move old completed tasks and their notes to the archive
+    Archive {
+        /// Archive tasks completed longer ago than this, in days or weeks like 30d or 4w
+        #[clap(long, value_parser = parse_age_arg, default_value = "30d")]
+        older_than: Duration,
+        /// Show what would be archived without changing anything
+        #[clap(short = 'n', long, value_parser)]
+        dry_run: bool,
     },
     /// Start tracking a task
-    #[clap(trailing_var_arg = true)]
     Start {
         /// Existing task id
         #[clap(value_parser)]
@@ -105,6 +168,66 @@ enum Commands {
         #[clap(short, long, value_parser)]
         done: bool,
     },
+    /// Stop the running task(s) and start tracking another task
+    Switch {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Optional annotation for the job at hand
+        #[clap(value_parser)]
+        annotation: Vec<String>,
+    },
+    /// Show the running tasks of all namespaces and how long they have been running
+    Current,
+    /// Focus on a task in pomodoros: track time for a while, take a break and repeat
+    Focus {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Length of a pomodoro: 25m, 1h or 0:25
+        #[clap(long, value_parser = parse_duration_arg, default_value = "25m")]
+        length: Duration,
+        /// Length of the break between pomodoros, not tracked to the task
+        #[clap(long = "break", value_parser = parse_duration_arg, default_value = "5m")]
+        break_length: Duration,
+        /// Number of pomodoros in the session
+        #[clap(long, value_parser, default_value_t = 4)]
+        cycles: usize,
+    },
+    /// Log time spent on a task after the fact, e.g a meeting or a forgotten start
+    Log {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// When the work started: 14:00, yesterday 14:00, -45m, 2024-05-01 14:00 or
+        /// 2024-05-01T14:00:00
+        #[clap(
+            long,
+            allow_hyphen_values = true,
+            value_parser = parse_track_time_arg,
+            required_unless_present = "duration"
+        )]
+        from: Option<NaiveDateTime>,
+        /// When the work ended, in the same formats as the start. Defaults to now.
+        #[clap(
+            long,
+            allow_hyphen_values = true,
+            value_parser = parse_track_time_arg,
+            conflicts_with = "duration"
+        )]
+        to: Option<NaiveDateTime>,
+        /// How long the work took: 45m, 1h30m, 2h or 1:30. Ends now if the start is not given.
+        #[clap(long, value_parser = parse_duration_arg)]
+        duration: Option<Duration>,
+        /// Optional annotation for the work done
+        #[clap(value_parser)]
+        annotation: Vec<String>,
+    },
+    /// List and edit the time spans tracked to a task
+    Track {
+        #[clap(subcommand)]
+        command: TrackCommands,
+    },
     /// Edit raw datafile of the task (for advanced users)
     Edit {
         /// Existing task id
@@ -115,14 +238,14 @@ enum Commands {
     Config,
     /// Set task characteristics like priority, due date and etc
     Set {
-        /// Existing task id
-        #[clap(value_parser)]
-        id: String,
+        #[clap(flatten)]
+        selection: TaskSelection,
         /// Set/change priority of the task
         #[clap(long, value_enum)]
         priority: Option<TaskPriority>,
-        /// Set/change due date of the task (YYYY-MM-DDTHH:MM:SS)
-        #[clap(long, value_parser)]
+        /// Set/change due date of the task: YYYY-MM-DDTHH:MM:SS, YYYY-MM-DD, HH:MM, today,
+        /// tomorrow, mon..sun, eod, eow, eom, eoy, +3h, +3d or +2w
+        #[clap(long, value_parser = parse_due_date_arg)]
         due_date: Option<NaiveDateTime>,
         /// Add tag to task
         #[clap(long, value_parser)]
@@ -133,12 +256,23 @@ enum Commands {
         /// Add/change metadata of the task: x-key=value
         #[clap(long, value_parser)]
         metadata: Option<Vec<MetadataKeyValuePair>>,
+        /// Set/change estimated effort of the task: 90m, 2h, 1d or 1d4h. A day is 8 hours.
+        #[clap(long, value_parser = parse_estimate_arg)]
+        estimate: Option<Duration>,
+        /// Flag the task to be billable (true) or non-billable (false)
+        #[clap(long, value_parser)]
+        billable: Option<bool>,
+        /// Add task(s) that have to be completed before this task can be started
+        #[clap(long, value_parser)]
+        depends: Option<Vec<String>>,
+        /// Make the task a subtask of another task
+        #[clap(long, value_parser)]
+        parent: Option<String>,
     },
     /// Unset task characteristics
     Unset {
-        /// Existing task id
-        #[clap(value_parser)]
-        id: String,
+        #[clap(flatten)]
+        selection: TaskSelection,
         /// Unset priority
         #[clap(long, value_parser)]
         priority: bool,
@@ -154,63 +288,536 @@ enum Commands {
         /// Remove metadata(s) from task
         #[clap(long, value_parser)]
         metadata: Option<Vec<String>>,
+        /// Unset estimated effort
+        #[clap(long, value_parser)]
+        estimate: bool,
+        /// Unset billable flag so that the task follows the configured default
+        #[clap(long, value_parser)]
+        billable: bool,
+        /// Remove dependency to task(s)
+        #[clap(long, value_parser)]
+        depends: Option<Vec<String>>,
+        /// Detach the task from its parent task
+        #[clap(long, value_parser)]
+        parent: bool,
     },
-    /// Shorthand: set 'hold' special tag for task
+    /// Shorthand: set 'hold' special tag for task(s)
     Hold {
-        /// Existing task id
-        #[clap(value_parser)]
-        id: String,
+        #[clap(flatten)]
+        selection: TaskSelection,
     },
-    /// Shorthand: set 'next' special tag for task
+    /// Shorthand: set 'next' special tag for task(s)
     Next {
-        /// Existing task id
+        #[clap(flatten)]
+        selection: TaskSelection,
+    },
+    /// Show tasks and their subtasks as a tree with progress rolled up from the subtasks
+    Tree {
+        /// Existing task id, shows only the task and its subtasks
         #[clap(value_parser)]
-        id: String,
+        id: Option<String>,
+        /// Show completed tasks as well
+        #[clap(short, long, value_parser)]
+        include_done: bool,
     },
+    /// Open the interactive terminal user interface
+    #[cfg(feature = "tui")]
+    Tui,
     /// Output tags list and how many times an tag is used
     Tags,
     /// Output projects list and how many times an project is used
     Projects,
+    /// Manage recurring tasks
+    Recur {
+        #[clap(subcommand)]
+        command: RecurCommands,
+    },
+    /// Show the urgency score of a task
+    Score {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// List each term contributing to the score
+        #[clap(short, long, value_parser)]
+        explain: bool,
+    },
+    /// Import tasks from other task managers
+    Import {
+        #[clap(subcommand)]
+        command: ImportCommands,
+    },
+    /// Export tasks with their time tracking and notes
+    Export {
+        /// Filter expression like in `list`. Empty will export all.
+        #[clap(value_parser, allow_hyphen_values = true)]
+        search: Vec<String>,
+        /// Include also completed tasks
+        #[clap(short, long, value_parser)]
+        include_done: bool,
+        /// Export tasks from all namespaces
+        #[clap(short, long, value_parser)]
+        all_namespaces: bool,
+        /// Output format of the export
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+    },
+    /// Rebuild the task index of the namespace from the stored tasks
+    Reindex,
+    /// Undo the most recent command that changed tasks or notes
+    Undo,
+    /// Redo the most recently undone command
+    Redo,
+    /// List the commands that changed tasks or notes, most recent first
+    History {
+        /// Amount of commands to list
+        #[clap(short, long, value_parser, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Manage namespaces
+    Namespace {
+        #[clap(subcommand)]
+        command: NamespaceCommands,
+    },
+    /// Manage the storage of tasks and notes
+    Db {
+        #[clap(subcommand)]
+        command: DbCommands,
+    },
+    /// Report time tracked to tasks per period and group
+    Report {
+        /// Filter expression to limit the report to matching tasks. Empty will include all.
+        #[clap(value_parser, allow_hyphen_values = true)]
+        filter: Vec<String>,
+        /// First day of the report, defaults to the beginning of the period containing --to
+        #[clap(long, value_parser)]
+        from: Option<NaiveDate>,
+        /// Last day of the report, defaults to today
+        #[clap(long, value_parser)]
+        to: Option<NaiveDate>,
+        /// Length of a single period in the report
+        #[clap(short, long, value_enum, default_value_t = ReportPeriod::Week)]
+        period: ReportPeriod,
+        /// Group by project, tag or a metadata key like x-client
+        #[clap(short, long, value_parser, default_value = "project")]
+        group_by: ReportGrouping,
+        /// Output format of the report
+        #[clap(short, long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+        /// Report completed pomodoros per task instead of tracked time
+        #[clap(long, value_parser)]
+        focus: bool,
+    },
+    /// Compare estimated effort to time tracked to completed tasks per project
+    Estimates {
+        /// Filter expression to limit the comparison to matching tasks. Empty will include all.
+        #[clap(value_parser, allow_hyphen_values = true)]
+        filter: Vec<String>,
+    },
+    /// Timesheet of the billable time tracked during a month
+    Invoice {
+        /// Filter expression to limit the timesheet to matching tasks. Empty will include all.
+        #[clap(value_parser, allow_hyphen_values = true)]
+        filter: Vec<String>,
+        /// Include only the tasks of the client given in the x-client metadata
+        #[clap(long, value_parser)]
+        client: Option<String>,
+        /// Month of the timesheet: YYYY-MM. Defaults to the current month.
+        #[clap(long, value_parser = parse_month_arg)]
+        month: Option<NaiveDate>,
+        /// Include tasks from all namespaces
+        #[clap(short, long, value_parser)]
+        all_namespaces: bool,
+        /// Format of the timesheet
+        #[clap(short, long, value_enum, default_value_t = InvoiceFormat::Markdown)]
+        format: InvoiceFormat,
+    },
+}
+
+/// Tasks a command is applied to, given by their ids or by a filter expression
+#[derive(Args)]
+struct TaskSelection {
+    /// Existing task id(s)
+    #[clap(
+        value_parser,
+        required_unless_present = "filter",
+        conflicts_with = "filter"
+    )]
+    ids: Vec<String>,
+    /// Apply to the open tasks matching the filter expression
+    #[clap(long, value_parser)]
+    filter: Option<Filter>,
+    /// Apply without confirmation
+    #[clap(short, long, value_parser)]
+    force: bool,
+}
+
+impl TaskSelection {
+    /// Several tasks are selected, or the selected tasks are not known before the filter is run
+    fn is_bulk(&self) -> bool {
+        self.ids.len() > 1 || self.filter.is_some()
+    }
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Copy all tasks and notes of the namespace to another storage backend
+    Migrate {
+        /// Storage backend to copy the data to
+        #[clap(value_enum)]
+        to: StorageBackend,
+        /// Storage backend to copy the data from, defaults to the configured backend
+        #[clap(short, long, value_enum)]
+        from: Option<StorageBackend>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Import tasks from the output of Taskwarrior `task export`. Importing again updates the
+    /// tasks imported before.
+    Taskwarrior {
+        /// JSON file written by `task export`, or - to read from standard input
+        #[clap(value_parser)]
+        file: PathBuf,
+        /// Show what would be imported without saving anything
+        #[clap(short = 'n', long, value_parser)]
+        dry_run: bool,
+    },
+    /// Import the todos of an iCalendar file. Importing again updates the tasks imported before.
+    Ical {
+        /// iCalendar (.ics) file, or - to read from standard input
+        #[clap(value_parser)]
+        file: PathBuf,
+        /// Show what would be imported without saving anything
+        #[clap(short = 'n', long, value_parser)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List deleted tasks and notes, most recently deleted first
+    List,
+    /// Restore a deleted task and its note
+    Restore {
+        /// Task id of the trash entry
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Delete entries in the trash permanently
+    Purge {
+        /// Task id of the trash entry, empty will purge the whole trash
+        #[clap(value_parser)]
+        id: Option<String>,
+        /// Purge without confirmation
+        #[clap(short, long, value_parser)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum NamespaceCommands {
+    /// List namespaces and the amount of tasks in them
+    List,
+    /// Create a new namespace
+    Create {
+        /// Name of the namespace
+        #[clap(value_parser)]
+        name: String,
+    },
+    /// Rename a namespace
+    Rename {
+        /// Existing namespace
+        #[clap(value_parser)]
+        name: String,
+        /// New name for the namespace
+        #[clap(value_parser)]
+        new_name: String,
+    },
+    /// Delete a namespace with all of its tasks and notes
+    Delete {
+        /// Existing namespace
+        #[clap(value_parser)]
+        name: String,
+        /// Delete without confirmation
+        #[clap(short, long, value_parser)]
+        force: bool,
+    },
+    /// Make the namespace current, it is used when no namespace is given
+    Use {
+        /// Existing namespace
+        #[clap(value_parser)]
+        name: String,
+    },
+    /// Move a task and its note to another namespace
+    MoveTask {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Namespace to move the task to
+        #[clap(value_parser)]
+        namespace: String,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum TrackCommands {
+    /// List the tracked time spans of a task with their numbers
+    List {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Change the start, end or annotation of a time span
+    Edit {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Number of the time span as shown by `tsk track list`
+        #[clap(value_parser)]
+        number: usize,
+        /// New start: 14:00, yesterday 14:00, -45m, 2024-05-01 14:00 or 2024-05-01T14:00:00
+        #[clap(long, allow_hyphen_values = true, value_parser = parse_track_time_arg)]
+        from: Option<NaiveDateTime>,
+        /// New end in the same formats as the start, stops a running time span
+        #[clap(long, allow_hyphen_values = true, value_parser = parse_track_time_arg)]
+        to: Option<NaiveDateTime>,
+        /// New annotation
+        #[clap(long, value_parser)]
+        annotation: Option<String>,
+    },
+    /// Delete a time span
+    Delete {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Number of the time span as shown by `tsk track list`
+        #[clap(value_parser)]
+        number: usize,
+    },
+    /// Split a time span in two
+    Split {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Number of the time span as shown by `tsk track list`
+        #[clap(value_parser)]
+        number: usize,
+        /// Moment to split the time span at, defaults to the middle of it
+        #[clap(long, allow_hyphen_values = true, value_parser = parse_track_time_arg)]
+        at: Option<NaiveDateTime>,
+    },
+    /// Merge a time span with the one following it, the time between them included
+    Merge {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Number of the time span as shown by `tsk track list`
+        #[clap(value_parser)]
+        number: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecurCommands {
+    /// List recurrence templates and their currently open instances
+    List,
+    /// Set or change the recurrence rule of a task: daily, weekly, monthly, Nd or mon,wed,fri
+    Set {
+        /// Existing task id
+        #[clap(value_parser)]
+        id: String,
+        /// Recurrence rule
+        #[clap(value_parser)]
+        rule: Recurrence,
+    },
+    /// Stop the recurrence so that no new instances are created when the task is completed
+    Stop {
+        /// Existing task id of the template or any of its instances
+        #[clap(value_parser)]
+        id: String,
+    },
+}
+
+/// Returns the argument of the command the token is a flag or an option of, if any. Options
+/// given as `--option=value` are recognized as well.
+fn find_flag<'a>(command: &'a clap::Command, token: &OsString) -> Option<&'a clap::Arg> {
+    let token = token.to_str()?;
+    command.get_arguments().find(|arg| {
+        if let Some(long) = token.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or(long);
+            arg.get_long_and_visible_aliases()
+                .is_some_and(|longs| longs.contains(&name))
+        } else if let Some(short) = token.strip_prefix('-') {
+            let mut chars = short.chars();
+            match (chars.next(), chars.next()) {
+                (Some(short), None) => arg.get_short() == Some(short),
+                _ => false,
+            }
+        } else {
+            false
+        }
+    })
+}
+
+/// Filter expressions take words starting with a hyphen (`-hold`), so once a filter has begun
+/// clap would read the rest of the command line as part of it. Flags and options of the command
+/// that come after the filter words are moved in front of them, everything after `--` is left
+/// as is.
+fn hoist_flags(args: Vec<OsString>) -> Vec<OsString> {
+    let mut command = Cli::command();
+    command.build();
+
+    // skip the options given before the subcommand
+    let mut position = 1;
+    while let Some(token) = args.get(position) {
+        if !token.to_string_lossy().starts_with('-') {
+            break;
+        }
+        position += match find_flag(&command, token) {
+            Some(arg) if arg.get_action().takes_values() && !token.to_string_lossy().contains('=') => 2,
+            _ => 1,
+        };
+    }
+    let subcommand = match args
+        .get(position)
+        .and_then(|name| command.find_subcommand(name))
+    {
+        Some(subcommand) => subcommand,
+        None => return args,
+    };
+    if !subcommand
+        .get_positionals()
+        .any(|arg| arg.is_allow_hyphen_values_set())
+    {
+        return args;
+    }
+
+    let (head, tail) = args.split_at(position + 1);
+    let mut flags: Vec<OsString> = vec![];
+    let mut rest: Vec<OsString> = vec![];
+    let mut tokens = tail.iter();
+    while let Some(token) = tokens.next() {
+        if token.as_os_str() == "--" {
+            rest.push(token.clone());
+            rest.extend(tokens.by_ref().cloned());
+            break;
+        }
+        match find_flag(subcommand, token) {
+            Some(arg) => {
+                flags.push(token.clone());
+                if arg.get_action().takes_values() && !token.to_string_lossy().contains('=') {
+                    flags.extend(tokens.next().cloned());
+                }
+            }
+            None => rest.push(token.clone()),
+        }
+    }
+
+    head.iter().cloned().chain(flags).chain(rest).collect()
 }
 
-fn main() -> Result<()> {
+fn main() {
     dotenv().ok();
 
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(hoist_flags(std::env::args_os().collect()));
+
+    if let Err(error) = run(cli) {
+        eprintln!("Error: {:?}", error);
+        exit(ExitStatus::from_error(&error).code());
+    }
+}
 
-    let settings = Settings::new(cli.namespace, cli.config.to_str().unwrap())
+fn run(cli: Cli) -> Result<()> {
+    let mut settings = Settings::new(cli.namespace, cli.config.to_str().unwrap())
         .with_context(|| "while loading settings")?;
+    if let Some(format) = cli.output {
+        settings.output.format = format;
+    }
 
-    if settings.output.namespace {
+    // machine readable output must not be mixed with informational lines
+    let machine_output = matches!(
+        &cli.command,
+        Some(Commands::Report {
+            format: ReportFormat::Csv | ReportFormat::Json,
+            ..
+        }) | Some(Commands::Export { .. })
+            | Some(Commands::Invoice { .. })
+    ) || settings.output.format.is_structured();
+    if settings.output.namespace && !machine_output {
         println!(" Namespace: '{}'", settings.namespace);
     }
 
     match &cli.command {
         Some(Commands::Set {
-            id,
+            selection,
             priority,
             due_date,
             tag,
             project,
             metadata,
-        }) => cli_set_characteristic(id, priority, due_date, tag, project, metadata, &settings),
+            estimate,
+            billable,
+            depends,
+            parent,
+        }) => cli_set_characteristic(
+            selection, priority, due_date, tag, project, metadata, estimate, billable, depends,
+            parent, &settings,
+        ),
         Some(Commands::Unset {
-            id,
+            selection,
             priority,
             due_date,
             tag,
             project,
             metadata,
-        }) => cli_unset_characteristic(id, priority, due_date, tag, project, metadata, &settings),
-        Some(Commands::New { descriptor }) => cli_new_task(descriptor.join(" "), &settings),
+            estimate,
+            billable,
+            depends,
+            parent,
+        }) => cli_unset_characteristic(
+            selection, priority, due_date, tag, project, metadata, estimate, billable, depends,
+            parent, &settings,
+        ),
+        Some(Commands::New { parent, descriptor }) => {
+            cli_new_task(descriptor.join(" "), parent, &settings)
+        }
         Some(Commands::Show { id }) => show_task(id, &settings),
         Some(Commands::Config) => show_config(&settings),
         Some(Commands::List {
             search,
             include_done,
-        }) => cli_list_tasks(search, include_done, &settings),
-        Some(Commands::Done { id }) => cli_complete_task(id, &settings),
-        Some(Commands::Delete { id, force }) => delete_task(id, force, &settings),
+            all_namespaces,
+        }) => {
+            if !search.is_empty() {
+                cli_list_tasks(
+                    &Some(search.join(" ")),
+                    include_done,
+                    all_namespaces,
+                    &settings,
+                )
+            } else {
+                cli_list_tasks(&None, include_done, all_namespaces, &settings)
+            }
+        }
+        Some(Commands::Done { selection }) => cli_complete_task(selection, &settings),
+        Some(Commands::Delete { selection }) => cli_delete_task(selection, &settings),
+        Some(Commands::Trash { command }) => match command {
+            TrashCommands::List => cli_list_trash(&settings),
+            TrashCommands::Restore { id } => cli_restore_from_trash(id, &settings),
+            TrashCommands::Purge { id, force } => cli_purge_trash(id, force, &settings),
+        },
+        Some(Commands::Archive {
+            older_than,
+            dry_run,
+        }) => cli_archive(older_than, dry_run, &settings),
         Some(Commands::Edit { id }) => edit_task(id, &settings),
         Some(Commands::Start { id, annotation }) => {
             if !annotation.is_empty() {
@@ -220,33 +827,205 @@ fn main() -> Result<()> {
             }
         }
         Some(Commands::Stop { id, done }) => cli_stop_task(id, done, &settings),
-        Some(Commands::Hold { id }) => cli_set_characteristic(
+        Some(Commands::Switch { id, annotation }) => cli_switch_task(
+            id,
+            &(!annotation.is_empty()).then(|| annotation.join(" ")),
+            &settings,
+        ),
+        Some(Commands::Current) => cli_current_tasks(&settings),
+        Some(Commands::Focus {
+            id,
+            length,
+            break_length,
+            cycles,
+        }) => cli_focus(id, length, break_length, cycles, &settings),
+        Some(Commands::Log {
             id,
+            from,
+            to,
+            duration,
+            annotation,
+        }) => cli_log_time(
+            id,
+            from,
+            to,
+            duration,
+            &(!annotation.is_empty()).then(|| annotation.join(" ")),
+            &settings,
+        ),
+        Some(Commands::Track { command }) => match command {
+            TrackCommands::List { id } => cli_list_spans(id, &settings),
+            TrackCommands::Edit {
+                id,
+                number,
+                from,
+                to,
+                annotation,
+            } => {
+                let from = from.map(local_time).transpose()?;
+                let to = to.map(local_time).transpose()?;
+                cli_change_spans(
+                    id,
+                    &format!("Time span {} changed", number),
+                    |task| edit_span(task, *number, &from, &to, annotation),
+                    &settings,
+                )
+            }
+            TrackCommands::Delete { id, number } => cli_change_spans(
+                id,
+                &format!("Time span {} deleted", number),
+                |task| delete_span(task, *number).map(|_| ()),
+                &settings,
+            ),
+            TrackCommands::Split { id, number, at } => {
+                let at = at.map(local_time).transpose()?;
+                cli_change_spans(
+                    id,
+                    &format!("Time span {} split", number),
+                    |task| {
+                        let at = match at {
+                            Some(at) => at,
+                            None => span_middle(task, *number)?,
+                        };
+                        split_span(task, *number, at)
+                    },
+                    &settings,
+                )
+            }
+            TrackCommands::Merge { id, number } => cli_change_spans(
+                id,
+                &format!("Time spans {} and {} merged", number, number + 1),
+                |task| merge_spans(task, *number),
+                &settings,
+            ),
+        },
+        Some(Commands::Hold { selection }) => cli_set_characteristic(
+            selection,
             &None,
             &None,
             &Some(vec!["hold".to_string()]),
             &None,
             &None,
+            &None,
+            &None,
+            &None,
+            &None,
             &settings,
         ),
-        Some(Commands::Next { id }) => cli_set_characteristic(
-            id,
+        Some(Commands::Next { selection }) => cli_set_characteristic(
+            selection,
             &None,
             &None,
             &Some(vec!["next".to_string()]),
             &None,
             &None,
+            &None,
+            &None,
+            &None,
+            &None,
             &settings,
         ),
+        Some(Commands::Tree { id, include_done }) => cli_task_tree(id, include_done, &settings),
+        #[cfg(feature = "tui")]
+        Some(Commands::Tui) => run_tui(&settings),
         Some(Commands::Tags) => cli_list_tags(&settings),
         Some(Commands::Projects) => cli_list_projects(&settings),
-        None => cli_list_tasks(&None, &false, &settings),
+        Some(Commands::Recur { command }) => match command {
+            RecurCommands::List => cli_list_recurrences(&settings),
+            RecurCommands::Set { id, rule } => cli_set_recurrence(id, rule, &settings),
+            RecurCommands::Stop { id } => cli_stop_recurrence(id, &settings),
+        },
+        Some(Commands::Import { command }) => match command {
+            ImportCommands::Taskwarrior { file, dry_run } => {
+                cli_import_taskwarrior(file, dry_run, &settings)
+            }
+            ImportCommands::Ical { file, dry_run } => cli_import_ical(file, dry_run, &settings),
+        },
+        Some(Commands::Export {
+            search,
+            include_done,
+            all_namespaces,
+            format,
+        }) => {
+            let search = if !search.is_empty() {
+                Some(search.join(" "))
+            } else {
+                None
+            };
+            cli_export(&search, include_done, all_namespaces, format, &settings)
+        }
+        Some(Commands::Reindex) => cli_reindex(&settings),
+        Some(Commands::Undo) => cli_undo(&settings),
+        Some(Commands::Redo) => cli_redo(&settings),
+        Some(Commands::History { limit }) => cli_history(limit, &settings),
+        Some(Commands::Namespace { command }) => match command {
+            NamespaceCommands::List => cli_list_namespaces(&settings),
+            NamespaceCommands::Create { name } => cli_create_namespace(name, &settings),
+            NamespaceCommands::Rename { name, new_name } => {
+                cli_rename_namespace(name, new_name, &settings)
+            }
+            NamespaceCommands::Delete { name, force } => {
+                cli_delete_namespace(name, force, &settings)
+            }
+            NamespaceCommands::Use { name } => cli_use_namespace(name, &settings),
+            NamespaceCommands::MoveTask { id, namespace } => {
+                cli_move_task(id, namespace, &settings)
+            }
+        },
+        Some(Commands::Db { command }) => match command {
+            DbCommands::Migrate { to, from } => cli_migrate(from, to, &settings),
+        },
+        Some(Commands::Score { id, explain }) => cli_score(id, explain, &settings),
+        Some(Commands::Report {
+            filter,
+            from,
+            to,
+            period,
+            group_by,
+            format,
+            focus,
+        }) => {
+            let filter = if !filter.is_empty() {
+                Some(filter.join(" "))
+            } else {
+                None
+            };
+            cli_report(
+                &filter, from, to, period, group_by, format, focus, &settings,
+            )
+        }
+        Some(Commands::Estimates { filter }) => {
+            let filter = if !filter.is_empty() {
+                Some(filter.join(" "))
+            } else {
+                None
+            };
+            cli_estimates(&filter, &settings)
+        }
+        Some(Commands::Invoice {
+            filter,
+            client,
+            month,
+            all_namespaces,
+            format,
+        }) => {
+            let filter = if !filter.is_empty() {
+                Some(filter.join(" "))
+            } else {
+                None
+            };
+            cli_invoice(&filter, client, month, all_namespaces, format, &settings)
+        }
+        None => cli_list_tasks(&None, &false, &false, &settings),
     }
 }
 
 fn cli_list_tags(settings: &Settings) -> Result<()> {
     let tags = scan_tags(settings)
         .with_context(|| "error while querying tags")?;
+    if print_structured(&tags.iter().collect::<BTreeMap<_, _>>(), settings)? {
+        return Ok(());
+    }
 
     let mut tag_cells = vec![];
     let mut found_tags_count = 0;
@@ -284,13 +1063,16 @@ fn cli_list_tags(settings: &Settings) -> Result<()> {
 }
 
 fn cli_list_projects(settings: &Settings) -> Result<()> {
-    let projects = scan_tags(settings)
-        .with_context(|| "error while querying tags")?;
+    let projects = scan_projects(settings).with_context(|| "error while querying projects")?;
+    if print_structured(&projects.iter().collect::<BTreeMap<_, _>>(), settings)? {
+        return Ok(());
+    }
 
     let mut project_cells = vec![];
     let mut found_projects_count = 0;
 
-    for project in projects {
+    // sorted by name so that subprojects follow their parent projects
+    for project in projects.into_iter().collect::<BTreeMap<_, _>>() {
         found_projects_count += 1;
 
         project_cells.push(vec![
@@ -316,129 +1098,1199 @@ fn cli_list_projects(settings: &Settings) -> Result<()> {
             println!("\n Number of projects: {}", found_projects_count);
         }
     } else {
-        println!("No tags");
+        println!("No projects");
     }
 
     Ok(())
 }
 
-fn cli_new_task(descriptor: String, settings: &Settings) -> Result<()> {
-    let task = new_task(descriptor, settings)?;
-    println!("Created a task '{}'", task.id);
+fn cli_new_task(descriptor: String, parent: &Option<String>, settings: &Settings) -> Result<()> {
+    let task = new_task(descriptor, parent, settings)?;
+    if !print_structured_task(&task, settings)? {
+        println!("Created a task '{}'", task.id);
+    }
     Ok(())
 }
 
-fn cli_list_tasks(search: &Option<String>, include_done: &bool, settings: &Settings) -> Result<()> {
-    let found_tasks = list_tasks(search, include_done, settings)?;
-    let total_tasks_count: usize = amount_of_tasks(settings, false)?;
+/// Settings of every namespace if asked for, otherwise just the current one
+fn namespace_settings(all_namespaces: &bool, settings: &Settings) -> Result<Vec<Settings>> {
+    if *all_namespaces {
+        Ok(list_namespaces(settings)?
+            .into_iter()
+            .map(|namespace| Settings {
+                namespace: namespace.name,
+                ..settings.clone()
+            })
+            .collect())
+    } else {
+        Ok(vec![settings.clone()])
+    }
+}
 
-    let mut task_cells = vec![];
-    let mut found_tasks_count: usize = 0;
-    for found_task in found_tasks {
-        found_tasks_count += 1;
+/// Tasks matching the search in their exported form, from every namespace if asked for
+fn export_namespaces(
+    search: &Option<String>,
+    include_done: &bool,
+    all_namespaces: &bool,
+    settings: &Settings,
+) -> Result<Vec<ExportedTask>> {
+    let mut exported = vec![];
+    for settings in namespace_settings(all_namespaces, settings)? {
+        let tasks = list_tasks_with_archive(search, include_done, &settings)?;
+        exported.append(&mut export_tasks(&tasks, &settings)?);
+    }
+    Ok(exported)
+}
+
+/// Print the task as JSON or YAML in the same form as `tsk export` if the output format asks for
+/// it. Returns false if nothing was printed.
+fn print_structured_task(task: &Task, settings: &Settings) -> Result<bool> {
+    if !settings.output.format.is_structured() {
+        return Ok(false);
+    }
+    let exported = export_tasks(std::slice::from_ref(task), settings)?;
+    print_structured(&exported[0], settings)
+}
+
+/// Print the tasks as JSON or YAML if the output format asks for it, as a list if several tasks
+/// were selected. Returns false if nothing was printed.
+fn print_structured_tasks(
+    tasks: &[Task],
+    selection: &TaskSelection,
+    settings: &Settings,
+) -> Result<bool> {
+    if !selection.is_bulk() {
+        return match tasks.first() {
+            Some(task) => print_structured_task(task, settings),
+            None => Ok(false),
+        };
+    }
+    if !settings.output.format.is_structured() {
+        return Ok(false);
+    }
+    print_structured(&export_tasks(tasks, settings)?, settings)
+}
+
+/// Select the tasks a command is applied to. When several tasks are selected they are listed
+/// first and the user has to confirm the change, unless it is forced. Returns no tasks if the
+/// user declined.
+fn confirm_selection(
+    selection: &TaskSelection,
+    action: &str,
+    settings: &Settings,
+) -> Result<Vec<Task>> {
+    let tasks = select_tasks(&selection.ids, &selection.filter, settings)?;
+    if !selection.is_bulk() || selection.force || tasks.is_empty() {
+        return Ok(tasks);
+    }
+
+    if !settings.output.format.is_structured() {
+        let mut task_cells = vec![];
+        for task in &tasks {
+            let desc = truncate_description(&task.description, settings);
+            task_cells.push(vec![
+                task.short_id()
+                    .map(|short_id| short_id.to_string())
+                    .unwrap_or_default()
+                    .cell(),
+                desc.cell(),
+                task.project.clone().unwrap_or_default().cell(),
+            ]);
+        }
+        let tasks_table = task_cells
+            .table()
+            .title(vec![
+                "ID".cell().bold(true).underline(true),
+                "Description".cell().bold(true).underline(true),
+                "Project".cell().bold(true).underline(true),
+            ])
+            .border(Border::builder().build())
+            .separator(Separator::builder().build());
+        print_stdout(tasks_table)
+            .with_context(|| "while trying to print out pretty table of task(s)")?;
+        println!("\n {} task(s) will be {}.", tasks.len(), action);
+    }
+
+    let answer = Question::new(&format!("Apply to these {} task(s)?", tasks.len()))
+        .default(Answer::NO)
+        .show_defaults()
+        .confirm();
+    if answer == Answer::YES {
+        Ok(tasks)
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn cli_export(
+    search: &Option<String>,
+    include_done: &bool,
+    all_namespaces: &bool,
+    format: &ExportFormat,
+    settings: &Settings,
+) -> Result<()> {
+    let exported = export_namespaces(search, include_done, all_namespaces, settings)?;
+    write_export(exported, format, std::io::stdout().lock())
+}
+
+fn cli_list_tasks(
+    search: &Option<String>,
+    include_done: &bool,
+    all_namespaces: &bool,
+    settings: &Settings,
+) -> Result<()> {
+    if settings.output.format.is_structured() {
+        let exported = export_namespaces(search, include_done, all_namespaces, settings)?;
+        print_structured(&exported, settings)?;
+        return Ok(());
+    }
+    let namespace_settings = namespace_settings(all_namespaces, settings)?;
+
+    let now = Local::now();
+    let mut task_cells = vec![];
+    let mut found_tasks_count: usize = 0;
+    let mut total_tasks_count: usize = 0;
+    for settings in &namespace_settings {
+        let found_tasks = list_tasks_with_archive(search, include_done, settings)?;
+        // blocked tasks are worked out from the summaries once per namespace
+        let open_task_ids = open_task_ids(settings)?;
+        total_tasks_count += amount_of_tasks(settings, false)?;
+        if *include_done {
+            total_tasks_count += archived_summaries(settings)?.len();
+        }
+
+        for found_task in found_tasks {
+            found_tasks_count += 1;
+
+            let runtime_str = if found_task.is_running() {
+                let runtime = found_task.current_runtime().unwrap();
+                Hhmmss::hhmmss(&runtime)
+            } else {
+                "[stopped]".to_string()
+            };
+            let mut cell_color: Option<Color> = None;
+            let default_score = "0".to_string();
+            let scorestr = found_task.metadata.get("tsk-rs-task-score").unwrap_or(&default_score);
+            let mut score = scorestr.parse::<usize>().with_context(|| "error while converting score to number")?;
+            // adjust the score with user configurable multiplier
+            score = (score as f64 * settings.output.scoremultiplier) as usize;
+            if settings.output.colors {
+                cell_color = match score {
+                    7..=12 => Some(Color::Green),
+                    13..=18 => Some(Color::Yellow),
+                    n if n >= 19 => Some(Color::Red),
+                    _ => None,
+                };
+            }
+
+            let mut desc = truncate_description(&found_task.description, settings);
+
+            let description = if let Some(tags) = found_task.tags.clone() {
+                if settings.task.specialvisible {
+                    // make special tags visible
+                    if tags.contains(&"next".to_string()) {
+                        desc = format!("{} #next", desc);
+                    }
+                    if tags.contains(&"hold".to_string()) {
+                        desc = format!("{} #hold", desc);
+                    }
+                    if tags.contains(&"start".to_string()) {
+                        desc = format!("{} #start", desc);
+                    }
+                }
+                desc
+            } else {
+                desc
+            };
+            let description = if found_task.is_blocked_by(&open_task_ids) {
+                format!("{} [blocked]", description)
+            } else {
+                description
+            };
+            let (remaining_str, remaining_color) = match found_task.remaining_effort(now) {
+                Some(remaining) if remaining < Duration::zero() => (
+                    format!("over {}", (-remaining).hhmmss()),
+                    if settings.output.colors {
+                        Some(Color::Red)
+                    } else {
+                        cell_color
+                    },
+                ),
+                Some(remaining) => (remaining.hhmmss(), cell_color),
+                None => ("".to_string(), cell_color),
+            };
+
+            let mut row = vec![];
+            if *all_namespaces {
+                row.push(
+                    settings
+                        .namespace
+                        .clone()
+                        .cell()
+                        .foreground_color(cell_color),
+                );
+            }
+            row.append(&mut vec![
+                found_task
+                    .short_id()
+                    .map(|short_id| short_id.to_string())
+                    .unwrap_or_default()
+                    .cell()
+                    .foreground_color(cell_color),
+                found_task.id.cell().foreground_color(cell_color),
+                description.cell().foreground_color(cell_color),
+                found_task
+                    .project
+                    .clone()
+                    .unwrap_or_else(|| "".to_string())
+                    .cell()
+                    .foreground_color(cell_color),
+                score.cell().foreground_color(cell_color),
+                remaining_str.cell().foreground_color(remaining_color),
+                runtime_str.cell().foreground_color(cell_color),
+            ]);
+            task_cells.push(row);
+        }
+    }
+
+    if !task_cells.is_empty() {
+        let mut titles = vec![];
+        if *all_namespaces {
+            titles.push("Namespace".cell().bold(true).underline(true));
+        }
+        titles.append(&mut vec![
+            "ID".cell().bold(true).underline(true),
+            "Task ID".cell().bold(true).underline(true),
+            "Description".cell().bold(true).underline(true),
+            "Project".cell().bold(true).underline(true),
+            "Score".cell().bold(true).underline(true),
+            "Remaining".cell().bold(true).underline(true),
+            "Cur. runtime".cell().bold(true).underline(true),
+        ]);
+        let tasks_table = task_cells
+            .table()
+            .title(titles) // headers of the table
+            .border(Border::builder().build())
+            .separator(Separator::builder().build()); // empty border around the table
+        print_stdout(tasks_table)
+            .with_context(|| "while trying to print out pretty table of task(s)")?;
+        if settings.output.totals {
+            println!(
+                "\n Number of tasks: {}/{}",
+                found_tasks_count, total_tasks_count
+            );
+        }
+    } else {
+        println!("No tasks");
+    }
+
+    Ok(())
+}
+
+fn cli_complete_task(selection: &TaskSelection, settings: &Settings) -> Result<()> {
+    let tasks = confirm_selection(selection, "marked as done", settings)?;
+    let completed =
+        complete_tasks(tasks, settings).with_context(|| "while marking tasks as completed")?;
+    let tasks: Vec<Task> = completed.iter().map(|(task, _)| task.clone()).collect();
+    if print_structured_tasks(&tasks, selection, settings)? {
+        return Ok(());
+    }
+    for (task, next_instance) in completed {
+        println!("Task '{}' now marked as done.", task.id);
+        if let Some(next_instance) = next_instance {
+            println!("Created next recurring instance '{}'", next_instance.id);
+        }
+    }
+
+    Ok(())
+}
+
+fn cli_list_recurrences(settings: &Settings) -> Result<()> {
+    let templates = list_recurrence_templates(settings)
+        .with_context(|| "error while querying recurrence templates")?;
+    let structured: Vec<_> = templates
+        .iter()
+        .map(|recurring| {
+            json!({
+                "id": recurring.template.id,
+                "description": recurring.template.description,
+                "rule": recurring.template.recurrence.as_ref().map(|rule| rule.to_string()),
+                "open_instance": recurring.open_instance.as_ref().map(|instance| instance.id),
+                "due": recurring
+                    .open_instance
+                    .as_ref()
+                    .and_then(|instance| instance.metadata.get("tsk-rs-task-due-time")),
+            })
+        })
+        .collect();
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
+
+    let mut template_cells = vec![];
+    for recurring in &templates {
+        let (instance_id, due) = if let Some(instance) = &recurring.open_instance {
+            (
+                instance.id.to_string(),
+                instance
+                    .metadata
+                    .get("tsk-rs-task-due-time")
+                    .cloned()
+                    .unwrap_or_default(),
+            )
+        } else {
+            ("".to_string(), "".to_string())
+        };
+
+        template_cells.push(vec![
+            recurring.template.id.cell(),
+            recurring.template.description.clone().cell(),
+            recurring
+                .template
+                .recurrence
+                .as_ref()
+                .map(|rule| rule.to_string())
+                .unwrap_or_default()
+                .cell(),
+            instance_id.cell(),
+            due.cell(),
+        ]);
+    }
+
+    if !template_cells.is_empty() {
+        let templates_table = template_cells
+            .table()
+            .title(vec![
+                "Template ID".cell().bold(true).underline(true),
+                "Description".cell().bold(true).underline(true),
+                "Rule".cell().bold(true).underline(true),
+                "Open instance".cell().bold(true).underline(true),
+                "Due".cell().bold(true).underline(true),
+            ]) // headers of the table
+            .border(Border::builder().build())
+            .separator(Separator::builder().build()); // empty border around the table
+
+        print_stdout(templates_table)
+            .with_context(|| "while trying to print out pretty table of recurrence template(s)")?;
+
+        if settings.output.totals {
+            println!("\n Number of recurring tasks: {}", templates.len());
+        }
+    } else {
+        println!("No recurring tasks");
+    }
+
+    Ok(())
+}
+
+fn cli_list_namespaces(settings: &Settings) -> Result<()> {
+    let mut namespace_cells = vec![];
+    let mut structured = vec![];
+    for namespace in list_namespaces(settings)? {
+        let namespace_settings = Settings {
+            namespace: namespace.name.clone(),
+            ..settings.clone()
+        };
+        let tasks = amount_of_tasks(&namespace_settings, false)?;
+        structured.push(json!({
+            "name": namespace.name,
+            "current": namespace.is_current,
+            "tasks": tasks,
+        }));
+        namespace_cells.push(vec![
+            if namespace.is_current { "*" } else { "" }.cell(),
+            namespace.name.cell(),
+            tasks.cell(),
+        ]);
+    }
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
+
+    let namespaces_table = namespace_cells
+        .table()
+        .title(vec![
+            "".cell(),
+            "Namespace".cell().bold(true).underline(true),
+            "Tasks".cell().bold(true).underline(true),
+        ]) // headers of the table
+        .border(Border::builder().build())
+        .separator(Separator::builder().build()); // empty border around the table
+    print_stdout(namespaces_table)
+        .with_context(|| "while trying to print out pretty table of namespace(s)")?;
+
+    Ok(())
+}
+
+fn cli_create_namespace(name: &str, settings: &Settings) -> Result<()> {
+    create_namespace(name, settings)?;
+    if !print_structured(&json!({ "namespace": name }), settings)? {
+        println!("Namespace '{}' created.", name);
+    }
+
+    Ok(())
+}
+
+fn cli_rename_namespace(name: &str, new_name: &str, settings: &Settings) -> Result<()> {
+    rename_namespace(name, new_name, settings)?;
+    if !print_structured(
+        &json!({ "namespace": new_name, "previous_name": name }),
+        settings,
+    )? {
+        println!("Namespace '{}' renamed to '{}'.", name, new_name);
+    }
+
+    Ok(())
+}
+
+fn cli_delete_namespace(name: &str, force: &bool, settings: &Settings) -> Result<()> {
+    let answer = if !force {
+        Question::new(&format!(
+            "Really delete namespace '{}' with all of its tasks and notes?",
+            name
+        ))
+        .default(Answer::NO)
+        .show_defaults()
+        .confirm()
+    } else {
+        Answer::YES
+    };
+
+    if answer == Answer::YES {
+        delete_namespace(name, settings).with_context(|| "while deleting namespace")?;
+        if !print_structured(&json!({ "namespace": name, "deleted": true }), settings)? {
+            println!("Namespace '{}' now deleted permanently.", name);
+        }
+    }
+
+    Ok(())
+}
+
+fn cli_use_namespace(name: &str, settings: &Settings) -> Result<()> {
+    set_current_namespace(name, settings)?;
+    if !print_structured(&json!({ "namespace": name, "current": true }), settings)? {
+        println!("Now using namespace '{}'.", name);
+    }
+
+    Ok(())
+}
+
+fn cli_move_task(id: &str, namespace: &str, settings: &Settings) -> Result<()> {
+    let task = move_task(id, namespace, settings)?;
+    let target_settings = Settings {
+        namespace: namespace.to_string(),
+        ..settings.clone()
+    };
+    if print_structured_task(&task, &target_settings)? {
+        return Ok(());
+    }
+    println!(
+        "Task '{}' moved to namespace '{}' with id {}.",
+        task.id,
+        namespace,
+        task.short_id().unwrap_or_default()
+    );
+
+    Ok(())
+}
+
+fn describe_operation(operation: &Operation) -> String {
+    let descriptions = operation.descriptions();
+    if descriptions.is_empty() {
+        format!("{} change(s)", operation.changes.len())
+    } else {
+        descriptions.join(", ")
+    }
+}
+
+/// Operation in the form it is printed as JSON or YAML
+fn structured_operation(operation: &Operation) -> serde_json::Value {
+    json!({
+        "group": operation.group,
+        "time": operation.time,
+        "command": operation.command,
+        "changes": operation.changes.len(),
+        "tasks": operation.descriptions(),
+        "undone": operation.undone,
+    })
+}
+
+fn cli_undo(settings: &Settings) -> Result<()> {
+    let operation = undo(settings)?;
+    if print_structured(&structured_operation(&operation), settings)? {
+        return Ok(());
+    }
+    println!(
+        "Undid '{}' from {}: {}",
+        operation.command,
+        operation.time.format("%Y-%m-%d %H:%M:%S"),
+        describe_operation(&operation)
+    );
+
+    Ok(())
+}
+
+fn cli_redo(settings: &Settings) -> Result<()> {
+    let operation = redo(settings)?;
+    if print_structured(&structured_operation(&operation), settings)? {
+        return Ok(());
+    }
+    println!(
+        "Redid '{}' from {}: {}",
+        operation.command,
+        operation.time.format("%Y-%m-%d %H:%M:%S"),
+        describe_operation(&operation)
+    );
+
+    Ok(())
+}
+
+fn cli_history(limit: &usize, settings: &Settings) -> Result<()> {
+    let operations: Vec<Operation> = history(settings)?.into_iter().rev().take(*limit).collect();
+    let structured: Vec<_> = operations.iter().map(structured_operation).collect();
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
+
+    let mut history_cells = vec![];
+    for operation in &operations {
+        history_cells.push(vec![
+            operation.time.format("%Y-%m-%d %H:%M:%S").cell(),
+            operation.command.clone().cell(),
+            describe_operation(operation).cell(),
+            if operation.undone { "[undone]" } else { "" }.cell(),
+        ]);
+    }
+
+    if !history_cells.is_empty() {
+        let history_table = history_cells
+            .table()
+            .title(vec![
+                "Time".cell().bold(true).underline(true),
+                "Command".cell().bold(true).underline(true),
+                "Changed".cell().bold(true).underline(true),
+                "".cell(),
+            ]) // headers of the table
+            .border(Border::builder().build())
+            .separator(Separator::builder().build()); // empty border around the table
+        print_stdout(history_table)
+            .with_context(|| "while trying to print out pretty table of history")?;
+    } else {
+        println!("No history");
+    }
+
+    Ok(())
+}
+
+fn print_import_results(
+    results: &[ImportResult],
+    dry_run: &bool,
+    settings: &Settings,
+) -> Result<()> {
+    let structured: Vec<_> = results
+        .iter()
+        .map(|result| {
+            let action: &str = result.action.into();
+            json!({
+                "action": action,
+                "id": result.task.id,
+                "description": result.task.description,
+            })
+        })
+        .collect();
+    if print_structured(
+        &json!({ "dry_run": dry_run, "results": structured }),
+        settings,
+    )? {
+        return Ok(());
+    }
+
+    let mut result_cells = vec![];
+    for result in results {
+        let action: &str = result.action.into();
+        result_cells.push(vec![
+            action.cell(),
+            result.task.id.cell(),
+            result.task.description.clone().cell(),
+        ]);
+    }
+
+    if !result_cells.is_empty() {
+        let results_table = result_cells
+            .table()
+            .title(vec![
+                "Action".cell().bold(true).underline(true),
+                "Task ID".cell().bold(true).underline(true),
+                "Description".cell().bold(true).underline(true),
+            ]) // headers of the table
+            .border(Border::builder().build())
+            .separator(Separator::builder().build()); // empty border around the table
+        print_stdout(results_table)
+            .with_context(|| "while trying to print out pretty table of imported task(s)")?;
+    }
+
+    let count = |action: ImportAction| {
+        results
+            .iter()
+            .filter(|result| result.action == action)
+            .count()
+    };
+    println!(
+        "\n {}{} created, {} updated, {} unchanged and {} skipped.",
+        if *dry_run { "Dry run, nothing saved: " } else { "" },
+        count(ImportAction::Create),
+        count(ImportAction::Update),
+        count(ImportAction::Unchanged),
+        count(ImportAction::Skip)
+    );
+
+    Ok(())
+}
+
+/// Read the file to import, or standard input if the file is -
+fn read_import_input(file: &PathBuf) -> Result<String> {
+    if file.to_str() == Some("-") {
+        let mut input = String::new();
+        stdin()
+            .read_to_string(&mut input)
+            .with_context(|| "while reading standard input")?;
+        Ok(input)
+    } else {
+        read_to_string(file).with_context(|| "while reading import file")
+    }
+}
+
+fn cli_import_taskwarrior(file: &PathBuf, dry_run: &bool, settings: &Settings) -> Result<()> {
+    let input = read_import_input(file)?;
+    let results = import_taskwarrior(&input, *dry_run, settings)?;
+    print_import_results(&results, dry_run, settings)
+}
+
+fn cli_import_ical(file: &PathBuf, dry_run: &bool, settings: &Settings) -> Result<()> {
+    let input = read_import_input(file)?;
+    let results = import_ical(&input, *dry_run, settings)?;
+    print_import_results(&results, dry_run, settings)
+}
+
+fn cli_reindex(settings: &Settings) -> Result<()> {
+    let amount = reindex(settings)?;
+    if !print_structured(&json!({ "indexed": amount }), settings)? {
+        println!("Indexed {} task(s).", amount);
+    }
+
+    Ok(())
+}
+
+fn cli_migrate(
+    from: &Option<StorageBackend>,
+    to: &StorageBackend,
+    settings: &Settings,
+) -> Result<()> {
+    let from = from.clone().unwrap_or(settings.data.backend.clone());
+    let summary = migrate(&from, to, settings).with_context(|| "while migrating storage")?;
+    let from_name: &str = from.into();
+    let to_name: &str = to.into();
+    if print_structured(
+        &json!({
+            "from": from_name,
+            "to": to_name,
+            "tasks": summary.tasks,
+            "notes": summary.notes,
+        }),
+        settings,
+    )? {
+        return Ok(());
+    }
+    println!(
+        "Copied {} task(s) and {} note(s) from {} to {} storage.",
+        summary.tasks, summary.notes, from_name, to_name
+    );
+    if settings.data.backend != *to {
+        println!(
+            "Set backend = \"{}\" in the [data] section of the configuration to start using it.",
+            to_name
+        );
+    }
+
+    Ok(())
+}
+
+fn cli_score(id: &str, explain: &bool, settings: &Settings) -> Result<()> {
+    let (task, terms) = explain_score(id, settings)?;
+    let score = total_score(&terms);
+    // adjust the score with user configurable multiplier just like in the listing
+    let multiplied = (score as f64 * settings.output.scoremultiplier) as usize;
+    if print_structured(
+        &json!({
+            "id": task.id,
+            "score": score,
+            "multiplier": settings.output.scoremultiplier,
+            "multiplied": multiplied,
+            "terms": terms,
+        }),
+        settings,
+    )? {
+        return Ok(());
+    }
+
+    if *explain {
+        let mut term_cells = vec![];
+        for term in &terms {
+            term_cells.push(vec![
+                term.reason.clone().cell(),
+                format!("{:+.2}", term.points).cell(),
+            ]);
+        }
+
+        if !term_cells.is_empty() {
+            let terms_table = term_cells
+                .table()
+                .title(vec![
+                    "Term".cell().bold(true).underline(true),
+                    "Points".cell().bold(true).underline(true),
+                ]) // headers of the table
+                .border(Border::builder().build())
+                .separator(Separator::builder().build()); // empty border around the table
+            print_stdout(terms_table)
+                .with_context(|| "while trying to print out pretty table of score terms")?;
+        } else {
+            println!("No terms contribute to the score");
+        }
+        println!();
+    }
+
+    if settings.output.scoremultiplier != 1.0 {
+        println!(
+            " Score of '{}': {} ({} x {})",
+            task.id, multiplied, score, settings.output.scoremultiplier
+        );
+    } else {
+        println!(" Score of '{}': {}", task.id, score);
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cli_report(
+    filter: &Option<String>,
+    from: &Option<NaiveDate>,
+    to: &Option<NaiveDate>,
+    period: &ReportPeriod,
+    grouping: &ReportGrouping,
+    format: &ReportFormat,
+    focus: &bool,
+    settings: &Settings,
+) -> Result<()> {
+    let now = Local::now();
+    let to = to.unwrap_or_else(|| now.date_naive());
+    let from = from.unwrap_or_else(|| period.start_of(to));
+    if from > to {
+        bail!(ReportError::InvalidRange(from, to));
+    }
+
+    // time tracked to completed and archived tasks is reported as well
+    let tasks = list_tasks_with_archive(filter, &true, settings)?;
+    if *focus {
+        return cli_focus_report(&tasks, from, to, period, format, settings);
+    }
+    let entries = time_report(&tasks, from, to, period, grouping, now);
+
+    match format {
+        ReportFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&entries)
+                    .with_context(|| "while serializing report to json")?
+            );
+        }
+        ReportFormat::Csv => {
+            // header is written explicitly so that an empty report still has one
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(std::io::stdout());
+            writer
+                .write_record(["period", "period_start", "group", "seconds"])
+                .with_context(|| "while writing report as csv")?;
+            for entry in &entries {
+                writer
+                    .serialize(entry)
+                    .with_context(|| "while writing report as csv")?;
+            }
+            writer.flush().with_context(|| "while writing report as csv")?;
+        }
+        ReportFormat::Table => {
+            if print_structured(&entries, settings)? {
+                return Ok(());
+            }
+            let mut report_cells = vec![];
+            for entry in &entries {
+                report_cells.push(vec![
+                    entry.period.clone().cell(),
+                    entry.group.clone().cell(),
+                    Duration::seconds(entry.seconds).hhmmss().cell(),
+                ]);
+            }
+
+            if !report_cells.is_empty() {
+                let report_table = report_cells
+                    .table()
+                    .title(vec![
+                        "Period".cell().bold(true).underline(true),
+                        match grouping {
+                            ReportGrouping::Project => "Project".to_string(),
+                            ReportGrouping::Tag => "Tag".to_string(),
+                            ReportGrouping::Metadata(key) => key.clone(),
+                        }
+                        .cell()
+                        .bold(true)
+                        .underline(true),
+                        "Time".cell().bold(true).underline(true),
+                    ]) // headers of the table
+                    .border(Border::builder().build())
+                    .separator(Separator::builder().build()); // empty border around the table
+
+                print_stdout(report_table)
+                    .with_context(|| "while trying to print out pretty table of time report")?;
+
+                if settings.output.totals {
+                    // with tag grouping the same time can be on several rows, while every task
+                    //  belongs to exactly one project group
+                    let total: i64 = time_report(
+                        &tasks,
+                        from,
+                        to,
+                        period,
+                        &ReportGrouping::Project,
+                        now,
+                    )
+                    .iter()
+                    .map(|entry| entry.seconds)
+                    .sum();
+                    println!(
+                        "\n Total time tracked {} - {}: {}",
+                        from,
+                        to,
+                        Duration::seconds(total).hhmmss()
+                    );
+                }
+            } else {
+                println!("No time tracked between {} and {}", from, to);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cli_focus_report(
+    tasks: &[Task],
+    from: NaiveDate,
+    to: NaiveDate,
+    period: &ReportPeriod,
+    format: &ReportFormat,
+    settings: &Settings,
+) -> Result<()> {
+    let entries = focus_report(tasks, from, to, period);
+
+    match format {
+        ReportFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&entries)
+                    .with_context(|| "while serializing focus report to json")?
+            );
+        }
+        ReportFormat::Csv => {
+            // header is written explicitly so that an empty report still has one
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(std::io::stdout());
+            writer
+                .write_record([
+                    "period",
+                    "period_start",
+                    "task_id",
+                    "task",
+                    "pomodoros",
+                    "total_pomodoros",
+                    "seconds",
+                ])
+                .with_context(|| "while writing focus report as csv")?;
+            for entry in &entries {
+                writer
+                    .serialize(entry)
+                    .with_context(|| "while writing focus report as csv")?;
+            }
+            writer
+                .flush()
+                .with_context(|| "while writing focus report as csv")?;
+        }
+        ReportFormat::Table => {
+            if print_structured(&entries, settings)? {
+                return Ok(());
+            }
+            if entries.is_empty() {
+                println!("No pomodoros completed between {} and {}", from, to);
+                return Ok(());
+            }
+            let mut report_cells = vec![];
+            for entry in &entries {
+                report_cells.push(vec![
+                    entry.period.clone().cell(),
+                    entry.task.clone().cell(),
+                    entry.pomodoros.cell(),
+                    Duration::seconds(entry.seconds).hhmmss().cell(),
+                    entry.total_pomodoros.cell(),
+                ]);
+            }
+            let report_table = report_cells
+                .table()
+                .title(vec![
+                    "Period".cell().bold(true).underline(true),
+                    "Task".cell().bold(true).underline(true),
+                    "Pomodoros".cell().bold(true).underline(true),
+                    "Focus time".cell().bold(true).underline(true),
+                    "All time".cell().bold(true).underline(true),
+                ]) // headers of the table
+                .border(Border::builder().build())
+                .separator(Separator::builder().build()); // empty border around the table
+            print_stdout(report_table)
+                .with_context(|| "while trying to print out pretty table of focus report")?;
+            if settings.output.totals {
+                println!(
+                    "\n Pomodoros completed {} - {}: {}",
+                    from,
+                    to,
+                    entries.iter().map(|entry| entry.pomodoros).sum::<usize>()
+                );
+            }
+        }
+    }
 
-        let runtime_str = if found_task.is_running() {
-            let runtime = found_task.current_runtime().unwrap();
-            Hhmmss::hhmmss(&runtime)
-        } else {
-            "[stopped]".to_string()
-        };
-        let mut cell_color: Option<Color> = None;
-        let default_score = "0".to_string();
-        let scorestr = found_task.metadata.get("tsk-rs-task-score").unwrap_or(&default_score);
-        let mut score = scorestr.parse::<usize>().with_context(|| "error while converting score to number")?;
-        // adjust the score with user configurable multiplier
-        score = (score as f64 * settings.output.scoremultiplier) as usize;
-        if settings.output.colors {
-            cell_color = match score {
-                7..=12 => Some(Color::Green),
-                13..=18 => Some(Color::Yellow),
-                n if n >= 19 => Some(Color::Red),
-                _ => None,
-            };
+    Ok(())
+}
+
+fn cli_estimates(filter: &Option<String>, settings: &Settings) -> Result<()> {
+    // completed tasks are often archived by the time their estimates are compared
+    let tasks = list_tasks_with_archive(filter, &true, settings)?;
+    let (projects, total) = estimate_accuracy(&tasks, Local::now());
+    if print_structured(&json!({ "projects": projects, "total": total }), settings)? {
+        return Ok(());
+    }
+    if projects.is_empty() {
+        println!("No completed tasks with an estimate");
+        return Ok(());
+    }
+
+    let accuracy_row = |accuracy: &EstimateAccuracy| {
+        vec![
+            accuracy.project.clone().cell(),
+            accuracy.tasks.cell(),
+            accuracy.over_estimate.cell(),
+            Duration::seconds(accuracy.estimated_seconds)
+                .hhmmss()
+                .cell(),
+            Duration::seconds(accuracy.actual_seconds).hhmmss().cell(),
+            format!("{:.0}%", accuracy.ratio * 100.0).cell(),
+        ]
+    };
+    let estimates_table = projects
+        .iter()
+        .map(accuracy_row)
+        .collect::<Vec<_>>()
+        .table()
+        .title(vec![
+            "Project".cell().bold(true).underline(true),
+            "Tasks".cell().bold(true).underline(true),
+            "Over".cell().bold(true).underline(true),
+            "Estimated".cell().bold(true).underline(true),
+            "Actual".cell().bold(true).underline(true),
+            "Actual/Est.".cell().bold(true).underline(true),
+        ]) // headers of the table
+        .border(Border::builder().build())
+        .separator(Separator::builder().build()); // empty border around the table
+    print_stdout(estimates_table)
+        .with_context(|| "while trying to print out pretty table of estimates")?;
+    if settings.output.totals {
+        println!(
+            "\n Total of {} task(s): estimated {}, actual {} ({:.0}%)",
+            total.tasks,
+            Duration::seconds(total.estimated_seconds).hhmmss(),
+            Duration::seconds(total.actual_seconds).hhmmss(),
+            total.ratio * 100.0
+        );
+    }
+
+    Ok(())
+}
+
+fn cli_invoice(
+    filter: &Option<String>,
+    client: &Option<String>,
+    month: &Option<NaiveDate>,
+    all_namespaces: &bool,
+    format: &InvoiceFormat,
+    settings: &Settings,
+) -> Result<()> {
+    let month = month.unwrap_or_else(|| Local::now().date_naive());
+    // time tracked to completed and archived tasks is billed as well
+    let mut tasks = vec![];
+    for settings in namespace_settings(all_namespaces, settings)? {
+        tasks.append(&mut list_tasks_with_archive(filter, &true, &settings)?);
+    }
+    let invoice = build_invoice(&tasks, client, month, &settings.billing);
+    if print_structured(&invoice, settings)? {
+        return Ok(());
+    }
+
+    match format {
+        InvoiceFormat::Markdown => print!("{}", invoice.to_markdown()),
+        InvoiceFormat::Csv => invoice.write_csv(std::io::stdout())?,
+    }
+
+    Ok(())
+}
+
+fn cli_set_recurrence(id: &str, rule: &Recurrence, settings: &Settings) -> Result<()> {
+    let mut task = load_task(id, settings)?;
+    task.recurrence = Some(rule.clone());
+    save_task(&mut task, settings)?;
+    if !print_structured_task(&task, settings)? {
+        println!("Task '{}' now recurs {}", task.id, rule);
+    }
+
+    Ok(())
+}
+
+fn cli_stop_recurrence(id: &str, settings: &Settings) -> Result<()> {
+    let modified = stop_recurrence(id, settings)?;
+    if !print_structured(&json!({ "modified": modified }), settings)? {
+        println!("Recurrence stopped, {} task(s) modified", modified);
+    }
+
+    Ok(())
+}
+
+fn cli_delete_task(selection: &TaskSelection, settings: &Settings) -> Result<()> {
+    let tasks = confirm_selection(selection, "moved to trash", settings)?;
+
+    // a single task is confirmed here, several tasks were listed and confirmed already
+    let answer = if !selection.force && !selection.is_bulk() {
+        Question::new("Really delete this task?")
+            .default(Answer::NO)
+            .show_defaults()
+            .confirm()
+    } else {
+        Answer::YES
+    };
+
+    if answer == Answer::YES {
+        trash_tasks(&tasks, settings).with_context(|| "while deleting tasks")?;
+        if !print_structured_tasks(&tasks, selection, settings)? {
+            for task in &tasks {
+                println!("Task '{}' moved to trash.", task.id);
+            }
         }
+    }
 
-        let mut desc = found_task.description.clone();
-        if desc.len() > settings.output.descriptionlength + 3 {
-            // if the desc truncated to max length plus three dot characters is
-            //  shorter than the max len then truncate it and add those three dots
-            desc = format!("{}...", &desc[..settings.output.descriptionlength]);
+    Ok(())
+}
+
+fn cli_list_trash(settings: &Settings) -> Result<()> {
+    let entries = list_trash(settings)?;
+    if settings.output.format.is_structured() {
+        let now = Local::now();
+        let mut structured = vec![];
+        for entry in &entries {
+            #[cfg(feature = "note")]
+            let note = entry.note.as_ref().and_then(|note| note.markdown.clone());
+            #[cfg(not(feature = "note"))]
+            let note = None;
+            let task = match &entry.task {
+                Some(task) => Some(ExportedTask::from_task(
+                    task,
+                    &settings.namespace,
+                    note.clone(),
+                    now,
+                )?),
+                None => None,
+            };
+            structured.push(json!({
+                "id": entry.id,
+                "deleted": entry.deleted,
+                "task": task,
+                "note": note,
+            }));
         }
+        print_structured(&structured, settings)?;
+        return Ok(());
+    }
 
-        let description = if let Some(tags) = found_task.tags.clone() {
-            if settings.task.specialvisible {
-                // make special tags visible
-                if tags.contains(&"next".to_string()) {
-                    desc = format!("{} #next", desc);
-                }
-                if tags.contains(&"hold".to_string()) {
-                    desc = format!("{} #hold", desc);
-                }
-                if tags.contains(&"start".to_string()) {
-                    desc = format!("{} #start", desc);
-                }
-            }
-            desc
-        } else {
-            desc
+    let mut trash_cells = vec![];
+    for entry in entries {
+        let (short_id, description) = match &entry.task {
+            Some(task) => (
+                task.short_id()
+                    .map(|short_id| short_id.to_string())
+                    .unwrap_or_default(),
+                task.description.clone(),
+            ),
+            None => ("".to_string(), "[note only]".to_string()),
         };
-
-        task_cells.push(vec![
-            found_task.id.cell().foreground_color(cell_color),
-            description.cell().foreground_color(cell_color),
-            found_task
-                .project
-                .clone()
-                .unwrap_or_else(|| "".to_string())
-                .cell()
-                .foreground_color(cell_color),
-            score.cell().foreground_color(cell_color),
-            runtime_str.cell().foreground_color(cell_color),
+        trash_cells.push(vec![
+            short_id.cell(),
+            entry.id.cell(),
+            description.cell(),
+            entry.deleted.format("%Y-%m-%d %H:%M:%S").cell(),
         ]);
     }
 
-    if !task_cells.is_empty() {
-        let tasks_table = task_cells
+    if !trash_cells.is_empty() {
+        let trash_count = trash_cells.len();
+        let trash_table = trash_cells
             .table()
             .title(vec![
+                "ID".cell().bold(true).underline(true),
                 "Task ID".cell().bold(true).underline(true),
                 "Description".cell().bold(true).underline(true),
-                "Project".cell().bold(true).underline(true),
-                "Score".cell().bold(true).underline(true),
-                "Cur. runtime".cell().bold(true).underline(true),
+                "Deleted".cell().bold(true).underline(true),
             ]) // headers of the table
             .border(Border::builder().build())
             .separator(Separator::builder().build()); // empty border around the table
-        print_stdout(tasks_table)
-            .with_context(|| "while trying to print out pretty table of task(s)")?;
+        print_stdout(trash_table)
+            .with_context(|| "while trying to print out pretty table of trash")?;
         if settings.output.totals {
-            println!(
-                "\n Number of tasks: {}/{}",
-                found_tasks_count, total_tasks_count
-            );
+            println!("\n Number of entries in trash: {}", trash_count);
         }
     } else {
-        println!("No tasks");
+        println!("Trash is empty");
     }
 
     Ok(())
 }
 
-fn cli_complete_task(id: &String, settings: &Settings) -> Result<()> {
-    let mut task = load_task(id, settings)?;
-    task.mark_as_completed()
-        .with_context(|| "while marking task as completed")?;
-    save_task(&mut task, settings).with_context(|| "while saving modified task yaml file")?;
-    println!("Task '{}' now marked as done.", task.id);
+fn cli_restore_from_trash(id: &str, settings: &Settings) -> Result<()> {
+    let entry = restore_from_trash(id, settings)?;
+    if let Some(task) = &entry.task {
+        if print_structured_task(task, settings)? {
+            return Ok(());
+        }
+    } else if print_structured(&json!({ "id": entry.id }), settings)? {
+        return Ok(());
+    }
+    println!("Task '{}' restored from trash.", entry.id);
 
     Ok(())
 }
 
-fn delete_task(id: &String, force: &bool, settings: &Settings) -> Result<()> {
-    let task = load_task(id, settings)?;
-
+fn cli_purge_trash(id: &Option<String>, force: &bool, settings: &Settings) -> Result<()> {
+    let question = match id {
+        Some(_) => "Really delete this entry from trash permanently?",
+        None => "Really delete everything in trash permanently?",
+    };
     let answer = if !force {
-        Question::new("Really delete this task?")
+        Question::new(question)
             .default(Answer::NO)
             .show_defaults()
             .confirm()
@@ -447,15 +2299,52 @@ fn delete_task(id: &String, force: &bool, settings: &Settings) -> Result<()> {
     };
 
     if answer == Answer::YES {
-        remove_file(task_pathbuf_from_task(&task, settings)?)
-            .with_context(|| "while deleting task yaml file")?;
-        println!("Task '{}' now deleted permanently.", task.id);
+        let purged = purge_trash(id, settings).with_context(|| "while purging trash")?;
+        if !print_structured(&json!({ "purged": purged }), settings)? {
+            println!("{} entries deleted from trash permanently.", purged);
+        }
+    }
+
+    Ok(())
+}
+
+fn cli_archive(older_than: &Duration, dry_run: &bool, settings: &Settings) -> Result<()> {
+    let months = archive_tasks(*older_than, *dry_run, settings)?;
+    if print_structured(
+        &json!({
+            "dry_run": dry_run,
+            "months": months
+                .iter()
+                .map(|(month, tasks)| {
+                    (
+                        month.clone(),
+                        tasks.iter().map(|task| task.id).collect::<Vec<Uuid>>(),
+                    )
+                })
+                .collect::<BTreeMap<String, Vec<Uuid>>>(),
+        }),
+        settings,
+    )? {
+        return Ok(());
+    }
+
+    if months.is_empty() {
+        println!("No tasks to archive");
+        return Ok(());
     }
+    for (month, tasks) in &months {
+        println!(" {}: {} task(s)", month, tasks.len());
+    }
+    println!(
+        "\n {}{} task(s) archived.",
+        if *dry_run { "Dry run, nothing archived: " } else { "" },
+        months.values().map(Vec::len).sum::<usize>()
+    );
 
     Ok(())
 }
 
-fn edit_task(id: &String, settings: &Settings) -> Result<()> {
+fn edit_task(id: &str, settings: &Settings) -> Result<()> {
     let mut task = load_task(id, settings)?;
 
     let mut modified = false;
@@ -472,28 +2361,484 @@ fn edit_task(id: &String, settings: &Settings) -> Result<()> {
 
     if modified {
         save_task(&mut task, settings).with_context(|| "while saving modified task yaml file")?;
+    }
+    if !print_structured_task(&task, settings)? && modified {
         println!("Task '{}' was updated.", task.id);
     }
 
     Ok(())
 }
 
-fn cli_start_task(id: &String, annotation: &Option<String>, settings: &Settings) -> Result<()> {
+fn cli_start_task(id: &str, annotation: &Option<String>, settings: &Settings) -> Result<()> {
     let task = start_task(id, annotation, settings)?;
+    if print_structured_task(&task, settings)? {
+        return Ok(());
+    }
     println!("Started time tracking for task '{}'", task.id);
+    if task.is_blocked(settings)? {
+        println!("Warning: task is blocked by unfinished dependencies");
+    }
 
     Ok(())
 }
 
-fn cli_stop_task(id: &String, done: &bool, settings: &Settings) -> Result<()> {
+fn cli_stop_task(id: &str, done: &bool, settings: &Settings) -> Result<()> {
     let task = stop_task(id, done, settings)?;
-    println!("Stopped time tracking for task '{}'", task.id);
+    if !print_structured_task(&task, settings)? {
+        println!("Stopped time tracking for task '{}'", task.id);
+        warn_over_estimate(&task);
+    }
+
+    Ok(())
+}
+
+/// Count down on a single terminal line until the time is up. Returns false if the countdown was
+/// interrupted.
+fn countdown(label: &str, length: &Duration, interrupted: &AtomicBool, quiet: bool) -> bool {
+    let end = Local::now() + *length;
+    loop {
+        if interrupted.load(Ordering::Relaxed) {
+            if !quiet {
+                println!();
+            }
+            return false;
+        }
+        let left = end - Local::now();
+        if left <= Duration::zero() {
+            if !quiet {
+                // ring the terminal bell when the time is up
+                println!("\r {} {}\x07", label, Duration::zero().hhmmss());
+            }
+            return true;
+        }
+        if !quiet {
+            let seconds_left = Duration::seconds((left.num_milliseconds() + 999) / 1000);
+            print!("\r {} {} ", label, seconds_left.hhmmss());
+            _ = stdout().flush();
+        }
+        sleep(
+            left.to_std()
+                .unwrap_or_default()
+                .min(std::time::Duration::from_millis(200)),
+        );
+    }
+}
+
+fn cli_focus(
+    id: &str,
+    length: &Duration,
+    break_length: &Duration,
+    cycles: &usize,
+    settings: &Settings,
+) -> Result<()> {
+    validate_session(length, *cycles)?;
+    // interrupting stops the time tracking instead of leaving it running
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))
+        .with_context(|| "while setting up interrupt handler")?;
+    let quiet = settings.output.format.is_structured();
+
+    let id = load_task(id, settings)?.id.to_string();
+    for cycle in 1..=*cycles {
+        let task = start_pomodoro(&id, cycle, *cycles, settings)?;
+        let completed = countdown(
+            &format!("Pomodoro {}/{} of '{}':", cycle, cycles, task.description),
+            length,
+            &interrupted,
+            quiet,
+        );
+        let task = finish_pomodoro(&id, completed, settings)?;
+        if !completed {
+            if !print_structured_task(&task, settings)? {
+                println!("Focus interrupted, pomodoro {}/{} not completed", cycle, cycles);
+            }
+            return Ok(());
+        }
+        if cycle == *cycles {
+            if !print_structured_task(&task, settings)? {
+                println!(
+                    "Focus session done, {} pomodoro(s) completed for task '{}'",
+                    task.pomodoros(),
+                    task.id
+                );
+            }
+            return Ok(());
+        }
+        if !countdown("Break:", break_length, &interrupted, quiet) {
+            if !print_structured_task(&task, settings)? {
+                println!("Focus interrupted during the break");
+            }
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+fn warn_over_estimate(task: &Task) {
+    let now = Local::now();
+    if let (Some(estimate), true) = (task.estimate(), task.is_over_estimate(now)) {
+        println!(
+            "Warning: tracked time {} exceeds the estimate {}",
+            task.tracked_time(now).hhmmss(),
+            estimate.hhmmss()
+        );
+    }
+}
+
+fn cli_switch_task(id: &str, annotation: &Option<String>, settings: &Settings) -> Result<()> {
+    let (stopped, task) = switch_task(id, annotation, settings)?;
+    if settings.output.format.is_structured() {
+        let now = Local::now();
+        let mut stopped_tasks = vec![];
+        for running in &stopped {
+            stopped_tasks.push(ExportedTask::from_task(
+                &running.task,
+                &running.namespace,
+                None,
+                now,
+            )?);
+        }
+        print_structured(
+            &json!({
+                "stopped": stopped_tasks,
+                "started": ExportedTask::from_task(&task, &settings.namespace, None, now)?,
+            }),
+            settings,
+        )?;
+        return Ok(());
+    }
+
+    for running in &stopped {
+        if running.namespace == settings.namespace {
+            println!("Stopped time tracking for task '{}'", running.task.id);
+        } else {
+            println!(
+                "Stopped time tracking for task '{}' in namespace '{}'",
+                running.task.id, running.namespace
+            );
+        }
+    }
+    println!("Started time tracking for task '{}'", task.id);
+
+    Ok(())
+}
+
+fn cli_current_tasks(settings: &Settings) -> Result<()> {
+    let running = running_tasks(true, settings)?;
+    if settings.output.format.is_structured() {
+        let now = Local::now();
+        let mut structured = vec![];
+        for running in &running {
+            let runtime = running.task.current_runtime();
+            structured.push(json!({
+                "namespace": running.namespace,
+                "runtime_seconds": runtime.map(|runtime| runtime.num_seconds()),
+                "task": ExportedTask::from_task(&running.task, &running.namespace, None, now)?,
+            }));
+        }
+        print_structured(&structured, settings)?;
+        return Ok(());
+    }
+
+    let mut running_cells = vec![];
+    for running in &running {
+        running_cells.push(vec![
+            running.namespace.clone().cell(),
+            running
+                .task
+                .short_id()
+                .map(|short_id| short_id.to_string())
+                .unwrap_or_default()
+                .cell(),
+            running.task.description.clone().cell(),
+            running
+                .task
+                .current_timetrack()
+                .map(|(_, track)| track.start_time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default()
+                .cell(),
+            running
+                .task
+                .current_runtime()
+                .map(|runtime| Hhmmss::hhmmss(&runtime))
+                .unwrap_or_default()
+                .cell(),
+        ]);
+    }
+
+    if running_cells.is_empty() {
+        println!("No task is running");
+        return Ok(());
+    }
+    let running_table = running_cells
+        .table()
+        .title(vec![
+            "Namespace".cell().bold(true).underline(true),
+            "ID".cell().bold(true).underline(true),
+            "Description".cell().bold(true).underline(true),
+            "Started".cell().bold(true).underline(true),
+            "Cur. runtime".cell().bold(true).underline(true),
+        ]) // headers of the table
+        .border(Border::builder().build())
+        .separator(Separator::builder().build()); // empty border around the table
+    print_stdout(running_table)
+        .with_context(|| "while trying to print out pretty table of running task(s)")?;
+
+    Ok(())
+}
+
+fn cli_log_time(
+    id: &str,
+    from: &Option<NaiveDateTime>,
+    to: &Option<NaiveDateTime>,
+    duration: &Option<Duration>,
+    annotation: &Option<String>,
+    settings: &Settings,
+) -> Result<()> {
+    let now = Local::now();
+    let (start_time, end_time) = match (from, duration) {
+        (Some(from), Some(duration)) => {
+            let start_time = local_time(*from)?;
+            match start_time.checked_add_signed(*duration) {
+                Some(end_time) => (start_time, end_time),
+                None => bail!(TrackError::OutOfRange),
+            }
+        }
+        (Some(from), None) => (
+            local_time(*from)?,
+            match to {
+                Some(to) => local_time(*to)?,
+                None => now,
+            },
+        ),
+        (None, Some(duration)) => match now.checked_sub_signed(*duration) {
+            Some(start_time) => (start_time, now),
+            None => bail!(TrackError::OutOfRange),
+        },
+        (None, None) => bail!("give the start or the duration of the work"),
+    };
+    cli_change_spans(
+        id,
+        &format!("Logged {}", Hhmmss::hhmmss(&(end_time - start_time))),
+        |task| log_span(task, start_time, end_time, annotation),
+        settings,
+    )
+}
+
+/// Middle of the time span, a running span counts up to now
+fn span_middle(task: &Task, number: usize) -> Result<DateTime<Local>> {
+    match task
+        .timetracker
+        .iter()
+        .flatten()
+        .nth(number.saturating_sub(1))
+        .filter(|_| number > 0)
+    {
+        Some(span) => {
+            let end_time = span.end_time.unwrap_or_else(Local::now);
+            Ok(span.start_time + (end_time - span.start_time) / 2)
+        }
+        None => bail!(TrackError::NotFound(number)),
+    }
+}
+
+/// Apply the change to the time spans of the task, save it and show the time spans
+fn cli_change_spans<F>(id: &str, message: &str, change: F, settings: &Settings) -> Result<()>
+where
+    F: FnOnce(&mut Task) -> Result<()>,
+{
+    let mut task = load_task(id, settings)?;
+    change(&mut task)?;
+    save_task(&mut task, settings).with_context(|| "while saving task")?;
+    if print_structured_task(&task, settings)? {
+        return Ok(());
+    }
+    println!("{} for task '{}'", message, task.id);
+    warn_over_estimate(&task);
+    print_spans(&task, settings)
+}
+
+fn cli_list_spans(id: &str, settings: &Settings) -> Result<()> {
+    let task = load_task(id, settings)?;
+    let now = Local::now();
+    let structured: Vec<_> = task
+        .timetracker
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, span)| {
+            json!({
+                "number": index + 1,
+                "start_time": span.start_time,
+                "end_time": span.end_time,
+                "seconds": (span.end_time.unwrap_or(now) - span.start_time).num_seconds(),
+                "annotation": span.annotation,
+            })
+        })
+        .collect();
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
+    print_spans(&task, settings)
+}
+
+fn print_spans(task: &Task, settings: &Settings) -> Result<()> {
+    let now = Local::now();
+    let mut total = Duration::zero();
+    let mut span_cells = vec![];
+    for (index, span) in task.timetracker.iter().flatten().enumerate() {
+        let runtime = span.end_time.unwrap_or(now) - span.start_time;
+        total += runtime;
+        span_cells.push(vec![
+            (index + 1).cell(),
+            span.start_time.format("%Y-%m-%d %H:%M:%S").cell(),
+            span.end_time
+                .map(|end_time| end_time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "[running]".to_string())
+                .cell(),
+            Hhmmss::hhmmss(&runtime).cell(),
+            span.annotation.clone().unwrap_or_default().cell(),
+        ]);
+    }
+
+    if span_cells.is_empty() {
+        println!("No tracked time");
+        return Ok(());
+    }
+    let spans_table = span_cells
+        .table()
+        .title(vec![
+            "#".cell().bold(true).underline(true),
+            "Start".cell().bold(true).underline(true),
+            "End".cell().bold(true).underline(true),
+            "Duration".cell().bold(true).underline(true),
+            "Annotation".cell().bold(true).underline(true),
+        ]) // headers of the table
+        .border(Border::builder().build())
+        .separator(Separator::builder().build()); // empty border around the table
+    print_stdout(spans_table)
+        .with_context(|| "while trying to print out pretty table of time spans")?;
+    if settings.output.totals {
+        println!("\n Total tracked time: {}", Hhmmss::hhmmss(&total));
+        if let Some(estimate) = task.estimate() {
+            println!(" Estimate: {}", estimate.hhmmss());
+        }
+    }
+
+    Ok(())
+}
+
+/// Task tree in the form it is printed as JSON or YAML
+fn structured_task_node(node: &TaskNode, now: DateTime<Local>) -> serde_json::Value {
+    let (done, total) = node.progress();
+    json!({
+        "id": node.task.id,
+        "short_id": node.task.short_id(),
+        "description": node.task.description,
+        "done": node.task.done,
+        "project": node.task.project,
+        "subtasks_done": done,
+        "subtasks_total": total,
+        "tracked_seconds": node.tracked_seconds(now),
+        "subtasks": node
+            .subtasks
+            .iter()
+            .map(|subtask| structured_task_node(subtask, now))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Completed tasks are shown in the tree only if asked for or if they have open subtasks
+fn show_in_tree(node: &TaskNode, include_done: &bool) -> bool {
+    let (done, total) = node.progress();
+    *include_done || !node.task.done || done < total
+}
+
+/// Render the task and its subtasks as a tree
+fn task_node_tree(
+    node: &TaskNode,
+    include_done: &bool,
+    now: DateTime<Local>,
+    settings: &Settings,
+) -> Option<Tree<String>> {
+    if !show_in_tree(node, include_done) {
+        return None;
+    }
+    let (done, total) = node.progress();
+
+    let desc = truncate_description(&node.task.description, settings);
+    let mut label = format!(
+        "{} {} {}",
+        if node.task.done { "🗹" } else { "☐" },
+        node.task
+            .short_id()
+            .map(|short_id| short_id.to_string())
+            .unwrap_or_default(),
+        desc
+    );
+    if total > 0 {
+        label = format!("{} | {}/{} done", label, done, total);
+    }
+    let seconds = node.tracked_seconds(now);
+    if seconds > 0 {
+        label = format!("{} | {}", label, Duration::seconds(seconds).hhmmss());
+    }
+
+    let mut tree = Tree::new(label);
+    for subtask in &node.subtasks {
+        if let Some(leaf) = task_node_tree(subtask, include_done, now, settings) {
+            tree.push(leaf);
+        }
+    }
+    Some(tree)
+}
+
+fn cli_task_tree(id: &Option<String>, include_done: &bool, settings: &Settings) -> Result<()> {
+    let mut trees = load_task_trees(settings)?;
+    if let Some(id) = id {
+        let task = load_task(id, settings)?;
+        trees = trees
+            .iter()
+            .filter_map(|tree| tree.find(&task.id))
+            .cloned()
+            .collect();
+    }
+
+    let now = Local::now();
+    if settings.output.format.is_structured() {
+        let structured: Vec<_> = trees
+            .iter()
+            .filter(|tree| show_in_tree(tree, include_done))
+            .map(|tree| structured_task_node(tree, now))
+            .collect();
+        print_structured(&structured, settings)?;
+        return Ok(());
+    }
+
+    let mut tree_root = Tree::new("🗐 Tasks".to_string());
+    let mut tree_populated = false;
+    for tree in &trees {
+        if let Some(leaf) = task_node_tree(tree, include_done, now, settings) {
+            tree_root.push(leaf);
+            tree_populated = true;
+        }
+    }
+
+    if tree_populated {
+        println!("\n{}", tree_root);
+    } else {
+        println!("No tasks");
+    }
 
     Ok(())
 }
 
-fn show_task(id: &String, settings: &Settings) -> Result<()> {
+fn show_task(id: &str, settings: &Settings) -> Result<()> {
     let mut task = load_task(id, settings)?;
+    if print_structured_task(&task, settings)? {
+        return Ok(());
+    }
     let task_yaml = task.to_yaml_string()?;
 
     PrettyPrinter::new()
@@ -508,44 +2853,183 @@ fn show_task(id: &String, settings: &Settings) -> Result<()> {
     Ok(())
 }
 
+fn parse_due_date_arg(input: &str) -> Result<NaiveDateTime, String> {
+    parse_due_date(input, Local::now().naive_local()).map_err(|error| error.to_string())
+}
+
+fn parse_track_time_arg(input: &str) -> Result<NaiveDateTime, String> {
+    parse_track_time(input, Local::now().naive_local()).map_err(|error| error.to_string())
+}
+
+fn parse_duration_arg(input: &str) -> Result<Duration, String> {
+    parse_duration(input).map_err(|error| error.to_string())
+}
+
+fn parse_estimate_arg(input: &str) -> Result<Duration, String> {
+    parse_estimate(input).map_err(|error| error.to_string())
+}
+
+fn parse_month_arg(input: &str) -> Result<NaiveDate, String> {
+    parse_month(input).map_err(|error| error.to_string())
+}
+
+fn parse_age_arg(input: &str) -> Result<Duration, String> {
+    parse_age(input).map_err(|error| error.to_string())
+}
+
+fn resolve_task_ids(ids: &Option<Vec<String>>, settings: &Settings) -> Result<Option<Vec<Uuid>>> {
+    if let Some(ids) = ids {
+        let mut resolved_ids: Vec<Uuid> = vec![];
+        for id in ids {
+            resolved_ids.push(resolve_task_id(id, settings)?);
+        }
+        return Ok(Some(resolved_ids));
+    }
+    Ok(None)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn cli_set_characteristic(
-    id: &String,
+    selection: &TaskSelection,
     priority: &Option<TaskPriority>,
     due_date: &Option<NaiveDateTime>,
     tags: &Option<Vec<String>>,
     project: &Option<String>,
     metadata: &Option<Vec<MetadataKeyValuePair>>,
+    estimate: &Option<Duration>,
+    billable: &Option<bool>,
+    depends: &Option<Vec<String>>,
+    parent: &Option<String>,
     settings: &Settings,
 ) -> Result<()> {
-    let mut task = load_task(id, settings)?;
-    let modified = task.set_characteristic(priority, due_date, tags, project, metadata);
-
-    if modified {
-        save_task(&mut task, settings)?;
-        println!("Task characteristics modified for '{}'", task.id);
-    }
-
-    Ok(())
+    let depends = resolve_task_ids(depends, settings)?;
+    let parent = match parent {
+        Some(parent) => Some(load_task(parent, settings)?.id),
+        None => None,
+    };
+    let tasks = confirm_selection(selection, "modified", settings)?;
+    let modified = modify_tasks(
+        tasks,
+        |task| {
+            let mut modified =
+                task.set_characteristic(priority, due_date, tags, project, metadata, &depends)?;
+            if estimate.is_some() && task.set_estimate(estimate) {
+                modified = true;
+            }
+            if billable.is_some() && task.set_billable(billable) {
+                modified = true;
+            }
+            if parent.is_some() && task.parent != parent {
+                task.parent = parent;
+                modified = true;
+            }
+            Ok(modified)
+        },
+        settings,
+    )?;
+    print_modified_tasks(&modified, selection, settings)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn cli_unset_characteristic(
-    id: &String,
+    selection: &TaskSelection,
     priority: &bool,
     due_date: &bool,
     tags: &Option<Vec<String>>,
     project: &bool,
     metadata: &Option<Vec<String>>,
+    estimate: &bool,
+    billable: &bool,
+    depends: &Option<Vec<String>>,
+    parent: &bool,
     settings: &Settings,
 ) -> Result<()> {
-    let mut task = load_task(id, settings)?;
-    let modified = task.unset_characteristic(priority, due_date, tags, project, metadata);
+    let depends = resolve_task_ids(depends, settings)?;
+    let tasks = confirm_selection(selection, "modified", settings)?;
+    let modified = modify_tasks(
+        tasks,
+        |task| {
+            let mut modified =
+                task.unset_characteristic(priority, due_date, tags, project, metadata, &depends);
+            if *estimate && task.set_estimate(&None) {
+                modified = true;
+            }
+            if *billable && task.set_billable(&None) {
+                modified = true;
+            }
+            if *parent && task.parent.is_some() {
+                task.parent = None;
+                modified = true;
+            }
+            Ok(modified)
+        },
+        settings,
+    )?;
+    print_modified_tasks(&modified, selection, settings)
+}
 
-    if modified {
-        save_task(&mut task, settings)?;
+fn print_modified_tasks(
+    modified: &[Task],
+    selection: &TaskSelection,
+    settings: &Settings,
+) -> Result<()> {
+    if print_structured_tasks(modified, selection, settings)? {
+        return Ok(());
+    }
+    for task in modified {
         println!("Task characteristics modified for '{}'", task.id);
     }
+    if selection.is_bulk() {
+        println!("{} task(s) modified.", modified.len());
+    }
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(hoist_flags(args.iter().map(OsString::from).collect())).unwrap()
+    }
+
+    #[test]
+    fn output_after_filter() {
+        let cli = parse(&["tsk", "list", "project:work", "-hold", "--output", "json", "-i"]);
+        assert_eq!(cli.output, Some(OutputFormat::Json));
+        match cli.command {
+            Some(Commands::List {
+                search,
+                include_done,
+                ..
+            }) => {
+                assert_eq!(search, vec!["project:work", "-hold"]);
+                assert!(include_done);
+            }
+            _ => panic!("expected list command"),
+        }
+    }
+
+    #[test]
+    fn output_after_descriptor() {
+        let cli = parse(&["tsk", "new", "y est:1h", "--output=yaml"]);
+        assert_eq!(cli.output, Some(OutputFormat::Yaml));
+        match cli.command {
+            Some(Commands::New { descriptor, .. }) => assert_eq!(descriptor, vec!["y est:1h"]),
+            _ => panic!("expected new command"),
+        }
+    }
+
+    #[test]
+    fn flags_after_double_dash_are_filter_words() {
+        let cli = parse(&["tsk", "--output", "json", "list", "--", "-hold", "--output"]);
+        assert_eq!(cli.output, Some(OutputFormat::Json));
+        match cli.command {
+            Some(Commands::List { search, .. }) => assert_eq!(search, vec!["-hold", "--output"]),
+            _ => panic!("expected list command"),
+        }
+    }
+}
+
 // eof
@@ -7,12 +7,20 @@
 use std::{path::PathBuf, sync::{Arc, Mutex}};
 
 use clap::{Parser, Subcommand};
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{bail, Context, Result};
 use dotenv::dotenv;
-use tsk_rs::{settings::{Settings, default_config, show_config}, notify::{FilesystemMonitor, DatabaseFileType, FileHandler}, task::{Task, load_task}, note::Note};
+use tsk_rs::{settings::{Settings, default_config, show_config}, notify::{FilesystemMonitor, DatabaseFileType, FileHandler}, task::{Task, load_task}, note::Note, parser::filter_lexicon::Filter, storage::StorageBackend};
 
 #[derive(Default)]
-struct EventHandler;
+struct EventHandler {
+    filter: Option<Filter>,
+}
+
+impl EventHandler {
+    fn is_watched(&self, task: &Task) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(task))
+    }
+}
 
 impl FileHandler for EventHandler {
     fn handle(&self, file: DatabaseFileType, settings: Settings) {
@@ -21,6 +29,7 @@ impl FileHandler for EventHandler {
         match file {
             DatabaseFileType::Task(_id) => {
                 match Task::from_notify_event(file, &settings) {
+                    Ok(task) if !self.is_watched(&task) => {},
                     Ok(task) => println!("[ Update for a task ] {}", task.description),
                     Err(error) => eprintln!("{:?}", error)
                 };
@@ -29,6 +38,7 @@ impl FileHandler for EventHandler {
                 match Note::from_notify_event(file, &settings) {
                     Ok(note) => {
                         match load_task(&note.task_id.to_string(), &settings) {
+                            Ok(task) if !self.is_watched(&task) => {},
                             Ok(task) => println!("[Update for a task note] {}", task.description),
                             Err(error) => eprintln!("{:?}", error)
                         };        
@@ -47,14 +57,13 @@ struct Cli {
     #[clap(short, long, value_parser, env = "TSK_CONFIGFILE", value_name = "CONFIGFILE", default_value = default_config())]
     config: PathBuf,
 
-    /// Sets the namespace of tasks
+    /// Sets the namespace of tasks, defaults to the current namespace
     #[clap(
         short,
         long,
         value_parser,
         env = "TSK_NAMESPACE",
-        value_name = "NAMESPACE",
-        default_value = "default"
+        value_name = "NAMESPACE"
     )]
     namespace: Option<String>,
 
@@ -67,7 +76,11 @@ enum Commands {
     /// Display the current configuration of the tsk-rs suite
     Config,
     /// Watch for the changes in database [default]
-    Watch,
+    Watch {
+        /// Only report changes to tasks matching the filter expression
+        #[clap(short, long, value_parser)]
+        filter: Option<Filter>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -83,17 +96,24 @@ fn main() -> Result<()> {
     }
 
     match &cli.command {
-        Some(Commands::Watch) => watch(&settings),
+        Some(Commands::Watch { filter }) => watch(filter, &settings),
         Some(Commands::Config) => show_config(&settings),
-        None => watch(&settings)
+        None => watch(&None, &settings)
     }
 }
 
-fn watch(settings: &Settings) -> Result<()> {
+fn watch(filter: &Option<Filter>, settings: &Settings) -> Result<()> {
+    if settings.data.backend != StorageBackend::Flatfile {
+        // changes are detected from the data files on disk
+        bail!("watching for changes is only supported with the flatfile storage backend");
+    }
+
     // start monitoring the database folder for changes
     println!("Watching for task and note changes, CTRL+C to quit ...");
 
-    let handler = EventHandler::default();
+    let handler = EventHandler {
+        filter: filter.clone(),
+    };
 
     let mut monitor = FilesystemMonitor::new();
     monitor.watch(settings, Arc::new(Mutex::new(handler)), on_watch_error);
@@ -13,16 +13,20 @@ use cli_table::{
 use color_eyre::eyre::{bail, Context, Result};
 use dotenv::dotenv;
 use question::{Answer, Question};
-use std::{fs::remove_file, path::PathBuf};
+use serde_json::json;
+use std::{path::PathBuf, process::exit};
 use termtree::Tree;
 use tsk_rs::{
     metadata::MetadataKeyValuePair,
     note::{
-        amount_of_notes, list_notes, load_note, note_pathbuf_from_id, note_pathbuf_from_note,
-        save_note, Note,
+        amount_of_notes, list_notes, load_note, save_note, Note,
     },
+    output::{print_structured, truncate_description, ExitStatus, OutputFormat},
+    parser::filter_lexicon::Filter,
     settings::{default_config, show_config, Settings},
+    storage::open_storage,
     task::{load_task, TaskError},
+    trash::trash_note,
 };
 
 #[derive(Parser)]
@@ -32,17 +36,20 @@ struct Cli {
     #[clap(short, long, value_parser, env = "TSK_CONFIGFILE", value_name = "CONFIGFILE", default_value = default_config())]
     config: PathBuf,
 
-    /// Sets the namespace of tasks
+    /// Sets the namespace of tasks, defaults to the current namespace
     #[clap(
         short,
         long,
         value_parser,
         env = "TSK_NAMESPACE",
-        value_name = "NAMESPACE",
-        default_value = "default"
+        value_name = "NAMESPACE"
     )]
     namespace: Option<String>,
 
+    /// Sets the output format, overrides the format set in the configuration
+    #[clap(long, value_enum, global = true, value_name = "FORMAT")]
+    output: Option<OutputFormat>,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
@@ -68,7 +75,7 @@ enum Commands {
         #[clap(short, long, value_parser)]
         raw: bool,
     },
-    /// Delete a note
+    /// Move a note to the trash, it can be restored with `tsk trash restore`
     Delete {
         /// Existing task/note id
         #[clap(value_parser)]
@@ -82,6 +89,9 @@ enum Commands {
         /// Existing task/note id or a part of one. Empty will list all.
         #[clap(value_parser)]
         id: Option<String>,
+        /// Only list notes of tasks matching the filter expression
+        #[clap(short, long, value_parser)]
+        filter: Option<Filter>,
         /// List orphaned notes (Task file has been deleted)
         #[clap(short, long, value_parser)]
         orphaned: bool,
@@ -95,6 +105,9 @@ enum Commands {
         /// Existing task/note id or a part of one. Empty will list all.
         #[clap(value_parser)]
         id: Option<String>,
+        /// Only list aps from notes of tasks matching the filter expression
+        #[clap(short, long, value_parser)]
+        filter: Option<Filter>,
         /// List aps from orphaned notes (Task file has been deleted)
         #[clap(short, long, value_parser)]
         orphaned: bool,
@@ -127,15 +140,25 @@ enum Commands {
     },
 }
 
-fn main() -> Result<()> {
+fn main() {
     dotenv().ok();
 
     let cli = Cli::parse();
 
-    let settings = Settings::new(cli.namespace, cli.config.to_str().unwrap())
+    if let Err(error) = run(cli) {
+        eprintln!("Error: {:?}", error);
+        exit(ExitStatus::from_error(&error).code());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let mut settings = Settings::new(cli.namespace, cli.config.to_str().unwrap())
         .with_context(|| "while loading settings")?;
+    if let Some(format) = cli.output {
+        settings.output.format = format;
+    }
 
-    if settings.output.namespace {
+    if settings.output.namespace && !settings.output.format.is_structured() {
         println!(" Namespace: '{}'", settings.namespace);
     }
 
@@ -144,46 +167,54 @@ fn main() -> Result<()> {
         Some(Commands::Show { id, raw }) => show_note(id, raw, &settings),
         Some(Commands::List {
             id,
+            filter,
             orphaned,
             completed,
-        }) => cli_list_notes(id, orphaned, completed, &settings),
-        Some(Commands::Delete { id, force }) => delete_note(id, force, &settings),
+        }) => cli_list_notes(id, filter, orphaned, completed, &settings),
+        Some(Commands::Delete { id, force }) => cli_delete_note(id, force, &settings),
         Some(Commands::Config) => show_config(&settings),
         Some(Commands::Set { id, metadata }) => cli_set_characteristic(id, metadata, &settings),
         Some(Commands::Unset { id, metadata }) => cli_unset_characteristic(id, metadata, &settings),
         Some(Commands::ActionPoints {
             id,
+            filter,
             orphaned,
             completed,
             done,
-        }) => list_aps(id, orphaned, completed, done, &settings),
-        None => cli_list_notes(&None, &false, &false, &settings),
+        }) => list_aps(id, filter, orphaned, completed, done, &settings),
+        None => cli_list_notes(&None, &None, &false, &false, &settings),
     }
 }
 
 fn list_aps(
     id: &Option<String>,
+    filter: &Option<Filter>,
     orphaned: &bool,
     completed: &bool,
     done: &bool,
     settings: &Settings,
 ) -> Result<()> {
-    let found_notes = list_notes(id, orphaned, completed, settings)?;
+    let found_notes = list_notes(id, filter, orphaned, completed, settings)?;
 
     let mut tree_root = Tree::new("🗐 Task notes".to_string());
     let mut tree_populated = false;
+    let mut structured = vec![];
 
     for found_note in found_notes {
         let aps = found_note.note.get_action_points()?;
         if let Some(aps) = aps {
+            let aps: Vec<_> = aps.into_iter().filter(|ap| *done || !ap.checked).collect();
+            if aps.is_empty() {
+                continue;
+            }
+            structured.push(json!({
+                "task_id": found_note.note.task_id,
+                "description": found_note.task.as_ref().map(|task| task.description.clone()),
+                "action_points": aps,
+            }));
+
             let desc = if let Some(task) = found_note.task.clone() {
-                let mut desc = task.description.clone();
-                if desc.len() > settings.output.descriptionlength + 3 {
-                    // if the desc truncated to max length plus three dot characters is
-                    //  shorter than the max len then truncate it and add those three dots
-                    desc = format!("{}...", &desc[..settings.output.descriptionlength]);
-                }
-                desc
+                truncate_description(&task.description, settings)
             } else {
                 "[ orphaned ]".to_string()
             };
@@ -194,23 +225,20 @@ fn list_aps(
                 "[ orphaned ]".to_string()
             };
 
-            let mut ap_added_to_leaf = false;
             let mut note_leaf = Tree::new(format!("🗏 {} | {}", desc, task_id));
             for ap in aps {
-                if *done || !ap.checked {
-                    let mark = if ap.checked { "🗹" } else { "☐" };
-                    let action_leaf = Tree::new(format!("{} {}", mark, ap.description));
-                    note_leaf.push(action_leaf);
-                    ap_added_to_leaf = true;
-                }
-            }
-            if ap_added_to_leaf {
-                tree_root.push(note_leaf);
-                tree_populated = true;
+                let mark = if ap.checked { "🗹" } else { "☐" };
+                let action_leaf = Tree::new(format!("{} {}", mark, ap.description));
+                note_leaf.push(action_leaf);
             }
+            tree_root.push(note_leaf);
+            tree_populated = true;
         }
     }
 
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
     if tree_populated {
         println!("\n{}", tree_root);
     } else {
@@ -222,26 +250,36 @@ fn list_aps(
 
 fn cli_list_notes(
     id: &Option<String>,
+    filter: &Option<Filter>,
     orphaned: &bool,
     completed: &bool,
     settings: &Settings,
 ) -> Result<()> {
     let mut note_cells = vec![];
 
-    let found_notes = list_notes(id, orphaned, completed, settings)?;
+    let found_notes = list_notes(id, filter, orphaned, completed, settings)?;
     let found_notes_count: usize = amount_of_notes(settings, false)?;
 
     let mut listed_notes_count: usize = 0;
+    let mut structured = vec![];
     for found_note in found_notes {
+        if found_note.task.is_some() || *orphaned {
+            structured.push(json!({
+                "short_id": found_note.task.as_ref().and_then(|task| task.short_id()),
+                "task_id": found_note.note.task_id,
+                "description": found_note.task.as_ref().map(|task| task.description.clone()),
+                "project": found_note.task.as_ref().and_then(|task| task.project.clone()),
+                "orphaned": found_note.task.is_none(),
+            }));
+        }
         if let Some(task) = found_note.task {
-            let mut desc = task.description.clone();
-            if desc.len() > settings.output.descriptionlength + 3 {
-                // if the desc truncated to max length plus three dot characters is
-                //  shorter than the max len then truncate it and add those three dots
-                desc = format!("{}...", &desc[..settings.output.descriptionlength]);
-            }
+            let desc = truncate_description(&task.description, settings);
             listed_notes_count += 1;
             note_cells.push(vec![
+                task.short_id()
+                    .map(|short_id| short_id.to_string())
+                    .unwrap_or_default()
+                    .cell(),
                 task.id.cell(),
                 desc.cell(),
                 task.project.unwrap_or_else(|| "".to_string()).cell(),
@@ -249,18 +287,22 @@ fn cli_list_notes(
         } else if *orphaned {
             // there is no task file anymore, and orphaned is true so we add it
             note_cells.push(vec![
+                "".to_string().cell(),
                 found_note.note.task_id.cell(),
                 "[orphaned]".to_string().cell(),
                 "[orphaned]".to_string().cell(),
-                "[orphaned]".to_string().cell(),
             ]);
         }
     }
 
+    if print_structured(&structured, settings)? {
+        return Ok(());
+    }
     if !note_cells.is_empty() {
         let tasks_table = note_cells
             .table()
             .title(vec![
+                "ID".cell().bold(true).underline(true),
                 "Note/Task ID".cell().bold(true).underline(true),
                 "Description".cell().bold(true).underline(true),
                 "Project".cell().bold(true).underline(true),
@@ -281,9 +323,8 @@ fn cli_list_notes(
     Ok(())
 }
 
-fn delete_note(id: &String, force: &bool, settings: &Settings) -> Result<()> {
+fn cli_delete_note(id: &str, force: &bool, settings: &Settings) -> Result<()> {
     let note = load_note(id, settings)?;
-    let note_pathbuf = note_pathbuf_from_note(&note, settings)?;
 
     let answer = if !force {
         Question::new("Really delete this note?")
@@ -295,14 +336,17 @@ fn delete_note(id: &String, force: &bool, settings: &Settings) -> Result<()> {
     };
 
     if answer == Answer::YES {
-        remove_file(note_pathbuf).with_context(|| "while removing note file")?;
-        println!("Note for '{}' now deleted permanently.", note.task_id);
+        trash_note(&note.task_id.to_string(), settings)
+            .with_context(|| "while deleting note")?;
+        if !print_structured(&note, settings)? {
+            println!("Note for '{}' moved to trash.", note.task_id);
+        }
     }
 
     Ok(())
 }
 
-fn edit_note(id: &String, raw: &bool, settings: &Settings) -> Result<()> {
+fn edit_note(id: &str, raw: &bool, settings: &Settings) -> Result<()> {
     let task = load_task(id, settings)?;
     if task.done {
         bail!(TaskError::TaskAlreadyCompleted);
@@ -310,11 +354,11 @@ fn edit_note(id: &String, raw: &bool, settings: &Settings) -> Result<()> {
 
     let mut modified = false;
     let mut note;
-    let note_pathbuf = note_pathbuf_from_id(id, settings)?;
-    if !note_pathbuf.is_file() {
+    let note_id = task.id.to_string();
+    if !open_storage(settings)?.note_exists(&task.id)? {
         note = Note::new(&task.id);
     } else {
-        note = load_note(id, settings)?;
+        note = load_note(&note_id, settings)?;
     }
 
     if !raw {
@@ -349,14 +393,19 @@ fn edit_note(id: &String, raw: &bool, settings: &Settings) -> Result<()> {
 
     if modified {
         save_note(&mut note, settings).with_context(|| "while saving note yaml file")?;
+    }
+    if !print_structured(&note, settings)? && modified {
         println!("Note for '{}' was updated.", note.task_id);
     }
 
     Ok(())
 }
 
-fn show_note(id: &String, raw: &bool, settings: &Settings) -> Result<()> {
+fn show_note(id: &str, raw: &bool, settings: &Settings) -> Result<()> {
     let note = load_note(id, settings)?;
+    if print_structured(&note, settings)? {
+        return Ok(());
+    }
 
     if !raw {
         // by default, only show the markdown inside the note yaml
@@ -386,7 +435,7 @@ fn show_note(id: &String, raw: &bool, settings: &Settings) -> Result<()> {
 }
 
 fn cli_set_characteristic(
-    id: &String,
+    id: &str,
     metadata: &Option<Vec<MetadataKeyValuePair>>,
     settings: &Settings,
 ) -> Result<()> {
@@ -395,6 +444,8 @@ fn cli_set_characteristic(
 
     if modified {
         save_note(&mut note, settings)?;
+    }
+    if !print_structured(&note, settings)? && modified {
         println!("Modifications saved for note '{}'", note.task_id);
     }
 
@@ -402,7 +453,7 @@ fn cli_set_characteristic(
 }
 
 fn cli_unset_characteristic(
-    id: &String,
+    id: &str,
     metadata: &Option<Vec<String>>,
     settings: &Settings,
 ) -> Result<()> {
@@ -411,6 +462,8 @@ fn cli_unset_characteristic(
 
     if modified {
         save_note(&mut note, settings)?;
+    }
+    if !print_structured(&note, settings)? && modified {
         println!("Modifications saved for note '{}'", note.task_id);
     }
 
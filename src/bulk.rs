@@ -0,0 +1,235 @@
+use color_eyre::eyre::{bail, Context, Result};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    parser::filter_lexicon::Filter,
+    settings::Settings,
+    task::{
+        check_dependency_cycles, check_parent_cycles, complete_task, list_tasks_matching,
+        load_task, store_task, Task,
+    },
+    trash::trash_task,
+};
+
+/// Errors of operations on several tasks at once
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BulkError {
+    /// Neither task ids nor a filter expression was given
+    #[error("give task id(s) or a filter expression to select the tasks")]
+    NothingSelected,
+}
+
+/// Select the tasks with the given ids and the open tasks matching the filter expression. Every
+/// task is selected only once, in the order the ids were given followed by the matching tasks in
+/// the order of their score.
+pub fn select_tasks(
+    ids: &[String],
+    filter: &Option<Filter>,
+    settings: &Settings,
+) -> Result<Vec<Task>> {
+    if ids.is_empty() && filter.is_none() {
+        bail!(BulkError::NothingSelected);
+    }
+
+    let mut selected_ids: Vec<Uuid> = vec![];
+    for id in ids {
+        let task = load_task(id, settings)?;
+        if !selected_ids.contains(&task.id) {
+            selected_ids.push(task.id);
+        }
+    }
+    if filter.is_some() {
+        for task in list_tasks_matching(filter, &false, settings)? {
+            if !selected_ids.contains(&task.id) {
+                selected_ids.push(task.id);
+            }
+        }
+    }
+
+    // listed tasks carry their calculated score in metadata, so the tasks are loaded again
+    let mut tasks = vec![];
+    for id in selected_ids {
+        tasks.push(load_task(&id.to_string(), settings)?);
+    }
+    Ok(tasks)
+}
+
+/// Apply the change to every task and return the tasks the change modified. Stops at the first
+/// change that fails.
+fn change_tasks<F>(tasks: Vec<Task>, mut change: F) -> Result<Vec<Task>>
+where
+    F: FnMut(&mut Task) -> Result<bool>,
+{
+    let mut modified_tasks = vec![];
+    for mut task in tasks {
+        if change(&mut task).with_context(|| format!("while modifying task '{}'", task.id))? {
+            modified_tasks.push(task);
+        }
+    }
+    Ok(modified_tasks)
+}
+
+/// Apply the change to every task and save the modified tasks. All changes are made and checked
+/// together, as if every modified task was saved already, before anything is saved. An invalid
+/// change leaves every task as it was. The saved tasks form a single operation in the journal
+/// and are undone together.
+pub fn modify_tasks<F>(tasks: Vec<Task>, change: F, settings: &Settings) -> Result<Vec<Task>>
+where
+    F: FnMut(&mut Task) -> Result<bool>,
+{
+    let mut modified_tasks = change_tasks(tasks, change)?;
+    check_dependency_cycles(&modified_tasks, settings)?;
+    check_parent_cycles(&modified_tasks, settings)?;
+    for task in modified_tasks.iter_mut() {
+        store_task(task, settings)?;
+    }
+    Ok(modified_tasks)
+}
+
+/// Mark every task as completed. Returns the completed tasks along with the next instances of
+/// the recurring ones.
+pub fn complete_tasks(tasks: Vec<Task>, settings: &Settings) -> Result<Vec<(Task, Option<Task>)>> {
+    // completing a copy of each task first catches the errors before any task is saved
+    let now = chrono::offset::Local::now();
+    change_tasks(tasks.clone(), |task| {
+        task.mark_as_completed()?;
+        task.next_recurrence(now)?;
+        Ok(true)
+    })?;
+
+    let mut completed = vec![];
+    for mut task in tasks {
+        let next_instance = complete_task(&mut task, settings)
+            .with_context(|| format!("while completing task '{}'", task.id))?;
+        completed.push((task, next_instance));
+    }
+    Ok(completed)
+}
+
+/// Move every task and its note to the trash
+pub fn trash_tasks(tasks: &[Task], settings: &Settings) -> Result<()> {
+    for task in tasks {
+        trash_task(&task.id.to_string(), settings)
+            .with_context(|| format!("while deleting task '{}'", task.id))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        task::{save_task, TaskError},
+        testing::TestData,
+    };
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            Task::new("first".to_string()).unwrap(),
+            Task::new("second".to_string()).unwrap(),
+            Task::new("third".to_string()).unwrap(),
+        ]
+    }
+
+    fn saved_tasks(settings: &Settings) -> (Task, Task) {
+        let mut first = Task::new("first".to_string()).unwrap();
+        let mut second = Task::new("second".to_string()).unwrap();
+        save_task(&mut first, settings).unwrap();
+        save_task(&mut second, settings).unwrap();
+        (first, second)
+    }
+
+    fn depends(id: &Uuid, settings: &Settings) -> Option<Vec<Uuid>> {
+        load_task(&id.to_string(), settings).unwrap().depends
+    }
+
+    #[test]
+    fn change_returns_modified_tasks_only() {
+        let modified = change_tasks(tasks(), |task| {
+            task.set_characteristic(
+                &None,
+                &None,
+                &None,
+                &(task.description != "second").then(|| "archive".to_string()),
+                &None,
+                &None,
+            )
+        })
+        .unwrap();
+
+        assert_eq!(modified.len(), 2);
+        assert!(modified
+            .iter()
+            .all(|task| task.project == Some("archive".to_string())));
+    }
+
+    #[test]
+    fn change_stops_at_first_error() {
+        let error = change_tasks(tasks(), |task| {
+            if task.description == "second" {
+                bail!(TaskError::TaskAlreadyCompleted);
+            }
+            Ok(true)
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<TaskError>(),
+            Some(&TaskError::TaskAlreadyCompleted)
+        );
+    }
+
+    #[test]
+    fn modify_refuses_cycle_formed_by_the_batch() {
+        let settings = TestData::new();
+        let (first, second) = saved_tasks(&settings);
+
+        let error = modify_tasks(
+            vec![first.clone(), second.clone()],
+            |task| {
+                let other = if task.id == first.id {
+                    second.id
+                } else {
+                    first.id
+                };
+                task.depends = Some(vec![other]);
+                Ok(true)
+            },
+            &settings,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<TaskError>(),
+            Some(TaskError::DependencyCycle(_))
+        ));
+        // neither half of the cycle was saved
+        assert_eq!(depends(&first.id, &settings), None);
+        assert_eq!(depends(&second.id, &settings), None);
+    }
+
+    #[test]
+    fn modify_checks_batch_as_a_whole() {
+        let settings = TestData::new();
+        let (mut first, second) = saved_tasks(&settings);
+        first.depends = Some(vec![second.id]);
+        save_task(&mut first, &settings).unwrap();
+
+        // reversing the dependency is a cycle only halfway through the batch
+        modify_tasks(
+            vec![second.clone(), first.clone()],
+            |task| {
+                task.depends = (task.id == second.id).then(|| vec![first.id]);
+                Ok(true)
+            },
+            &settings,
+        )
+        .unwrap();
+
+        assert_eq!(depends(&first.id, &settings), None);
+        assert_eq!(depends(&second.id, &settings), Some(vec![first.id]));
+    }
+}
+
+// eof
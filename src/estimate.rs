@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::task::Task;
+
+/// How well the effort of the completed tasks of a project was estimated
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EstimateAccuracy {
+    /// Project of the tasks, `(none)` for tasks without a project
+    pub project: String,
+    /// Number of completed tasks with an estimate
+    pub tasks: usize,
+    /// Number of the tasks that took more time than estimated
+    pub over_estimate: usize,
+    /// Summed estimates of the tasks in seconds
+    pub estimated_seconds: i64,
+    /// Summed time tracked to the tasks in seconds
+    pub actual_seconds: i64,
+    /// Actual time divided by the estimated time, above one when the tasks took longer than
+    /// estimated
+    pub ratio: f64,
+}
+
+impl EstimateAccuracy {
+    fn new(project: String) -> Self {
+        EstimateAccuracy {
+            project,
+            tasks: 0,
+            over_estimate: 0,
+            estimated_seconds: 0,
+            actual_seconds: 0,
+            ratio: 0.0,
+        }
+    }
+
+    fn add(&mut self, task: &Task, now: DateTime<Local>) {
+        let estimated = task.estimate().unwrap_or_default().num_seconds();
+        let actual = task.tracked_time(now).num_seconds();
+        self.tasks += 1;
+        self.over_estimate += usize::from(actual > estimated);
+        self.estimated_seconds += estimated;
+        self.actual_seconds += actual;
+        self.ratio = self.actual_seconds as f64 / self.estimated_seconds as f64;
+    }
+}
+
+/// Compare the estimates of the completed tasks to the time tracked to them, per project and in
+/// total. Tasks that are open or have no estimate are left out. Returns the projects sorted by
+/// name and the total over all of them.
+pub fn estimate_accuracy(
+    tasks: &[Task],
+    now: DateTime<Local>,
+) -> (Vec<EstimateAccuracy>, EstimateAccuracy) {
+    let mut projects: BTreeMap<String, EstimateAccuracy> = BTreeMap::new();
+    let mut total = EstimateAccuracy::new("(total)".to_string());
+    for task in tasks
+        .iter()
+        .filter(|task| task.done && task.estimate().is_some())
+    {
+        let project = task.project.clone().unwrap_or_else(|| "(none)".to_string());
+        projects
+            .entry(project.clone())
+            .or_insert_with(|| EstimateAccuracy::new(project))
+            .add(task, now);
+        total.add(task, now);
+    }
+    (projects.into_values().collect(), total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TimeTrack;
+    use chrono::Duration;
+
+    fn task(descriptor: &str, done: bool, tracked_minutes: i64, now: DateTime<Local>) -> Task {
+        let mut task = Task::from_task_descriptor(&descriptor.to_string()).unwrap();
+        task.done = done;
+        task.timetracker = Some(vec![TimeTrack {
+            start_time: now - Duration::minutes(tracked_minutes),
+            end_time: Some(now),
+            annotation: None,
+        }]);
+        task
+    }
+
+    fn accuracy(now: DateTime<Local>) -> (Vec<EstimateAccuracy>, EstimateAccuracy) {
+        estimate_accuracy(
+            &[
+                task("api @work est:1h", true, 90, now),
+                task("docs @work est:2h", true, 60, now),
+                task("tests @work est:2h", false, 300, now),
+                task("no estimate @work", true, 60, now),
+                task("groceries est:30m", true, 30, now),
+            ],
+            now,
+        )
+    }
+
+    #[test]
+    fn projects_sorted_by_name() {
+        let (projects, _) = accuracy(Local::now());
+
+        let names: Vec<&str> = projects
+            .iter()
+            .map(|project| project.project.as_str())
+            .collect();
+        assert_eq!(names, vec!["(none)", "work"]);
+    }
+
+    #[test]
+    fn open_and_unestimated_tasks_are_left_out() {
+        let (projects, _) = accuracy(Local::now());
+
+        assert_eq!(projects[1].tasks, 2);
+        assert_eq!(projects[1].estimated_seconds, 3 * 3600);
+        assert_eq!(projects[1].actual_seconds, 150 * 60);
+    }
+
+    #[test]
+    fn tasks_over_estimate_are_counted() {
+        let (projects, _) = accuracy(Local::now());
+
+        assert_eq!(projects[0].over_estimate, 0);
+        assert_eq!(projects[1].over_estimate, 1);
+    }
+
+    #[test]
+    fn ratio_of_actual_to_estimated_time() {
+        let (projects, _) = accuracy(Local::now());
+
+        assert_eq!(projects[0].ratio, 1.0);
+        assert!((projects[1].ratio - 150.0 / 180.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn total_covers_every_project() {
+        let (_, total) = accuracy(Local::now());
+
+        assert_eq!(total.tasks, 3);
+        assert_eq!(total.estimated_seconds, 3 * 3600 + 30 * 60);
+        assert_eq!(total.actual_seconds, 180 * 60);
+    }
+}
+
+// eof
@@ -0,0 +1,262 @@
+use std::io::Write;
+
+use chrono::{DateTime, Local, Utc};
+use color_eyre::eyre::{Context, Result};
+
+use super::ExportedTask;
+
+/// Maximum length of a content line in octets, longer lines are folded
+static MAX_LINE_LENGTH: usize = 75;
+
+/// Format the time as an UTC date-time value
+fn format_datetime(time: &DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Escape the special characters of a text value
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold the content line so that no line is longer than allowed. Continuation lines start with a
+/// space and the line is never split in the middle of an UTF-8 character.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length: usize = 0;
+    for character in line.chars() {
+        if length + character.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(character);
+        length += character.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// iCalendar priority of the task, 1 being the highest and 9 the lowest
+fn priority(priority: &str) -> Option<u8> {
+    match priority {
+        "critical" => Some(1),
+        "high" => Some(3),
+        "medium" => Some(5),
+        "low" => Some(7),
+        _ => None,
+    }
+}
+
+/// Content lines of the VTODO component of the task
+fn todo_lines(task: &ExportedTask, stamp: &str) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", task.id),
+        format!("DTSTAMP:{}", stamp),
+        format!("SUMMARY:{}", escape_text(&task.description)),
+    ];
+    if let Some(created) = &task.created {
+        lines.push(format!("CREATED:{}", format_datetime(created)));
+    }
+    if let Some(note) = &task.note {
+        lines.push(format!("DESCRIPTION:{}", escape_text(note)));
+    }
+    if let Some(due) = &task.due {
+        lines.push(format!("DUE:{}", format_datetime(due)));
+    }
+    if let Some(priority) = task.priority.as_deref().and_then(priority) {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape_text(tag)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    let running = task
+        .timetracker
+        .last()
+        .is_some_and(|track| track.end_time.is_none());
+    let status = if task.done {
+        "COMPLETED"
+    } else if running {
+        "IN-PROCESS"
+    } else {
+        "NEEDS-ACTION"
+    };
+    lines.push(format!("STATUS:{}", status));
+    if let Some(completed) = &task.completed {
+        lines.push(format!("COMPLETED:{}", format_datetime(completed)));
+    }
+    for dependency in &task.depends {
+        lines.push(format!("RELATED-TO;RELTYPE=DEPENDS-ON:{}", dependency));
+    }
+    if let Some(parent) = &task.parent {
+        lines.push(format!("RELATED-TO;RELTYPE=PARENT:{}", parent));
+    }
+    if let Some(project) = &task.project {
+        lines.push(format!("X-TSK-RS-PROJECT:{}", escape_text(project)));
+    }
+    lines.push(format!(
+        "X-TSK-RS-NAMESPACE:{}",
+        escape_text(&task.namespace)
+    ));
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+/// Content lines of the VEVENT components of the time tracked to the task. Running spans end at
+/// the time of the export.
+fn event_lines(task: &ExportedTask, stamp: &str, now: &DateTime<Local>) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for track in &task.timetracker {
+        lines.push("BEGIN:VEVENT".to_string());
+        // start time identifies the span so the uid stays the same between exports
+        lines.push(format!(
+            "UID:{}-{}",
+            task.id,
+            format_datetime(&track.start_time)
+        ));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", format_datetime(&track.start_time)));
+        lines.push(format!(
+            "DTEND:{}",
+            format_datetime(&track.end_time.unwrap_or(*now))
+        ));
+        lines.push(format!("SUMMARY:{}", escape_text(&task.description)));
+        if let Some(annotation) = &track.annotation {
+            lines.push(format!("DESCRIPTION:{}", escape_text(annotation)));
+        }
+        if !task.tags.is_empty() {
+            let tags: Vec<String> = task.tags.iter().map(|tag| escape_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push(format!("RELATED-TO:{}", task.id));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines
+}
+
+/// Write the tasks as an iCalendar file. Each task becomes a VTODO and each time tracking span
+/// of the task a VEVENT.
+pub fn write_ical<W: Write>(tasks: &[ExportedTask], mut writer: W) -> Result<()> {
+    let now = chrono::offset::Local::now();
+    let stamp = format_datetime(&now);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//tsk-rs//tsk-rs {}//EN", env!("CARGO_PKG_VERSION")),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for task in tasks {
+        lines.append(&mut todo_lines(task, &stamp));
+        lines.append(&mut event_lines(task, &stamp, &now));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    for line in lines {
+        writer
+            .write_all(fold_line(&line).as_bytes())
+            .with_context(|| "while writing export as icalendar")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::ical::{parse_ical, IcalTodo};
+
+    fn exported_task() -> ExportedTask {
+        let now = Local::now();
+        ExportedTask {
+            namespace: "default".to_string(),
+            id: uuid::Uuid::new_v4(),
+            short_id: Some(1),
+            description: "Pay bills; rent, water".to_string(),
+            done: false,
+            project: Some("home".to_string()),
+            tags: vec!["money".to_string()],
+            priority: Some("critical".to_string()),
+            due: Some(now),
+            created: Some(now),
+            completed: None,
+            depends: vec![],
+            parent: None,
+            recurrence: None,
+            recurrence_parent: None,
+            score: 0,
+            metadata: Default::default(),
+            timetracker: vec![],
+            tracked_seconds: 0,
+            estimate_seconds: None,
+            billable: None,
+            pomodoros: 0,
+            note: Some("# Bills\n- [ ] rent".to_string()),
+        }
+    }
+
+    fn roundtrip(task: &ExportedTask) -> Vec<IcalTodo> {
+        let mut output: Vec<u8> = vec![];
+        write_ical(std::slice::from_ref(task), &mut output).unwrap();
+        parse_ical(&String::from_utf8(output).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn folded_lines_fit_the_limit() {
+        let long = format!("SUMMARY:{}", "ä".repeat(60));
+
+        let folded = fold_line(&long);
+
+        assert!(folded
+            .split("\r\n")
+            .all(|line| line.len() <= MAX_LINE_LENGTH));
+    }
+
+    #[test]
+    fn roundtrip_keeps_id_and_description() {
+        let task = exported_task();
+
+        let todos = roundtrip(&task);
+
+        assert_eq!(todos.len(), 1);
+        let imported = todos[0].to_task().unwrap();
+        assert_eq!(imported.id, task.id);
+        assert_eq!(imported.description, task.description);
+    }
+
+    #[test]
+    fn roundtrip_keeps_project_and_tags() {
+        let task = exported_task();
+
+        let imported = roundtrip(&task)[0].to_task().unwrap();
+
+        assert_eq!(imported.project, task.project);
+        assert_eq!(imported.tags, Some(task.tags.clone()));
+    }
+
+    #[test]
+    fn roundtrip_keeps_priority() {
+        let imported = roundtrip(&exported_task())[0].to_task().unwrap();
+
+        assert_eq!(
+            imported.metadata.get("tsk-rs-task-priority"),
+            Some(&"Critical".to_string())
+        );
+    }
+
+    #[test]
+    fn note_is_exported_as_description() {
+        let task = exported_task();
+
+        let todos = roundtrip(&task);
+
+        assert_eq!(todos[0].description(), task.note);
+    }
+}
+
+// eof
@@ -0,0 +1,408 @@
+use std::{collections::BTreeMap, io::Write, str::FromStr};
+
+use chrono::{DateTime, Local};
+use color_eyre::eyre::{Context, Result};
+use serde::Serialize;
+use strum::{EnumString, IntoStaticStr};
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use crate::{note::load_note, storage::open_storage};
+use crate::{
+    settings::Settings,
+    task::{Task, TaskPriority},
+};
+
+/// Export to iCalendar todos and events
+pub mod ical;
+
+/// Version of the JSON export schema. Bumped only when fields are removed or their meaning
+/// changes, new fields can be added without a bump.
+pub const EXPORT_SCHEMA_VERSION: usize = 1;
+
+/// Available export formats
+#[derive(EnumString, IntoStaticStr, clap::ValueEnum, Clone, Eq, PartialEq, Debug)]
+#[strum(serialize_all = "lowercase")]
+pub enum ExportFormat {
+    /// Single JSON document following the documented schema
+    Json,
+    /// One row per task, nested fields are encoded as JSON
+    Csv,
+    /// One line per task in the todo.txt format. Time tracking and notes are left out.
+    Todotxt,
+    /// iCalendar file with a VTODO per task and a VEVENT per time tracking span
+    Ics,
+}
+
+/// Time tracking span of an exported task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedTimeTrack {
+    /// When the time tracking was started
+    pub start_time: DateTime<Local>,
+    /// When the time tracking ended, none if it is still running
+    pub end_time: Option<DateTime<Local>>,
+    /// Annotation of the span (if any)
+    pub annotation: Option<String>,
+    /// Length of the span in seconds, running spans are counted up to the time of the export
+    pub seconds: i64,
+}
+
+/// Task as it is exported. Internal metadata of the task is mapped to fields of its own and only
+/// user metadata is left in `metadata`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedTask {
+    /// Namespace the task belongs to
+    pub namespace: String,
+    /// Unique identifier of the task
+    pub id: Uuid,
+    /// Short id of the task (if any)
+    pub short_id: Option<usize>,
+    /// Description of the task
+    pub description: String,
+    /// Is the task completed or not
+    pub done: bool,
+    /// Project of the task (if any)
+    pub project: Option<String>,
+    /// Tags of the task
+    pub tags: Vec<String>,
+    /// Priority of the task (if any): low, medium, high or critical
+    pub priority: Option<String>,
+    /// When the task is due (if ever)
+    pub due: Option<DateTime<Local>>,
+    /// When the task was created
+    pub created: Option<DateTime<Local>>,
+    /// When the task was completed (if it is)
+    pub completed: Option<DateTime<Local>>,
+    /// Ids of the tasks this task depends on
+    pub depends: Vec<Uuid>,
+    /// Task this task is a subtask of (if any)
+    pub parent: Option<Uuid>,
+    /// Recurrence rule of the task (if any)
+    pub recurrence: Option<String>,
+    /// Recurring task this task is an instance of (if any)
+    pub recurrence_parent: Option<Uuid>,
+    /// Score of the task at the time of the export
+    pub score: usize,
+    /// User metadata of the task
+    pub metadata: BTreeMap<String, String>,
+    /// Time tracking spans of the task, oldest first
+    pub timetracker: Vec<ExportedTimeTrack>,
+    /// Total time tracked to the task in seconds
+    pub tracked_seconds: i64,
+    /// Estimated effort of the task in seconds (if any)
+    pub estimate_seconds: Option<i64>,
+    /// Billable flag of the task, none if the task follows the configured default
+    pub billable: Option<bool>,
+    /// Number of pomodoros completed while focusing on the task
+    pub pomodoros: usize,
+    /// Markdown of the note linked to the task (if any)
+    pub note: Option<String>,
+}
+
+/// JSON export document
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportDocument {
+    /// Version of the schema, see [EXPORT_SCHEMA_VERSION]
+    pub version: usize,
+    /// When the export was made
+    pub exported: DateTime<Local>,
+    /// Exported tasks
+    pub tasks: Vec<ExportedTask>,
+}
+
+fn parse_time(task: &Task, key: &str) -> Result<Option<DateTime<Local>>> {
+    match task.metadata.get(key) {
+        Some(value) => Ok(Some(DateTime::from_str(value).with_context(|| {
+            format!("while parsing `{}` of task `{}`", key, task.id)
+        })?)),
+        None => Ok(None),
+    }
+}
+
+impl ExportedTask {
+    /// Convert the task to its exported form. The task is expected to come from a task listing
+    /// so that its score is up to date.
+    pub fn from_task(
+        task: &Task,
+        namespace: &str,
+        note: Option<String>,
+        now: DateTime<Local>,
+    ) -> Result<Self> {
+        let timetracker: Vec<ExportedTimeTrack> = task
+            .timetracker
+            .iter()
+            .flatten()
+            .map(|track| ExportedTimeTrack {
+                start_time: track.start_time,
+                end_time: track.end_time,
+                annotation: track.annotation.clone(),
+                seconds: (track.end_time.unwrap_or(now) - track.start_time).num_seconds(),
+            })
+            .collect();
+        let score = task
+            .metadata
+            .get("tsk-rs-task-score")
+            .map(|score| score.parse::<usize>())
+            .transpose()
+            .with_context(|| "error while converting score to number")?
+            .unwrap_or(0);
+
+        Ok(ExportedTask {
+            namespace: namespace.to_string(),
+            id: task.id,
+            short_id: task.short_id(),
+            description: task.description.clone(),
+            done: task.done,
+            project: task.project.clone(),
+            tags: task.tags.clone().unwrap_or_default(),
+            priority: task
+                .metadata
+                .get("tsk-rs-task-priority")
+                .map(|priority| priority.to_lowercase()),
+            due: parse_time(task, "tsk-rs-task-due-time")?,
+            created: parse_time(task, "tsk-rs-task-create-time")?,
+            completed: parse_time(task, "tsk-rs-task-completed-time")?,
+            depends: task.depends.clone().unwrap_or_default(),
+            parent: task.parent,
+            recurrence: task.recurrence.as_ref().map(|rule| rule.to_string()),
+            recurrence_parent: task.recurrence_parent(),
+            score,
+            metadata: task
+                .metadata
+                .iter()
+                .filter(|(key, _)| !key.starts_with("tsk-rs-"))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            tracked_seconds: timetracker.iter().map(|track| track.seconds).sum(),
+            estimate_seconds: task.estimate().map(|estimate| estimate.num_seconds()),
+            billable: task.billable(),
+            pomodoros: task.pomodoros(),
+            timetracker,
+            note,
+        })
+    }
+
+    /// Format the task as a single todo.txt line. Priorities map to (A) for critical down to (D)
+    /// for low, tags become contexts and metadata becomes key:value pairs.
+    pub fn to_todotxt(&self) -> String {
+        let mut parts: Vec<String> = vec![];
+        if self.done {
+            parts.push("x".to_string());
+            if let Some(completed) = self.completed {
+                parts.push(completed.format("%Y-%m-%d").to_string());
+            }
+        } else if let Some(priority) = self
+            .priority
+            .as_ref()
+            .and_then(|priority| TaskPriority::from_str(&capitalize(priority)).ok())
+        {
+            let letter = match priority {
+                TaskPriority::Critical => "A",
+                TaskPriority::High => "B",
+                TaskPriority::Medium => "C",
+                TaskPriority::Low => "D",
+            };
+            parts.push(format!("({})", letter));
+        }
+        if let Some(created) = self.created {
+            parts.push(created.format("%Y-%m-%d").to_string());
+        }
+        parts.push(self.description.clone());
+        if let Some(project) = &self.project {
+            parts.push(format!("+{}", project));
+        }
+        for tag in &self.tags {
+            parts.push(format!("@{}", tag));
+        }
+        if let Some(due) = self.due {
+            parts.push(format!("due:{}", due.format("%Y-%m-%d")));
+        }
+        for (key, value) in &self.metadata {
+            // todo.txt values can not contain whitespace
+            parts.push(format!(
+                "{}:{}",
+                key,
+                value.split_whitespace().collect::<Vec<&str>>().join("_")
+            ));
+        }
+        parts.push(format!("uuid:{}", self.id));
+        parts.join(" ")
+    }
+}
+
+fn capitalize(input: &str) -> String {
+    let mut chars = input.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Convert the tasks of the namespace to their exported form, along with the markdown of their
+/// notes
+pub fn export_tasks(tasks: &[Task], settings: &Settings) -> Result<Vec<ExportedTask>> {
+    let now = chrono::offset::Local::now();
+    #[cfg(feature = "note")]
+    let storage = open_storage(settings)?;
+    let mut exported: Vec<ExportedTask> = vec![];
+    for task in tasks {
+        #[cfg(feature = "note")]
+        let note = if storage.note_exists(&task.id)? {
+            load_note(&task.id.to_string(), settings)?.markdown
+        } else {
+            None
+        };
+        #[cfg(not(feature = "note"))]
+        let note = None;
+        exported.push(ExportedTask::from_task(
+            task,
+            &settings.namespace,
+            note,
+            now,
+        )?);
+    }
+    Ok(exported)
+}
+
+/// Write the exported tasks in the requested format
+pub fn write_export<W: Write>(
+    tasks: Vec<ExportedTask>,
+    format: &ExportFormat,
+    mut writer: W,
+) -> Result<()> {
+    match format {
+        ExportFormat::Json => {
+            let document = ExportDocument {
+                version: EXPORT_SCHEMA_VERSION,
+                exported: chrono::offset::Local::now(),
+                tasks,
+            };
+            serde_json::to_writer_pretty(&mut writer, &document)
+                .with_context(|| "while writing export as json")?;
+            writeln!(writer).with_context(|| "while writing export as json")?;
+        }
+        ExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            csv_writer
+                .write_record([
+                    "namespace",
+                    "id",
+                    "short_id",
+                    "description",
+                    "done",
+                    "project",
+                    "tags",
+                    "priority",
+                    "due",
+                    "created",
+                    "completed",
+                    "depends",
+                    "parent",
+                    "recurrence",
+                    "recurrence_parent",
+                    "score",
+                    "metadata",
+                    "timetracker",
+                    "tracked_seconds",
+                    "estimate_seconds",
+                    "billable",
+                    "pomodoros",
+                    "note",
+                ])
+                .with_context(|| "while writing export as csv")?;
+            let optional = |value: Option<String>| value.unwrap_or_default();
+            for task in &tasks {
+                csv_writer
+                    .write_record([
+                        task.namespace.clone(),
+                        task.id.to_string(),
+                        optional(task.short_id.map(|short_id| short_id.to_string())),
+                        task.description.clone(),
+                        task.done.to_string(),
+                        optional(task.project.clone()),
+                        task.tags.join(" "),
+                        optional(task.priority.clone()),
+                        optional(task.due.map(|time| time.to_rfc3339())),
+                        optional(task.created.map(|time| time.to_rfc3339())),
+                        optional(task.completed.map(|time| time.to_rfc3339())),
+                        task.depends
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                        optional(task.parent.map(|id| id.to_string())),
+                        optional(task.recurrence.clone()),
+                        optional(task.recurrence_parent.map(|id| id.to_string())),
+                        task.score.to_string(),
+                        serde_json::to_string(&task.metadata)
+                            .with_context(|| "while serializing metadata")?,
+                        serde_json::to_string(&task.timetracker)
+                            .with_context(|| "while serializing time tracking")?,
+                        task.tracked_seconds.to_string(),
+                        optional(task.estimate_seconds.map(|seconds| seconds.to_string())),
+                        optional(task.billable.map(|billable| billable.to_string())),
+                        task.pomodoros.to_string(),
+                        optional(task.note.clone()),
+                    ])
+                    .with_context(|| "while writing export as csv")?;
+            }
+            csv_writer
+                .flush()
+                .with_context(|| "while writing export as csv")?;
+        }
+        ExportFormat::Ics => ical::write_ical(&tasks, writer)?,
+        ExportFormat::Todotxt => {
+            for task in &tasks {
+                writeln!(writer, "{}", task.to_todotxt())
+                    .with_context(|| "while writing export as todo.txt")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn export_task_fields() {
+        let mut task = Task::from_task_descriptor(
+            &"write report @work #office PRIO:high %x-client=acme".to_string(),
+        )
+        .unwrap();
+        let now = Local::now();
+        task.timetracker = Some(vec![crate::task::TimeTrack {
+            start_time: now - Duration::minutes(30),
+            end_time: None,
+            annotation: Some("drafting".to_string()),
+        }]);
+        task.metadata
+            .insert("x-client".to_string(), "acme corp".to_string());
+        task.metadata
+            .insert("tsk-rs-task-score".to_string(), "7".to_string());
+
+        let exported =
+            ExportedTask::from_task(&task, "work", Some("# notes".to_string()), now).unwrap();
+        assert_eq!(exported.namespace, "work");
+        assert_eq!(exported.description, "write report");
+        assert_eq!(exported.project, Some("work".to_string()));
+        assert_eq!(exported.tags, vec!["office".to_string()]);
+        assert_eq!(exported.priority, Some("high".to_string()));
+        assert_eq!(exported.score, 7);
+        assert_eq!(exported.tracked_seconds, 30 * 60);
+        assert!(exported.timetracker[0].end_time.is_none());
+        assert!(exported.created.is_some());
+        // internal metadata is mapped to fields and left out of metadata
+        assert_eq!(exported.metadata.len(), 1);
+        assert_eq!(exported.metadata["x-client"], "acme corp");
+
+        let line = exported.to_todotxt();
+        assert!(line.starts_with("(B) "));
+        assert!(line.contains(" write report +work @office x-client:acme_corp uuid:"));
+    }
+}
+
+// eof
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate};
+use color_eyre::eyre::{bail, Context, Result};
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    journal::begin_operation,
+    report::ReportPeriod,
+    settings::Settings,
+    task::{load_task, save_task, start_task, stop_task, Task, TimeTrack},
+};
+
+/// Errors that can occur while focusing on a task
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FocusError {
+    /// Pomodoro would not last any time
+    #[error("pomodoro must be longer than zero")]
+    ZeroLength,
+    /// Session would have no pomodoros
+    #[error("focus session needs at least one pomodoro")]
+    ZeroCycles,
+}
+
+/// Check that a focus session of the given length and number of pomodoros can be run
+pub fn validate_session(length: &Duration, cycles: usize) -> Result<()> {
+    if *length <= Duration::zero() {
+        bail!(FocusError::ZeroLength);
+    }
+    if cycles == 0 {
+        bail!(FocusError::ZeroCycles);
+    }
+    Ok(())
+}
+
+/// Annotation of the time span of a pomodoro, e.g `pomodoro 2/4`
+pub fn pomodoro_annotation(cycle: usize, cycles: usize) -> String {
+    format!("pomodoro {}/{}", cycle, cycles)
+}
+
+/// Returns true if the time span is a pomodoro that was completed. Interrupted pomodoros have
+/// an annotation of their own.
+pub fn is_completed_pomodoro(track: &TimeTrack) -> bool {
+    let Some(annotation) = &track.annotation else {
+        return false;
+    };
+    track.end_time.is_some()
+        && annotation
+            .strip_prefix("pomodoro ")
+            .and_then(|cycle| cycle.split_once('/'))
+            .is_some_and(|(cycle, cycles)| {
+                cycle.parse::<usize>().is_ok() && cycles.parse::<usize>().is_ok()
+            })
+}
+
+/// Start time tracking for the pomodoro. Each pomodoro is an operation of its own so that undo
+/// reverts only the latest one. Time tracking already running for the task is stopped first.
+pub fn start_pomodoro(id: &str, cycle: usize, cycles: usize, settings: &Settings) -> Result<Task> {
+    let annotation = pomodoro_annotation(cycle, cycles);
+    begin_operation(&format!("tsk focus {} {}", id, annotation));
+    if load_task(id, settings)?.is_running() {
+        stop_task(id, &false, settings)?;
+    }
+    start_task(id, &Some(annotation), settings)
+}
+
+/// Stop time tracking of the pomodoro. A completed pomodoro is counted to the task and an
+/// interrupted one is annotated as such.
+pub fn finish_pomodoro(id: &str, completed: bool, settings: &Settings) -> Result<Task> {
+    let mut task = load_task(id, settings)?;
+    let timetrack = task
+        .stop()
+        .with_context(|| "while stopping time tracking of pomodoro")?;
+    if completed {
+        task.metadata.insert(
+            "tsk-rs-task-pomodoros".to_string(),
+            (task.pomodoros() + 1).to_string(),
+        );
+    } else if let Some(timetrack) = timetrack {
+        let track = task
+            .timetracker
+            .iter_mut()
+            .flatten()
+            .find(|track| track.start_time == timetrack.start_time);
+        if let Some(track) = track {
+            track.annotation = track
+                .annotation
+                .as_ref()
+                .map(|annotation| format!("{} interrupted", annotation));
+        }
+    }
+    save_task(&mut task, settings).with_context(|| "while saving task after pomodoro")?;
+    Ok(task)
+}
+
+/// Focus sessions of a single task in a period of the report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FocusEntry {
+    /// Human readable label of the period
+    pub period: String,
+    /// First day of the period
+    pub period_start: NaiveDate,
+    /// Id of the task
+    pub task_id: Uuid,
+    /// Description of the task
+    pub task: String,
+    /// Pomodoros completed during the period
+    pub pomodoros: usize,
+    /// Pomodoros ever completed for the task
+    pub total_pomodoros: usize,
+    /// Time spent in the pomodoros of the period in seconds
+    pub seconds: i64,
+}
+
+/// Count the completed pomodoros of the tasks between the dates (both inclusive) per period
+/// and task. Pomodoros are counted to the day they start on. Entries are sorted by period and
+/// the description of the task.
+pub fn focus_report(
+    tasks: &[Task],
+    from: NaiveDate,
+    to: NaiveDate,
+    period: &ReportPeriod,
+) -> Vec<FocusEntry> {
+    let mut entries: BTreeMap<(NaiveDate, String, Uuid), FocusEntry> = BTreeMap::new();
+    for task in tasks {
+        for track in task.timetracker.iter().flatten() {
+            let date = track.start_time.date_naive();
+            if !is_completed_pomodoro(track) || date < from || date > to {
+                continue;
+            }
+            let period_start = period.start_of(date);
+            let entry = entries
+                .entry((period_start, task.description.clone(), task.id))
+                .or_insert_with(|| FocusEntry {
+                    period: period.label(period_start),
+                    period_start,
+                    task_id: task.id,
+                    task: task.description.clone(),
+                    pomodoros: 0,
+                    total_pomodoros: task.pomodoros(),
+                    seconds: 0,
+                });
+            entry.pomodoros += 1;
+            if let Some(end_time) = track.end_time {
+                entry.seconds += (end_time - track.start_time).num_seconds();
+            }
+        }
+    }
+    entries.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn span(day: i64, annotation: &str, finished: bool) -> TimeTrack {
+        let start = Local.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).unwrap() + Duration::days(day);
+        TimeTrack {
+            start_time: start,
+            end_time: finished.then(|| start + Duration::minutes(25)),
+            annotation: Some(annotation.to_string()),
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    /// Task with five pomodoros in total, two of them completed on monday of week 20 and one
+    /// on monday of week 21
+    fn task() -> Task {
+        let mut task = Task::new("write report".to_string()).unwrap();
+        task.metadata
+            .insert("tsk-rs-task-pomodoros".to_string(), "5".to_string());
+        task.timetracker = Some(vec![
+            span(0, &pomodoro_annotation(1, 2), true),
+            span(0, &pomodoro_annotation(2, 2), true),
+            span(1, "pomodoro 1/4 interrupted", true),
+            span(2, "meeting", true),
+            span(7, &pomodoro_annotation(1, 4), true),
+            span(8, &pomodoro_annotation(2, 4), false),
+        ]);
+        task
+    }
+
+    #[test]
+    fn finished_pomodoro_is_completed() {
+        assert!(is_completed_pomodoro(&span(
+            0,
+            &pomodoro_annotation(1, 2),
+            true
+        )));
+    }
+
+    #[test]
+    fn interrupted_running_and_other_spans_are_not_completed() {
+        assert!(!is_completed_pomodoro(&span(
+            0,
+            "pomodoro 1/4 interrupted",
+            true
+        )));
+        assert!(!is_completed_pomodoro(&span(
+            0,
+            &pomodoro_annotation(1, 4),
+            false
+        )));
+        assert!(!is_completed_pomodoro(&span(0, "meeting", true)));
+    }
+
+    #[test]
+    fn pomodoros_are_counted_per_period() {
+        let entries = focus_report(&[task()], date(13), date(26), &ReportPeriod::Week);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].period, "2024-W20");
+        assert_eq!(entries[0].pomodoros, 2);
+        assert_eq!(entries[0].seconds, 50 * 60);
+        assert_eq!(entries[1].period, "2024-W21");
+        assert_eq!(entries[1].pomodoros, 1);
+    }
+
+    #[test]
+    fn entries_carry_total_pomodoros_of_task() {
+        let entries = focus_report(&[task()], date(13), date(26), &ReportPeriod::Week);
+
+        assert!(entries.iter().all(|entry| entry.total_pomodoros == 5));
+    }
+
+    #[test]
+    fn pomodoros_outside_dates_are_left_out() {
+        let entries = focus_report(&[task()], date(14), date(19), &ReportPeriod::Day);
+
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn session_needs_length_and_cycles() {
+        assert_eq!(
+            validate_session(&Duration::zero(), 4)
+                .unwrap_err()
+                .downcast::<FocusError>()
+                .unwrap(),
+            FocusError::ZeroLength
+        );
+        assert_eq!(
+            validate_session(&Duration::minutes(25), 0)
+                .unwrap_err()
+                .downcast::<FocusError>()
+                .unwrap(),
+            FocusError::ZeroCycles
+        );
+    }
+}
+
+// eof
@@ -0,0 +1,123 @@
+use color_eyre::eyre::{bail, Result};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Errors that can occur while resolving an identifier given by the user
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IdError {
+    /// Nothing matched the given identifier
+    #[error("no entry found with id `{0}`")]
+    NotFound(String),
+    /// Identifier matched more than one entry
+    #[error("id `{id}` is ambiguous, it matches: {candidates}")]
+    Ambiguous {
+        /// Identifier that was given
+        id: String,
+        /// Comma separated list of full identifiers that matched
+        candidates: String,
+    },
+}
+
+/// Returns true if the string could be the beginning of an UUID. Only hexadecimal digits and
+/// hyphens are allowed.
+fn is_uuid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && prefix.len() <= 36
+        && prefix.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Find the ids that begin with the prefix
+pub fn find_ids_by_prefix(prefix: &str, ids: &[Uuid]) -> Vec<Uuid> {
+    if !is_uuid_prefix(prefix) {
+        return vec![];
+    }
+
+    let prefix = prefix.to_lowercase();
+    let mut found_ids: Vec<Uuid> = ids
+        .iter()
+        .filter(|id| id.to_string().starts_with(&prefix))
+        .cloned()
+        .collect();
+    found_ids.sort();
+
+    found_ids
+}
+
+/// Pick the single candidate that matched the id or report why that was not possible
+pub fn resolve_candidates(id: &str, candidates: Vec<Uuid>) -> Result<Uuid> {
+    match candidates.len() {
+        0 => bail!(IdError::NotFound(id.to_string())),
+        1 => Ok(candidates[0]),
+        _ => bail!(IdError::Ambiguous {
+            id: id.to_string(),
+            candidates: candidates
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid_prefix_valid() {
+        assert!(is_uuid_prefix("bd6f75aa-8c8d"));
+    }
+
+    #[test]
+    fn uuid_prefix_rejects_glob() {
+        assert!(!is_uuid_prefix("bd6f*"));
+        assert!(!is_uuid_prefix("../bd6f"));
+        assert!(!is_uuid_prefix(""));
+    }
+
+    #[test]
+    fn find_by_prefix() {
+        let first = Uuid::parse_str("bd6f75aa-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        let second = Uuid::parse_str("bd6f0000-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        let third = Uuid::parse_str("0d6f0000-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        let ids = vec![first, second, third];
+        assert_eq!(find_ids_by_prefix("BD6F", &ids), vec![second, first]);
+        assert_eq!(find_ids_by_prefix("bd6f7", &ids), vec![first]);
+        assert!(find_ids_by_prefix("bd6f*", &ids).is_empty());
+    }
+
+    #[test]
+    fn resolve_single_candidate() {
+        let uuid = Uuid::parse_str("bd6f75aa-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        assert_eq!(resolve_candidates("bd6f", vec![uuid]).unwrap(), uuid);
+    }
+
+    #[test]
+    fn resolve_no_candidates() {
+        assert_eq!(
+            resolve_candidates("bd6f", vec![])
+                .unwrap_err()
+                .downcast::<IdError>()
+                .unwrap(),
+            IdError::NotFound("bd6f".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_ambiguous_candidates() {
+        let first = Uuid::parse_str("bd6f75aa-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        let second = Uuid::parse_str("bd6f0000-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        assert_eq!(
+            resolve_candidates("bd6f", vec![second, first])
+                .unwrap_err()
+                .downcast::<IdError>()
+                .unwrap(),
+            IdError::Ambiguous {
+                id: "bd6f".to_string(),
+                candidates: format!("{}, {}", second, first)
+            }
+        );
+    }
+}
+
+// eof
@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use color_eyre::eyre::{bail, Context, Result};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use super::ImportAction;
+use super::{import_tasks, ImportResult};
+#[cfg(feature = "note")]
+use crate::{
+    note::{save_note, Note},
+    storage::open_storage,
+};
+use crate::{
+    settings::Settings,
+    task::{Task, TaskPriority},
+};
+
+/// Errors that can occur when importing from iCalendar
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IcalError {
+    /// Date or date-time value is not in a format iCalendar uses
+    #[error("invalid icalendar date-time `{0}`")]
+    InvalidDateTime(String),
+    /// Priority is not a number from 0 to 9
+    #[error("invalid icalendar priority `{0}`")]
+    InvalidPriority(String),
+    /// VTODO component has no summary to use as the description of the task
+    #[error("todo `{0}` has no summary")]
+    MissingSummary(String),
+    /// Component was not closed before the end of the input
+    #[error("component `{0}` is not terminated")]
+    UnterminatedComponent(String),
+}
+
+/// Content line of an iCalendar component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcalProperty {
+    /// Name of the property in upper case, e.g DUE
+    pub name: String,
+    /// Parameters of the property with their names in upper case, e.g VALUE=DATE
+    pub params: BTreeMap<String, String>,
+    /// Raw value of the property, still escaped
+    pub value: String,
+}
+
+/// VTODO component of an iCalendar file
+#[derive(Debug, Clone, Default)]
+pub struct IcalTodo {
+    /// Properties of the todo, nested components like alarms are left out
+    pub properties: Vec<IcalProperty>,
+}
+
+/// Join the folded content lines back together
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in input.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Split the content line to its name, parameters and value. Parameter values can be quoted and
+/// the quoted values can contain the delimiters.
+fn parse_property(line: &str) -> IcalProperty {
+    let mut parts: Vec<String> = vec![String::new()];
+    let mut value: Option<String> = None;
+    let mut quoted = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ';' if !quoted => parts.push(String::new()),
+            ':' if !quoted => {
+                value = Some(line[index + 1..].to_string());
+                break;
+            }
+            other => parts.last_mut().unwrap().push(other),
+        }
+    }
+    let name = parts.remove(0).to_uppercase();
+    let params = parts
+        .iter()
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_uppercase(), value.to_string()))
+        .collect();
+    IcalProperty {
+        name,
+        params,
+        value: value.unwrap_or_default(),
+    }
+}
+
+/// Resolve the escaped characters of a text value
+fn unescape_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            text.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// Split a list value like CATEGORIES on the commas that are not escaped
+fn split_list(value: &str) -> Vec<String> {
+    let mut items: Vec<String> = vec![String::new()];
+    let mut escaped = false;
+    for character in value.chars() {
+        match character {
+            ',' if !escaped => items.push(String::new()),
+            '\\' if !escaped => escaped = true,
+            other => {
+                if escaped && other != ',' {
+                    items.last_mut().unwrap().push('\\');
+                }
+                items.last_mut().unwrap().push(other);
+                escaped = false;
+            }
+        }
+    }
+    items
+        .iter()
+        .map(|item| unescape_text(item).trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Parse a date or date-time value. UTC times end with Z, other times are taken as local time.
+/// Dates are taken as the local midnight.
+fn parse_datetime(property: &IcalProperty) -> Result<DateTime<Local>> {
+    let value = property.value.trim();
+    let invalid = || IcalError::InvalidDateTime(value.to_string());
+    if property.params.get("VALUE").map(|kind| kind.as_str()) == Some("DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .ok_or_else(|| invalid().into());
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let datetime =
+            NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(datetime.and_utc().with_timezone(&Local));
+    }
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .ok_or_else(|| invalid().into())
+}
+
+/// Id of the task imported from the todo with the UID. UIDs that are not UUIDs, as written by
+/// other calendar applications, are turned to one so that the id stays the same between imports.
+fn task_id_from_uid(uid: &str) -> Uuid {
+    Uuid::parse_str(uid).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_URL, uid.as_bytes()))
+}
+
+impl IcalTodo {
+    /// Returns the first property with the name
+    pub fn get(&self, name: &str) -> Option<&IcalProperty> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// Returns the unescaped text of the first property with the name
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name)
+            .map(|property| unescape_text(&property.value))
+    }
+
+    /// Returns false if the todo is not imported: cancelled todos
+    pub fn is_importable(&self) -> bool {
+        self.text("STATUS").map(|status| status.to_uppercase()) != Some("CANCELLED".to_string())
+    }
+
+    /// Description of the todo, imported as the note of the task
+    pub fn description(&self) -> Option<String> {
+        self.text("DESCRIPTION")
+            .filter(|description| !description.trim().is_empty())
+    }
+
+    /// Convert to a task. The id of the task is taken from the UID, CATEGORIES become tags and
+    /// X-TSK-RS-PROJECT the project. Related todos of the type DEPENDS-ON become dependencies.
+    pub fn to_task(&self) -> Result<Task> {
+        let uid = self.text("UID");
+        let Some(summary) = self.text("SUMMARY") else {
+            bail!(IcalError::MissingSummary(uid.unwrap_or_default()));
+        };
+        let mut task = Task::new(summary)?;
+        if let Some(uid) = &uid {
+            task.id = task_id_from_uid(uid);
+        }
+        task.project = self.text("X-TSK-RS-PROJECT");
+
+        let tags: Vec<String> = self
+            .properties
+            .iter()
+            .filter(|property| property.name == "CATEGORIES")
+            .flat_map(|property| split_list(&property.value))
+            .map(|tag| tag.split_whitespace().collect::<Vec<&str>>().join("-"))
+            .collect();
+        if !tags.is_empty() {
+            task.tags = Some(tags);
+        }
+
+        if let Some(created) = self.get("CREATED") {
+            task.metadata.insert(
+                "tsk-rs-task-create-time".to_string(),
+                parse_datetime(created)?.to_rfc3339(),
+            );
+        }
+        if let Some(priority) = self.get("PRIORITY") {
+            // 1 is the highest priority and 9 the lowest, 0 means undefined
+            let priority = match priority.value.trim().parse::<u8>() {
+                Ok(0) => None,
+                Ok(1) => Some(TaskPriority::Critical),
+                Ok(2..=4) => Some(TaskPriority::High),
+                Ok(5) => Some(TaskPriority::Medium),
+                Ok(6..=9) => Some(TaskPriority::Low),
+                _ => bail!(IcalError::InvalidPriority(priority.value.clone())),
+            };
+            if let Some(priority) = priority {
+                let prio_str: &str = priority.into();
+                task.metadata
+                    .insert("tsk-rs-task-priority".to_string(), prio_str.to_string());
+            }
+        }
+        if let Some(due) = self.get("DUE") {
+            task.metadata.insert(
+                "tsk-rs-task-due-time".to_string(),
+                parse_datetime(due)?.to_rfc3339(),
+            );
+        }
+        if self.text("STATUS").map(|status| status.to_uppercase()) == Some("COMPLETED".to_string())
+        {
+            task.done = true;
+            if let Some(completed) = self.get("COMPLETED") {
+                task.metadata.insert(
+                    "tsk-rs-task-completed-time".to_string(),
+                    parse_datetime(completed)?.to_rfc3339(),
+                );
+            }
+        }
+
+        let dependencies: Vec<Uuid> = self
+            .properties
+            .iter()
+            .filter(|property| {
+                property.name == "RELATED-TO"
+                    && property
+                        .params
+                        .get("RELTYPE")
+                        .map(|kind| kind.to_uppercase())
+                        == Some("DEPENDS-ON".to_string())
+            })
+            .map(|property| task_id_from_uid(unescape_text(&property.value).trim()))
+            .collect();
+        if !dependencies.is_empty() {
+            task.depends = Some(dependencies);
+        }
+
+        Ok(task)
+    }
+}
+
+/// Parse the VTODO components of an iCalendar file. Other components, like events, are ignored.
+pub fn parse_ical(input: &str) -> Result<Vec<IcalTodo>> {
+    let mut todos: Vec<IcalTodo> = vec![];
+    // names of the components the current line is nested in
+    let mut components: Vec<String> = vec![];
+    let mut todo = IcalTodo::default();
+    for line in unfold(input) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let property = parse_property(&line);
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.trim().to_uppercase();
+                if component == "VTODO" {
+                    todo = IcalTodo::default();
+                }
+                components.push(component);
+            }
+            "END" => {
+                if components.pop().as_deref() == Some("VTODO") {
+                    todos.push(std::mem::take(&mut todo));
+                }
+            }
+            _ => {
+                if components.last().map(|component| component.as_str()) == Some("VTODO") {
+                    todo.properties.push(property);
+                }
+            }
+        }
+    }
+    if let Some(component) = components.pop() {
+        bail!(IcalError::UnterminatedComponent(component));
+    }
+    Ok(todos)
+}
+
+/// Import tasks from the VTODO components of an iCalendar file. Tasks are identified by the UID
+/// of the todo, so importing the same file again updates the tasks instead of duplicating them.
+/// Descriptions of the todos are imported as the notes of the tasks. With dry run nothing is
+/// saved, but the results tell what would have been done.
+pub fn import_ical(input: &str, dry_run: bool, settings: &Settings) -> Result<Vec<ImportResult>> {
+    let todos = parse_ical(input)?;
+    let mut tasks: Vec<(Task, bool)> = vec![];
+    for todo in &todos {
+        let task = todo.to_task().with_context(|| {
+            format!(
+                "while converting icalendar todo {}",
+                todo.text("UID").unwrap_or_default()
+            )
+        })?;
+        tasks.push((task, todo.is_importable()));
+    }
+    #[cfg(not(feature = "note"))]
+    let results = import_tasks(tasks, dry_run, settings)?;
+    #[cfg(feature = "note")]
+    let mut results = import_tasks(tasks, dry_run, settings)?;
+
+    #[cfg(feature = "note")]
+    {
+        let storage = open_storage(settings)?;
+        for (result, todo) in results.iter_mut().zip(&todos) {
+            let Some(markdown) = todo.description() else {
+                continue;
+            };
+            if result.action == ImportAction::Skip {
+                continue;
+            }
+            let mut note = if storage.note_exists(&result.task.id)? {
+                storage.load_note(&result.task.id)?
+            } else {
+                Note::new(&result.task.id)
+            };
+            if note.markdown.as_ref() == Some(&markdown) {
+                continue;
+            }
+            if result.action == ImportAction::Unchanged {
+                result.action = ImportAction::Update;
+            }
+            if !dry_run {
+                note.markdown = Some(markdown);
+                save_note(&mut note, settings)
+                    .with_context(|| format!("while saving imported note {}", note.task_id))?;
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    static CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example//Calendar//EN\r
+BEGIN:VTODO\r
+UID:20240501-report@example.com\r
+SUMMARY:Write the report\\, final\r
+DESCRIPTION:Ask for numbers\\nfrom finance\r
+CATEGORIES:writing,office work\r
+PRIORITY:2\r
+DUE:20240510T150000Z\r
+X-TSK-RS-PROJECT:work\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Reminder\r
+END:VALARM\r
+END:VTODO\r
+BEGIN:VEVENT\r
+UID:event\r
+SUMMARY:Meeting\r
+END:VEVENT\r
+BEGIN:VTODO\r
+UID:8ad1e2a5-7f3c-4a67-9e1c-4b1f0b2b8d11\r
+SUMMARY:Old chore with a summary that is long enough to be folded over to the next\r
+  line\r
+STATUS:COMPLETED\r
+COMPLETED:20240402T090000Z\r
+RELATED-TO;RELTYPE=DEPENDS-ON:20240501-report@example.com\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:cancelled\r
+SUMMARY:Never mind\r
+STATUS:CANCELLED\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn parse_and_convert() {
+        let todos = parse_ical(CALENDAR).unwrap();
+        assert_eq!(todos.len(), 3);
+        assert!(!todos[2].is_importable());
+        assert_eq!(
+            todos[0].description(),
+            Some("Ask for numbers\nfrom finance".to_string())
+        );
+
+        let task = todos[0].to_task().unwrap();
+        assert_eq!(task.id, task_id_from_uid("20240501-report@example.com"));
+        assert_eq!(task.description, "Write the report, final");
+        assert_eq!(task.project, Some("work".to_string()));
+        assert_eq!(
+            task.tags,
+            Some(vec!["writing".to_string(), "office-work".to_string()])
+        );
+        assert_eq!(
+            task.metadata.get("tsk-rs-task-priority"),
+            Some(&"High".to_string())
+        );
+        assert_eq!(
+            DateTime::parse_from_rfc3339(task.metadata.get("tsk-rs-task-due-time").unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+                .to_rfc3339(),
+            "2024-05-10T15:00:00+00:00"
+        );
+
+        let completed = todos[1].to_task().unwrap();
+        assert_eq!(
+            completed.id.to_string(),
+            "8ad1e2a5-7f3c-4a67-9e1c-4b1f0b2b8d11"
+        );
+        assert_eq!(
+            completed.description,
+            "Old chore with a summary that is long enough to be folded over to the next line"
+        );
+        assert!(completed.done);
+        assert!(completed
+            .metadata
+            .contains_key("tsk-rs-task-completed-time"));
+        assert_eq!(completed.depends, Some(vec![task.id]));
+
+        let unterminated = "BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:Open\n";
+        assert_eq!(
+            parse_ical(unterminated)
+                .unwrap_err()
+                .downcast::<IcalError>()
+                .unwrap(),
+            IcalError::UnterminatedComponent("VTODO".to_string())
+        );
+    }
+}
+
+// eof
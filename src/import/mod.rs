@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use color_eyre::eyre::{Context, Result};
+use strum::IntoStaticStr;
+use uuid::Uuid;
+
+use crate::{
+    settings::Settings,
+    storage::open_storage,
+    task::{save_task, Task},
+};
+
+/// Import from Taskwarrior JSON export
+pub mod taskwarrior;
+
+/// Import from iCalendar VTODO components
+pub mod ical;
+
+/// What was done (or would be done in a dry run) to an imported task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum ImportAction {
+    /// Task did not exist before and was created
+    Create,
+    /// Task was imported before and has been updated
+    Update,
+    /// Task was imported before and nothing has changed since
+    Unchanged,
+    /// Task was not imported, for example because it was deleted in the source
+    Skip,
+}
+
+/// Outcome of importing a single task
+pub struct ImportResult {
+    /// What was done to the task
+    pub action: ImportAction,
+    /// Task as it is after the import
+    pub task: Task,
+}
+
+/// Returns true if the tasks have the same content. The score and time tracking of the tasks
+/// are not compared, as those are maintained by tsk-rs itself.
+fn same_content(task: &Task, other: &Task) -> bool {
+    let without_score = |task: &Task| {
+        let mut metadata = task.metadata.clone();
+        metadata.remove("tsk-rs-task-score");
+        metadata
+    };
+    task.description == other.description
+        && task.done == other.done
+        && task.project == other.project
+        && task.tags == other.tags
+        && task.depends == other.depends
+        && without_score(task) == without_score(other)
+}
+
+/// Apply the imported version of the task over the existing one. Fields maintained by tsk-rs
+/// (short id, time tracking, recurrence) and metadata added in tsk-rs are kept.
+fn merge_imported(existing: &Task, imported: &Task) -> Task {
+    let mut merged = existing.clone();
+    merged.description = imported.description.clone();
+    merged.done = imported.done;
+    merged.project = imported.project.clone();
+    merged.tags = imported.tags.clone();
+    merged.depends = imported.depends.clone();
+    for key in [
+        "tsk-rs-task-priority",
+        "tsk-rs-task-due-time",
+        "tsk-rs-task-completed-time",
+    ] {
+        merged.metadata.remove(key);
+    }
+    for (key, value) in &imported.metadata {
+        if key == "tsk-rs-task-score" {
+            continue;
+        }
+        merged.metadata.insert(key.clone(), value.clone());
+    }
+    if merged.timetracker.is_none() {
+        merged.timetracker = imported.timetracker.clone();
+    }
+    merged
+}
+
+/// Save the converted tasks, each paired with a flag telling if it should be imported at all.
+/// Tasks that exist already are updated only if the import changes them. Dependencies are kept
+/// only if the task depended on is imported or exists already.
+fn import_tasks(
+    tasks: Vec<(Task, bool)>,
+    dry_run: bool,
+    settings: &Settings,
+) -> Result<Vec<ImportResult>> {
+    let storage = open_storage(settings)?;
+    let mut known_ids: HashSet<Uuid> = storage.task_ids()?.into_iter().collect();
+    known_ids.extend(
+        tasks
+            .iter()
+            .filter(|(_, importable)| *importable)
+            .map(|(task, _)| task.id),
+    );
+
+    let mut results: Vec<ImportResult> = vec![];
+    for (mut task, importable) in tasks {
+        if !importable {
+            results.push(ImportResult {
+                action: ImportAction::Skip,
+                task,
+            });
+            continue;
+        }
+        if let Some(depends) = task.depends.as_mut() {
+            depends.retain(|id| known_ids.contains(id));
+        }
+
+        let (action, mut task) = if storage.task_exists(&task.id)? {
+            let existing = storage.load_task(&task.id)?;
+            let merged = merge_imported(&existing, &task);
+            if same_content(&existing, &merged) {
+                (ImportAction::Unchanged, existing)
+            } else {
+                (ImportAction::Update, merged)
+            }
+        } else {
+            (ImportAction::Create, task)
+        };
+
+        if !dry_run && action != ImportAction::Unchanged {
+            save_task(&mut task, settings)
+                .with_context(|| format!("while saving imported task {}", task.id))?;
+        }
+        results.push(ImportResult { action, task });
+    }
+
+    Ok(results)
+}
+
+// eof
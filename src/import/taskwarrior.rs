@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use color_eyre::eyre::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{import_tasks, ImportResult};
+use crate::{
+    settings::Settings,
+    task::{Task, TaskPriority, TimeTrack},
+};
+
+/// Errors that can occur when importing from Taskwarrior
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TaskwarriorError {
+    /// Timestamp is not in the format Taskwarrior uses
+    #[error("invalid taskwarrior timestamp `{0}`")]
+    InvalidTimestamp(String),
+    /// Priority is not one of H, M or L
+    #[error("invalid taskwarrior priority `{0}`")]
+    InvalidPriority(String),
+}
+
+/// Taskwarrior attributes that are either mapped to the task or have no counterpart in tsk-rs.
+/// Everything else is an user defined attribute (UDA).
+static CORE_ATTRIBUTES: &[&str] = &[
+    "id",
+    "uuid",
+    "description",
+    "status",
+    "project",
+    "tags",
+    "priority",
+    "due",
+    "entry",
+    "end",
+    "start",
+    "modified",
+    "annotations",
+    "depends",
+    "urgency",
+    "wait",
+    "scheduled",
+    "until",
+    "recur",
+    "mask",
+    "imask",
+    "parent",
+    "rtype",
+    "template",
+    "last",
+];
+
+/// Annotation of a Taskwarrior task
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskwarriorAnnotation {
+    /// When the annotation was added
+    pub entry: String,
+    /// Text of the annotation
+    pub description: String,
+}
+
+/// Task in the Taskwarrior JSON export format
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskwarriorTask {
+    /// Unique identifier of the task, used as the id of the imported task
+    pub uuid: Uuid,
+    /// Description of the task
+    pub description: String,
+    /// One of pending, completed, deleted, waiting or recurring
+    #[serde(default)]
+    pub status: String,
+    /// Project of the task
+    pub project: Option<String>,
+    /// Tags of the task
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Priority, H, M or L
+    pub priority: Option<String>,
+    /// Due date
+    pub due: Option<String>,
+    /// When the task was created
+    pub entry: Option<String>,
+    /// When the task was completed or deleted
+    pub end: Option<String>,
+    /// When the task was started, present if the task is active
+    pub start: Option<String>,
+    /// Annotations of the task
+    #[serde(default)]
+    pub annotations: Vec<TaskwarriorAnnotation>,
+    /// Uuids of the tasks this task depends on. Older versions export a comma separated string
+    /// and newer ones an array.
+    pub depends: Option<Value>,
+    /// Remaining attributes, including the user defined ones
+    #[serde(flatten)]
+    pub attributes: BTreeMap<String, Value>,
+}
+
+/// Parse Taskwarrior timestamp, e.g 20240501T120000Z. RFC 3339 timestamps are accepted as well.
+fn parse_timestamp(input: &str) -> Result<DateTime<Local>> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(input, "%Y%m%dT%H%M%SZ") {
+        return Ok(datetime.and_utc().with_timezone(&Local));
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Ok(datetime.with_timezone(&Local));
+    }
+    bail!(TaskwarriorError::InvalidTimestamp(input.to_string()));
+}
+
+/// Render a JSON value of an user defined attribute as metadata value
+fn attribute_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl TaskwarriorTask {
+    /// Returns false if the task is not imported: deleted tasks and the templates of recurring
+    /// tasks (their pending instances are imported)
+    pub fn is_importable(&self) -> bool {
+        self.status != "deleted" && self.status != "recurring"
+    }
+
+    /// Uuids of the tasks this task depends on
+    pub fn dependencies(&self) -> Vec<Uuid> {
+        let ids: Vec<String> = match &self.depends {
+            Some(Value::String(ids)) => ids.split(',').map(|id| id.trim().to_string()).collect(),
+            Some(Value::Array(ids)) => ids.iter().map(attribute_value).collect(),
+            _ => vec![],
+        };
+        ids.iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect()
+    }
+
+    /// Convert to a task. Annotations are stored as `x-annotation-N` metadata and user defined
+    /// attributes as `x-<name>` metadata.
+    pub fn to_task(&self) -> Result<Task> {
+        let mut task = Task::new(self.description.clone())?;
+        task.id = self.uuid;
+        task.project = self.project.clone();
+        if !self.tags.is_empty() {
+            task.tags = Some(self.tags.clone());
+        }
+
+        if let Some(entry) = &self.entry {
+            task.metadata.insert(
+                "tsk-rs-task-create-time".to_string(),
+                parse_timestamp(entry)?.to_rfc3339(),
+            );
+        }
+        if let Some(priority) = &self.priority {
+            let priority = match priority.as_str() {
+                "H" => TaskPriority::High,
+                "M" => TaskPriority::Medium,
+                "L" => TaskPriority::Low,
+                other => bail!(TaskwarriorError::InvalidPriority(other.to_string())),
+            };
+            let prio_str: &str = priority.into();
+            task.metadata
+                .insert("tsk-rs-task-priority".to_string(), prio_str.to_string());
+        }
+        if let Some(due) = &self.due {
+            task.metadata.insert(
+                "tsk-rs-task-due-time".to_string(),
+                parse_timestamp(due)?.to_rfc3339(),
+            );
+        }
+        if self.status == "completed" {
+            task.done = true;
+            if let Some(end) = &self.end {
+                task.metadata.insert(
+                    "tsk-rs-task-completed-time".to_string(),
+                    parse_timestamp(end)?.to_rfc3339(),
+                );
+            }
+        } else if let Some(start) = &self.start {
+            // task is active in taskwarrior
+            task.timetracker = Some(vec![TimeTrack {
+                start_time: parse_timestamp(start)?,
+                end_time: None,
+                annotation: None,
+            }]);
+        }
+
+        for (index, annotation) in self.annotations.iter().enumerate() {
+            task.metadata.insert(
+                format!("x-annotation-{}", index + 1),
+                format!(
+                    "{} {}",
+                    parse_timestamp(&annotation.entry)?.format("%Y-%m-%d %H:%M"),
+                    annotation.description
+                ),
+            );
+        }
+        for (name, value) in &self.attributes {
+            if CORE_ATTRIBUTES.contains(&name.as_str()) {
+                continue;
+            }
+            task.metadata
+                .insert(format!("x-{}", name.to_lowercase()), attribute_value(value));
+        }
+
+        let dependencies = self.dependencies();
+        if !dependencies.is_empty() {
+            task.depends = Some(dependencies);
+        }
+
+        Ok(task)
+    }
+}
+
+/// Parse Taskwarrior export. Both the JSON array written by `task export` and the older format
+/// of one JSON object per line are accepted.
+pub fn parse_taskwarrior_export(input: &str) -> Result<Vec<TaskwarriorTask>> {
+    if input.trim_start().starts_with('[') {
+        return serde_json::from_str(input).with_context(|| "while parsing taskwarrior export");
+    }
+    let mut tasks: Vec<TaskwarriorTask> = vec![];
+    for line in input.lines() {
+        let line = line.trim().trim_end_matches(',');
+        if line.is_empty() {
+            continue;
+        }
+        tasks.push(serde_json::from_str(line).with_context(|| "while parsing taskwarrior export")?);
+    }
+    Ok(tasks)
+}
+
+/// Import tasks from Taskwarrior export. Tasks keep their Taskwarrior uuid as their id, so
+/// importing the same export again updates the tasks instead of duplicating them. With dry run
+/// nothing is saved, but the results tell what would have been done.
+pub fn import_taskwarrior(
+    input: &str,
+    dry_run: bool,
+    settings: &Settings,
+) -> Result<Vec<ImportResult>> {
+    let mut tasks: Vec<(Task, bool)> = vec![];
+    for exported_task in parse_taskwarrior_export(input)? {
+        let task = exported_task
+            .to_task()
+            .with_context(|| format!("while converting taskwarrior task {}", exported_task.uuid))?;
+        tasks.push((task, exported_task.is_importable()));
+    }
+    import_tasks(tasks, dry_run, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::import::{merge_imported, same_content};
+
+    static EXPORT: &str = r#"[
+{"id":1,"description":"Write the report","entry":"20240501T080000Z","modified":"20240502T080000Z","priority":"H","project":"work.reports","status":"pending","tags":["next","writing"],"uuid":"8ad1e2a5-7f3c-4a67-9e1c-4b1f0b2b8d11","due":"20240510T150000Z","annotations":[{"entry":"20240502T080000Z","description":"ask for numbers"}],"client":"acme","estimate":3,"urgency":12.5},
+{"id":0,"description":"Old chore","entry":"20240401T080000Z","end":"20240402T090000Z","status":"completed","uuid":"2c6f8a6e-2b0e-4d0e-8f55-6c0f6f7d1a22","depends":"8ad1e2a5-7f3c-4a67-9e1c-4b1f0b2b8d11"},
+{"id":0,"description":"Gone","entry":"20240401T080000Z","status":"deleted","uuid":"5d0f4c8b-3a53-4f2e-9e8a-0f1a2b3c4d33"}
+]"#;
+
+    #[test]
+    fn parse_and_convert() {
+        let exported = parse_taskwarrior_export(EXPORT).unwrap();
+        assert_eq!(exported.len(), 3);
+        assert!(!exported[2].is_importable());
+
+        let task = exported[0].to_task().unwrap();
+        assert_eq!(task.id.to_string(), "8ad1e2a5-7f3c-4a67-9e1c-4b1f0b2b8d11");
+        assert_eq!(task.description, "Write the report");
+        assert_eq!(task.project, Some("work.reports".to_string()));
+        assert_eq!(
+            task.tags,
+            Some(vec!["next".to_string(), "writing".to_string()])
+        );
+        assert_eq!(
+            task.metadata.get("tsk-rs-task-priority"),
+            Some(&"High".to_string())
+        );
+        assert_eq!(
+            DateTime::parse_from_rfc3339(task.metadata.get("tsk-rs-task-due-time").unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+                .to_rfc3339(),
+            "2024-05-10T15:00:00+00:00"
+        );
+        assert_eq!(task.metadata.get("x-client"), Some(&"acme".to_string()));
+        assert_eq!(task.metadata.get("x-estimate"), Some(&"3".to_string()));
+        assert!(task
+            .metadata
+            .get("x-annotation-1")
+            .unwrap()
+            .ends_with("ask for numbers"));
+        assert!(!task.metadata.contains_key("x-urgency"));
+        assert!(!task.done);
+
+        let completed = exported[1].to_task().unwrap();
+        assert!(completed.done);
+        assert!(completed
+            .metadata
+            .contains_key("tsk-rs-task-completed-time"));
+        assert_eq!(completed.depends, Some(vec![task.id]));
+    }
+
+    #[test]
+    fn reimport_keeps_local_fields() {
+        let exported = parse_taskwarrior_export(EXPORT).unwrap();
+        let mut existing = exported[0].to_task().unwrap();
+        existing
+            .metadata
+            .insert("tsk-rs-task-short-id".to_string(), "7".to_string());
+        existing.start(&None).unwrap();
+
+        // importing the same task again changes nothing
+        let imported = exported[0].to_task().unwrap();
+        assert!(same_content(
+            &existing,
+            &merge_imported(&existing, &imported)
+        ));
+
+        let mut changed = exported[0].clone();
+        changed.description = "Write the final report".to_string();
+        changed.priority = None;
+        let merged = merge_imported(&existing, &changed.to_task().unwrap());
+        assert!(!same_content(&existing, &merged));
+        assert_eq!(merged.description, "Write the final report");
+        assert_eq!(merged.short_id(), Some(7));
+        assert!(merged.is_running());
+        assert!(!merged.metadata.contains_key("tsk-rs-task-priority"));
+    }
+}
+
+// eof
@@ -0,0 +1,449 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::{Datelike, Months, NaiveDate};
+use color_eyre::eyre::{bail, Context, Result};
+use serde::Serialize;
+use strum::{EnumString, IntoStaticStr};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    project::project_hierarchy,
+    settings::{BillingSettings, Rounding},
+    task::Task,
+};
+
+/// Errors that can occur while building an invoice
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum InvoiceError {
+    /// Month was not given as year and month
+    #[error("invalid month `{0}`. try 2026-09")]
+    InvalidMonth(String),
+}
+
+/// Available timesheet formats
+#[derive(EnumString, IntoStaticStr, clap::ValueEnum, Clone, Eq, PartialEq, Debug)]
+#[strum(serialize_all = "lowercase")]
+pub enum InvoiceFormat {
+    /// Markdown table ready to be pasted to an invoice
+    Markdown,
+    /// One row per line item followed by a row of totals
+    Csv,
+}
+
+/// Parse a month given as `YYYY-MM` and return the first day of it
+pub fn parse_month(input: &str) -> Result<NaiveDate> {
+    match NaiveDate::parse_from_str(&format!("{}-01", input.trim()), "%Y-%m-%d") {
+        Ok(date) => Ok(date),
+        Err(_) => bail!(InvoiceError::InvalidMonth(input.to_string())),
+    }
+}
+
+/// Round the length of a tracked time span in seconds as configured
+pub fn round_seconds(seconds: i64, billing: &BillingSettings) -> i64 {
+    let increment = billing.roundminutes * 60;
+    if increment <= 0 {
+        return seconds;
+    }
+    let increments = match billing.rounding {
+        Rounding::Up => (seconds + increment - 1) / increment,
+        Rounding::Nearest => (seconds + increment / 2) / increment,
+        Rounding::Down => seconds / increment,
+    };
+    increments * increment
+}
+
+/// Returns true if the time tracked to the task is billed. Tasks without a flag of their own
+/// follow the configured default.
+pub fn is_billable(task: &Task, billing: &BillingSettings) -> bool {
+    task.billable().unwrap_or(billing.billable)
+}
+
+/// Hourly rate of the task. The rate of the most specific level of its project is used, then
+/// the rate of its client and then the default rate.
+pub fn hourly_rate(task: &Task, billing: &BillingSettings) -> f64 {
+    let rate_of = |rates: &BTreeMap<String, f64>, name: &str| {
+        rates
+            .iter()
+            .find(|(rated, _)| rated.eq_ignore_ascii_case(name))
+            .map(|(_, rate)| *rate)
+    };
+    let project_rate = task.project.as_ref().and_then(|project| {
+        project_hierarchy(project)
+            .iter()
+            .rev()
+            .find_map(|level| rate_of(&billing.projects, level))
+    });
+    project_rate
+        .or_else(|| {
+            task.metadata
+                .get("x-client")
+                .and_then(|client| rate_of(&billing.clients, client))
+        })
+        .unwrap_or(billing.rate)
+}
+
+/// Single line item of the invoice, the time tracked to a task with the same annotation on the
+/// same day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvoiceLine {
+    /// Day the time was tracked on
+    pub date: NaiveDate,
+    /// Id of the task
+    pub task_id: Uuid,
+    /// Description of the task
+    pub task: String,
+    /// Project of the task (if any)
+    pub project: Option<String>,
+    /// Annotation of the time spans, or the description of the task if they have none
+    pub description: String,
+    /// Billed time in seconds, each time span rounded as configured
+    pub seconds: i64,
+    /// Hourly rate
+    pub rate: f64,
+    /// Billed amount
+    pub amount: f64,
+}
+
+impl InvoiceLine {
+    /// Billed time in hours
+    pub fn hours(&self) -> f64 {
+        self.seconds as f64 / 3600.0
+    }
+}
+
+/// Timesheet of billable time tracked during a month
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Invoice {
+    /// Client the invoice is for (if limited to one)
+    pub client: Option<String>,
+    /// Month of the invoice as `YYYY-MM`
+    pub month: String,
+    /// Currency of the rates and amounts
+    pub currency: String,
+    /// Line items in order of their date
+    pub lines: Vec<InvoiceLine>,
+    /// Total billed time in seconds
+    pub seconds: i64,
+    /// Total billed amount
+    pub amount: f64,
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+impl Invoice {
+    /// Total billed time in hours
+    pub fn hours(&self) -> f64 {
+        self.seconds as f64 / 3600.0
+    }
+
+    fn with_currency(&self, amount: f64) -> String {
+        if self.currency.is_empty() {
+            format!("{:.2}", amount)
+        } else {
+            format!("{:.2} {}", amount, self.currency)
+        }
+    }
+
+    /// Format the invoice as a Markdown timesheet with a row of totals
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = match &self.client {
+            Some(client) => format!("# Timesheet {} {}\n\n", markdown_cell(client), self.month),
+            None => format!("# Timesheet {}\n\n", self.month),
+        };
+        markdown.push_str("| Date | Task | Description | Hours | Rate | Amount |\n");
+        markdown.push_str("|---|---|---|---:|---:|---:|\n");
+        for line in &self.lines {
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {:.2} | {:.2} | {:.2} |\n",
+                line.date,
+                markdown_cell(&line.task),
+                markdown_cell(&line.description),
+                line.hours(),
+                line.rate,
+                line.amount
+            ));
+        }
+        markdown.push_str(&format!(
+            "| | | **Total** | **{:.2}** | | **{}** |\n",
+            self.hours(),
+            self.with_currency(self.amount)
+        ));
+        markdown
+    }
+
+    /// Write the invoice as CSV with a row per line item and a row of totals
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        csv_writer
+            .write_record([
+                "date",
+                "task_id",
+                "task",
+                "project",
+                "description",
+                "hours",
+                "rate",
+                "amount",
+            ])
+            .with_context(|| "while writing invoice as csv")?;
+        for line in &self.lines {
+            csv_writer
+                .write_record([
+                    line.date.to_string(),
+                    line.task_id.to_string(),
+                    line.task.clone(),
+                    line.project.clone().unwrap_or_default(),
+                    line.description.clone(),
+                    format!("{:.2}", line.hours()),
+                    format!("{:.2}", line.rate),
+                    format!("{:.2}", line.amount),
+                ])
+                .with_context(|| "while writing invoice as csv")?;
+        }
+        csv_writer
+            .write_record([
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                "Total".to_string(),
+                format!("{:.2}", self.hours()),
+                String::new(),
+                format!("{:.2}", self.amount),
+            ])
+            .with_context(|| "while writing invoice as csv")?;
+        csv_writer
+            .flush()
+            .with_context(|| "while writing invoice as csv")?;
+        Ok(())
+    }
+}
+
+/// Build the invoice of the billable time tracked to the tasks during the month starting from
+/// the given day, limited to the tasks of the client if one is given. Time spans are counted to
+/// the day they start on and spans that are still running are left out.
+pub fn build_invoice(
+    tasks: &[Task],
+    client: &Option<String>,
+    month: NaiveDate,
+    billing: &BillingSettings,
+) -> Invoice {
+    let month = month.with_day(1).unwrap();
+    let next_month = month + Months::new(1);
+    let mut lines: BTreeMap<(NaiveDate, Uuid, String), InvoiceLine> = BTreeMap::new();
+    for task in tasks {
+        if !is_billable(task, billing) {
+            continue;
+        }
+        if let Some(client) = client {
+            if !task
+                .metadata
+                .get("x-client")
+                .is_some_and(|task_client| task_client.eq_ignore_ascii_case(client))
+            {
+                continue;
+            }
+        }
+
+        let rate = hourly_rate(task, billing);
+        for span in task.timetracker.iter().flatten() {
+            let Some(end_time) = span.end_time else {
+                continue;
+            };
+            let date = span.start_time.date_naive();
+            if date < month || date >= next_month {
+                continue;
+            }
+            let description = span
+                .annotation
+                .clone()
+                .unwrap_or_else(|| task.description.clone());
+            let line = lines
+                .entry((date, task.id, description.clone()))
+                .or_insert_with(|| InvoiceLine {
+                    date,
+                    task_id: task.id,
+                    task: task.description.clone(),
+                    project: task.project.clone(),
+                    description,
+                    seconds: 0,
+                    rate,
+                    amount: 0.0,
+                });
+            line.seconds += round_seconds((end_time - span.start_time).num_seconds(), billing);
+            line.amount = line.hours() * rate;
+        }
+    }
+
+    let lines: Vec<InvoiceLine> = lines.into_values().collect();
+    Invoice {
+        client: client.clone(),
+        month: month.format("%Y-%m").to_string(),
+        currency: billing.currency.clone(),
+        seconds: lines.iter().map(|line| line.seconds).sum(),
+        // summing floats starts from negative zero, which would show as -0.00 on empty invoices
+        amount: lines.iter().fold(0.0, |amount, line| amount + line.amount),
+        lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TimeTrack;
+    use chrono::{Duration, Local, TimeZone};
+
+    #[test]
+    fn no_rounding_by_default() {
+        assert_eq!(round_seconds(500, &BillingSettings::default()), 500);
+    }
+
+    #[test]
+    fn rounding_up() {
+        let billing = BillingSettings {
+            roundminutes: 15,
+            ..Default::default()
+        };
+
+        assert_eq!(round_seconds(60, &billing), 15 * 60);
+        assert_eq!(round_seconds(15 * 60, &billing), 15 * 60);
+    }
+
+    #[test]
+    fn rounding_to_nearest() {
+        let billing = BillingSettings {
+            roundminutes: 15,
+            rounding: Rounding::Nearest,
+            ..Default::default()
+        };
+
+        assert_eq!(round_seconds(7 * 60, &billing), 0);
+        assert_eq!(round_seconds(8 * 60, &billing), 15 * 60);
+    }
+
+    #[test]
+    fn rounding_down() {
+        let billing = BillingSettings {
+            roundminutes: 15,
+            rounding: Rounding::Down,
+            ..Default::default()
+        };
+
+        assert_eq!(round_seconds(29 * 60, &billing), 15 * 60);
+    }
+
+    fn billing() -> BillingSettings {
+        let mut billing = BillingSettings {
+            rate: 50.0,
+            roundminutes: 15,
+            ..Default::default()
+        };
+        billing.clients.insert("acme".to_string(), 100.0);
+        billing.projects.insert("acme.support".to_string(), 80.0);
+        billing
+    }
+
+    fn task(descriptor: &str, spans: Vec<(i64, i64, Option<&str>)>) -> Task {
+        let start = Local.with_ymd_and_hms(2026, 9, 1, 9, 0, 0).unwrap();
+        let mut task = Task::from_task_descriptor(&descriptor.to_string()).unwrap();
+        task.timetracker = Some(
+            spans
+                .into_iter()
+                .map(|(day, minutes, annotation)| TimeTrack {
+                    start_time: start + Duration::days(day),
+                    end_time: Some(start + Duration::days(day) + Duration::minutes(minutes)),
+                    annotation: annotation.map(|annotation| annotation.to_string()),
+                })
+                .collect(),
+        );
+        task
+    }
+
+    /// Invoice of client acme for september 2026
+    fn invoice() -> Invoice {
+        let mut internal = task("internal meeting %x-client=acme", vec![(0, 60, None)]);
+        internal.set_billable(&Some(false));
+        let tasks = vec![
+            task(
+                "api @acme.backend %x-client=acme",
+                vec![
+                    (0, 50, Some("review")),
+                    (0, 20, Some("review")),
+                    (1, 60, None),
+                    // october is left out
+                    (30, 60, None),
+                ],
+            ),
+            task(
+                "hotline @acme.support.phone %x-client=Acme",
+                vec![(2, 30, None)],
+            ),
+            task("other client %x-client=globex", vec![(0, 60, None)]),
+            internal,
+        ];
+        build_invoice(
+            &tasks,
+            &Some("acme".to_string()),
+            parse_month("2026-09").unwrap(),
+            &billing(),
+        )
+    }
+
+    #[test]
+    fn invoice_has_billable_time_of_client_and_month() {
+        let invoice = invoice();
+
+        assert_eq!(invoice.month, "2026-09");
+        let descriptions: Vec<&str> = invoice
+            .lines
+            .iter()
+            .map(|line| line.description.as_str())
+            .collect();
+        assert_eq!(descriptions, vec!["review", "api", "hotline"]);
+    }
+
+    #[test]
+    fn spans_are_rounded_before_summing() {
+        let invoice = invoice();
+
+        assert_eq!(invoice.lines[0].seconds, 90 * 60);
+        assert_eq!(invoice.lines[0].amount, 150.0);
+    }
+
+    #[test]
+    fn project_rate_precedes_client_rate() {
+        let invoice = invoice();
+
+        assert_eq!(invoice.lines[0].rate, 100.0);
+        assert_eq!(invoice.lines[2].rate, 80.0);
+    }
+
+    #[test]
+    fn totals_sum_the_lines() {
+        let invoice = invoice();
+
+        assert_eq!(invoice.seconds, 180 * 60);
+        assert_eq!(invoice.amount, 290.0);
+        assert!(invoice
+            .to_markdown()
+            .ends_with("| | | **Total** | **3.00** | | **290.00** |\n"));
+    }
+
+    #[test]
+    fn invalid_month() {
+        assert_eq!(
+            parse_month("2026-13")
+                .unwrap_err()
+                .downcast::<InvoiceError>()
+                .unwrap(),
+            InvoiceError::InvalidMonth("2026-13".to_string())
+        );
+    }
+}
+
+// eof
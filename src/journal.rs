@@ -0,0 +1,801 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    env,
+    fs::{metadata, read_to_string, rename, write, File, OpenOptions},
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local};
+use color_eyre::eyre::{bail, Context, Result};
+use file_lock::{FileLock, FileOptions};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use crate::note::Note;
+use crate::{
+    settings::Settings,
+    storage::{open_storage, Storage},
+    task::Task,
+};
+
+/// Errors that can occur when replaying the journal
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum JournalError {
+    /// There are no recorded operations left to undo
+    #[error("nothing to undo")]
+    NothingToUndo,
+    /// There are no undone operations to redo
+    #[error("nothing to redo")]
+    NothingToRedo,
+}
+
+/// What kind of an entry was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalSubject {
+    /// Change to a task
+    Task,
+    /// Change to a note
+    Note,
+}
+
+/// Single change to a task or a note, with YAML snapshots of the entry before and after it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalChange {
+    /// Operation the change is part of
+    pub group: Uuid,
+    /// When the change was made
+    pub time: DateTime<Local>,
+    /// Command line that made the change
+    pub command: String,
+    /// Kind of the changed entry
+    pub subject: JournalSubject,
+    /// Id of the changed task, or the task of the changed note
+    pub id: Uuid,
+    /// Entry before the change, none if the entry was created
+    pub before: Option<String>,
+    /// Entry after the change, none if the entry was deleted
+    pub after: Option<String>,
+}
+
+/// Line in the journal file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum JournalRecord {
+    Change(JournalChange),
+    Undo { group: Uuid, time: DateTime<Local> },
+    Redo { group: Uuid, time: DateTime<Local> },
+}
+
+/// Operation made by a single command, consisting of one or more changes
+#[derive(Debug, Clone)]
+pub struct Operation {
+    /// Identifier of the operation
+    pub group: Uuid,
+    /// When the operation was made
+    pub time: DateTime<Local>,
+    /// Command line that made the operation
+    pub command: String,
+    /// Changes in the order they were made
+    pub changes: Vec<JournalChange>,
+    /// True if the operation is currently undone
+    pub undone: bool,
+}
+
+impl Operation {
+    /// Returns descriptions of the tasks changed by the operation
+    pub fn descriptions(&self) -> Vec<String> {
+        self.changes
+            .iter()
+            .filter(|change| change.subject == JournalSubject::Task)
+            .filter_map(|change| change.after.as_ref().or(change.before.as_ref()))
+            .filter_map(|document| Task::from_yaml_string(document).ok())
+            .map(|task| task.description)
+            .collect()
+    }
+}
+
+thread_local! {
+    static OPERATION: RefCell<Option<(Uuid, String)>> = const { RefCell::new(None) };
+}
+
+/// Every change made by this process belongs to the same operation, so that a single command is
+/// undone as a whole. The operation is kept per thread, the commands make their changes on a
+/// single thread.
+fn current_operation() -> (Uuid, String) {
+    OPERATION.with_borrow_mut(|operation| {
+        operation
+            .get_or_insert_with(|| {
+                let command = env::args()
+                    .enumerate()
+                    .map(|(index, arg)| {
+                        if index == 0 {
+                            // only the name of the binary, not the path
+                            PathBuf::from(arg)
+                                .file_name()
+                                .map(|name| name.to_string_lossy().to_string())
+                                .unwrap_or_default()
+                        } else {
+                            arg
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                (Uuid::new_v4(), command)
+            })
+            .clone()
+    })
+}
+
+/// Start a new operation described by the command. The changes made after this are undone
+/// separately from the earlier changes of the process, which is needed by long running commands
+/// like the terminal user interface.
+pub fn begin_operation(command: &str) {
+    OPERATION.set(Some((Uuid::new_v4(), command.to_string())));
+}
+
+fn journal_pathbuf(settings: &Settings) -> Result<PathBuf> {
+    Ok(settings.db_pathbuf()?.join("journal.jsonl"))
+}
+
+fn state_pathbuf(settings: &Settings) -> Result<PathBuf> {
+    Ok(settings.db_pathbuf()?.join("journal-state.json"))
+}
+
+/// Lock the journal of the namespace. The lock is held until the returned lock is dropped.
+fn lock_journal(settings: &Settings) -> Result<FileLock> {
+    let options = FileOptions::new().write(true).create(true).append(true);
+    FileLock::lock(settings.db_pathbuf()?.join("journal.lock"), true, options)
+        .with_context(|| "while locking journal")
+}
+
+/// Operation in the journal file. The records of its changes lie between the byte offsets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct OperationSpan {
+    group: Uuid,
+    start: u64,
+    end: u64,
+}
+
+/// Operations in effect and the undone ones, most recent last, so that undo and redo read only
+/// the records of the operation at hand. The state is valid for the journal file of the recorded
+/// length, otherwise it is rebuilt by replaying the journal.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalState {
+    length: u64,
+    done: Vec<OperationSpan>,
+    undone: Vec<OperationSpan>,
+}
+
+impl JournalState {
+    /// Apply the record found at the offset of the journal file
+    fn apply(&mut self, record: &JournalRecord, start: u64, end: u64) {
+        match record {
+            JournalRecord::Change(change) => {
+                // the most recent operations are the ones that still receive changes
+                match self
+                    .done
+                    .iter_mut()
+                    .rev()
+                    .find(|span| span.group == change.group)
+                {
+                    Some(span) => span.end = end,
+                    None => {
+                        self.done.push(OperationSpan {
+                            group: change.group,
+                            start,
+                            end,
+                        });
+                        // a new operation makes the undone ones unreachable for redo
+                        self.undone.clear();
+                    }
+                }
+            }
+            JournalRecord::Undo { group, .. } => move_span(group, &mut self.done, &mut self.undone),
+            JournalRecord::Redo { group, .. } => move_span(group, &mut self.undone, &mut self.done),
+        }
+        self.length = end;
+    }
+
+    /// Replay the whole journal
+    fn rebuild(settings: &Settings) -> Result<Self> {
+        let mut state = Self::default();
+        for (record, start, end) in read_records(settings, 0, None)? {
+            state.apply(&record, start, end);
+        }
+        Ok(state)
+    }
+
+    /// Load the state of the journal, rebuilding it if the journal has changed behind it
+    fn load(settings: &Settings) -> Result<Self> {
+        let journal_length = match metadata(journal_pathbuf(settings)?) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return Ok(Self::default()),
+        };
+        let state: Option<Self> = read_to_string(state_pathbuf(settings)?)
+            .ok()
+            .and_then(|state_json| serde_json::from_str(&state_json).ok());
+        match state {
+            Some(state) if state.length == journal_length => Ok(state),
+            _ => Self::rebuild(settings),
+        }
+    }
+
+    fn save(&self, settings: &Settings) -> Result<()> {
+        let pathbuf = state_pathbuf(settings)?;
+        let temporary = pathbuf.with_extension("json.tmp");
+        write(
+            &temporary,
+            serde_json::to_string(self).with_context(|| "while serializing journal state")?,
+        )
+        .with_context(|| "while writing journal state file")?;
+        rename(&temporary, pathbuf).with_context(|| "while replacing journal state file")
+    }
+
+    /// Drop the oldest operations in effect when there are more than the limit, and rewrite the
+    /// journal with the records of the remaining operations only. Zero keeps every operation.
+    fn compact(&mut self, limit: usize, settings: &Settings) -> Result<()> {
+        if limit == 0 || self.done.len() + self.undone.len() <= limit {
+            return Ok(());
+        }
+        let dropped = (self.done.len() + self.undone.len() - limit).min(self.done.len());
+        self.done.drain(..dropped);
+
+        // operations in effect first and the undone ones in the order they were made, then the
+        // undo records, so that replaying the journal leads to the same state
+        let mut records: Vec<JournalRecord> = vec![];
+        for span in self.done.iter().chain(self.undone.iter().rev()) {
+            records.extend(
+                read_span(span, settings)?
+                    .into_iter()
+                    .map(JournalRecord::Change),
+            );
+        }
+        records.extend(self.undone.iter().map(|span| JournalRecord::Undo {
+            group: span.group,
+            time: chrono::offset::Local::now(),
+        }));
+
+        let pathbuf = journal_pathbuf(settings)?;
+        let temporary = pathbuf.with_extension("jsonl.tmp");
+        let mut file = File::create(&temporary).with_context(|| "while creating journal file")?;
+        let mut state = Self::default();
+        for record in records {
+            let line = to_line(&record)?;
+            file.write_all(line.as_bytes())
+                .with_context(|| "while writing to journal file")?;
+            let start = state.length;
+            state.apply(&record, start, start + line.len() as u64);
+        }
+        rename(&temporary, pathbuf).with_context(|| "while replacing journal file")?;
+
+        *self = state;
+        Ok(())
+    }
+}
+
+/// Move the operation from one stack to the other
+fn move_span(group: &Uuid, from: &mut Vec<OperationSpan>, to: &mut Vec<OperationSpan>) {
+    if let Some(index) = from.iter().rposition(|span| span.group == *group) {
+        to.push(from.remove(index));
+    }
+}
+
+fn to_line(record: &JournalRecord) -> Result<String> {
+    Ok(format!(
+        "{}\n",
+        serde_json::to_string(record).with_context(|| "while serializing journal record")?
+    ))
+}
+
+/// Append the record to the journal and bring the state up to date with it
+fn append(record: &JournalRecord, state: &mut JournalState, settings: &Settings) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_pathbuf(settings)?)
+        .with_context(|| "while opening journal file")?;
+    let line = to_line(record)?;
+    file.write_all(line.as_bytes())
+        .with_context(|| "while writing to journal file")?;
+    let start = state.length;
+    state.apply(record, start, start + line.len() as u64);
+    Ok(())
+}
+
+/// Read the records of the journal from the offset on, up to the end offset if one is given.
+/// Returns the records along with the offsets they start and end at.
+fn read_records(
+    settings: &Settings,
+    start: u64,
+    end: Option<u64>,
+) -> Result<Vec<(JournalRecord, u64, u64)>> {
+    let pathbuf = journal_pathbuf(settings)?;
+    if !pathbuf.is_file() {
+        return Ok(vec![]);
+    }
+    let mut reader =
+        BufReader::new(File::open(pathbuf).with_context(|| "while opening journal file")?);
+    reader
+        .seek(SeekFrom::Start(start))
+        .with_context(|| "while seeking journal file")?;
+
+    let mut records: Vec<(JournalRecord, u64, u64)> = vec![];
+    let mut offset = start;
+    let mut line = String::new();
+    while end.is_none_or(|end| offset < end) {
+        line.clear();
+        let length = reader
+            .read_line(&mut line)
+            .with_context(|| "while reading journal file")? as u64;
+        if length == 0 {
+            break;
+        }
+        if !line.trim().is_empty() {
+            records.push((
+                serde_json::from_str(&line).with_context(|| "while parsing journal record")?,
+                offset,
+                offset + length,
+            ));
+        }
+        offset += length;
+    }
+    Ok(records)
+}
+
+/// Read the changes of the operation. Changes recorded by other processes in between are skipped.
+fn read_span(span: &OperationSpan, settings: &Settings) -> Result<Vec<JournalChange>> {
+    Ok(read_records(settings, span.start, Some(span.end))?
+        .into_iter()
+        .filter_map(|(record, _, _)| match record {
+            JournalRecord::Change(change) if change.group == span.group => Some(change),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Record a change to a task or a note in the journal of the namespace
+pub fn record_change(
+    subject: JournalSubject,
+    id: &Uuid,
+    before: Option<String>,
+    after: Option<String>,
+    settings: &Settings,
+) -> Result<()> {
+    let (group, command) = current_operation();
+    let _lock = lock_journal(settings)?;
+    let mut state = JournalState::load(settings)?;
+    append(
+        &JournalRecord::Change(JournalChange {
+            group,
+            time: chrono::offset::Local::now(),
+            command,
+            subject,
+            id: *id,
+            before,
+            after,
+        }),
+        &mut state,
+        settings,
+    )?;
+    state
+        .compact(settings.data.journallimit, settings)
+        .with_context(|| "while compacting journal")?;
+    state.save(settings)
+}
+
+/// Collect the changes to operations, oldest first
+fn operations(changes: Vec<JournalChange>, undone: &HashSet<Uuid>) -> Vec<Operation> {
+    let mut positions: HashMap<Uuid, usize> = HashMap::new();
+    let mut operations: Vec<Operation> = vec![];
+    for change in changes {
+        match positions.get(&change.group) {
+            Some(position) => operations[*position].changes.push(change),
+            None => {
+                positions.insert(change.group, operations.len());
+                operations.push(Operation {
+                    group: change.group,
+                    time: change.time,
+                    command: change.command.clone(),
+                    undone: undone.contains(&change.group),
+                    changes: vec![change],
+                });
+            }
+        }
+    }
+    operations
+}
+
+/// Returns the operations recorded in the journal of the namespace, oldest first
+pub fn history(settings: &Settings) -> Result<Vec<Operation>> {
+    let state = JournalState::load(settings)?;
+    let undone: HashSet<Uuid> = state.undone.iter().map(|span| span.group).collect();
+    let changes = read_records(settings, 0, None)?
+        .into_iter()
+        .filter_map(|(record, _, _)| match record {
+            JournalRecord::Change(change) => Some(change),
+            _ => None,
+        })
+        .collect();
+    Ok(operations(changes, &undone))
+}
+
+/// Write the snapshot of the entry to the storage, or delete the entry if there is no snapshot
+fn restore(
+    storage: &dyn Storage,
+    subject: JournalSubject,
+    id: &Uuid,
+    snapshot: &Option<String>,
+) -> Result<()> {
+    match subject {
+        JournalSubject::Task => match snapshot {
+            Some(document) => storage.save_task(&mut Task::from_yaml_string(document)?),
+            None if storage.task_exists(id)? => storage.delete_task(id),
+            None => Ok(()),
+        },
+        #[cfg(feature = "note")]
+        JournalSubject::Note => match snapshot {
+            Some(document) => storage.save_note(&mut Note::from_yaml_string(document)?),
+            None if storage.note_exists(id)? => storage.delete_note(id),
+            None => Ok(()),
+        },
+        #[cfg(not(feature = "note"))]
+        JournalSubject::Note => Ok(()),
+    }
+}
+
+/// Undo the most recent operation in effect by restoring the entries it changed to their
+/// previous state. Deleted tasks and notes are restored as well.
+pub fn undo(settings: &Settings) -> Result<Operation> {
+    let _lock = lock_journal(settings)?;
+    let mut state = JournalState::load(settings)?;
+    let Some(span) = state.done.last().cloned() else {
+        bail!(JournalError::NothingToUndo);
+    };
+    let mut operation = operations(read_span(&span, settings)?, &HashSet::new())
+        .pop()
+        .ok_or(JournalError::NothingToUndo)?;
+
+    let storage = open_storage(settings)?;
+    for change in operation.changes.iter().rev() {
+        restore(storage.as_ref(), change.subject, &change.id, &change.before)
+            .with_context(|| "while undoing a change")?;
+    }
+    append(
+        &JournalRecord::Undo {
+            group: span.group,
+            time: chrono::offset::Local::now(),
+        },
+        &mut state,
+        settings,
+    )?;
+    state.save(settings)?;
+
+    operation.undone = true;
+    Ok(operation)
+}
+
+/// Redo the most recently undone operation
+pub fn redo(settings: &Settings) -> Result<Operation> {
+    let _lock = lock_journal(settings)?;
+    let mut state = JournalState::load(settings)?;
+    let Some(span) = state.undone.last().cloned() else {
+        bail!(JournalError::NothingToRedo);
+    };
+    let operation = operations(read_span(&span, settings)?, &HashSet::new())
+        .pop()
+        .ok_or(JournalError::NothingToRedo)?;
+
+    let storage = open_storage(settings)?;
+    for change in operation.changes.iter() {
+        restore(storage.as_ref(), change.subject, &change.id, &change.after)
+            .with_context(|| "while redoing a change")?;
+    }
+    append(
+        &JournalRecord::Redo {
+            group: span.group,
+            time: chrono::offset::Local::now(),
+        },
+        &mut state,
+        settings,
+    )?;
+    state.save(settings)?;
+
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "note")]
+    use crate::{
+        note::{delete_note, load_note, save_note},
+        task::delete_task,
+    };
+    use crate::{
+        bulk::modify_tasks,
+        storage::StorageBackend,
+        task::{load_task, save_task},
+        testing::TestData,
+    };
+
+    fn backends() -> Vec<StorageBackend> {
+        vec![
+            StorageBackend::Flatfile,
+            #[cfg(feature = "sqlite")]
+            StorageBackend::Sqlite,
+        ]
+    }
+
+    fn settings(backend: StorageBackend) -> TestData {
+        TestData::with(|settings| settings.data.backend = backend)
+    }
+
+    /// Save a new task as an operation of its own
+    fn new_task(description: &str, settings: &Settings) -> Task {
+        begin_operation(&format!("tsk new {}", description));
+        let mut task = Task::new(description.to_string()).unwrap();
+        save_task(&mut task, settings).unwrap();
+        task
+    }
+
+    fn change(group: Uuid) -> JournalRecord {
+        JournalRecord::Change(JournalChange {
+            group,
+            time: Local::now(),
+            command: "tsk done 1".to_string(),
+            subject: JournalSubject::Task,
+            id: Uuid::new_v4(),
+            before: None,
+            after: None,
+        })
+    }
+
+    fn undo(group: Uuid) -> JournalRecord {
+        JournalRecord::Undo {
+            group,
+            time: Local::now(),
+        }
+    }
+
+    fn redo(group: Uuid) -> JournalRecord {
+        JournalRecord::Redo {
+            group,
+            time: Local::now(),
+        }
+    }
+
+    /// Apply the records as if they were read from the journal, returns the groups in effect
+    /// and the undone ones
+    fn replay(records: &[JournalRecord]) -> (Vec<Uuid>, Vec<Uuid>) {
+        let mut state = JournalState::default();
+        for (index, record) in records.iter().enumerate() {
+            state.apply(record, index as u64, index as u64 + 1);
+        }
+        let groups = |spans: &[OperationSpan]| spans.iter().map(|span| span.group).collect();
+        (groups(&state.done), groups(&state.undone))
+    }
+
+    fn changes(records: &[JournalRecord]) -> Vec<JournalChange> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                JournalRecord::Change(change) => Some(change.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn groups(spans: &[OperationSpan]) -> Vec<Uuid> {
+        spans.iter().map(|span| span.group).collect()
+    }
+
+    #[test]
+    fn changes_of_a_group_form_one_operation() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let records = vec![change(first), change(first), change(second)];
+
+        assert_eq!(replay(&records), (vec![first, second], vec![]));
+        let operations = operations(changes(&records), &HashSet::new());
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].changes.len(), 2);
+    }
+
+    #[test]
+    fn undo_moves_operations_to_undone() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let records = vec![change(first), change(second), undo(second), undo(first)];
+
+        assert_eq!(replay(&records), (vec![], vec![second, first]));
+    }
+
+    #[test]
+    fn redo_brings_back_latest_undone() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let records = vec![
+            change(first),
+            change(second),
+            undo(second),
+            undo(first),
+            redo(first),
+        ];
+
+        let (_, undone) = replay(&records);
+        assert_eq!(undone, vec![second]);
+        let operations = operations(changes(&records), &undone.into_iter().collect());
+        assert!(!operations[0].undone);
+        assert!(operations[1].undone);
+    }
+
+    #[test]
+    fn new_operation_clears_redo() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let records = vec![change(first), undo(first), change(second)];
+
+        assert_eq!(replay(&records), (vec![second], vec![]));
+    }
+
+    #[test]
+    fn spans_locate_changes_of_an_operation() {
+        let settings = TestData::new();
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let mut state = JournalState::default();
+        for record in [change(first), change(second), change(first)] {
+            append(&record, &mut state, &settings).unwrap();
+        }
+
+        assert_eq!(
+            state.length,
+            metadata(journal_pathbuf(&settings).unwrap()).unwrap().len()
+        );
+        // the change of the other operation in between is skipped
+        assert_eq!(read_span(&state.done[0], &settings).unwrap().len(), 2);
+        assert_eq!(read_span(&state.done[1], &settings).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn stale_state_is_rebuilt() {
+        let settings = TestData::new();
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let mut state = JournalState::default();
+        append(&change(first), &mut state, &settings).unwrap();
+        state.save(&settings).unwrap();
+        // another record appended without updating the state
+        append(&change(second), &mut state, &settings).unwrap();
+        append(&undo(second), &mut state, &settings).unwrap();
+
+        let loaded = JournalState::load(&settings).unwrap();
+        assert_eq!(groups(&loaded.done), vec![first]);
+        assert_eq!(groups(&loaded.undone), vec![second]);
+        assert_eq!(loaded.undone, state.undone);
+    }
+
+    #[test]
+    fn compaction_keeps_most_recent_operations() {
+        let settings = TestData::new();
+        let operations: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let mut state = JournalState::default();
+        for group in &operations {
+            append(&change(*group), &mut state, &settings).unwrap();
+            append(&change(*group), &mut state, &settings).unwrap();
+        }
+        append(&undo(operations[4]), &mut state, &settings).unwrap();
+        let before = metadata(journal_pathbuf(&settings).unwrap()).unwrap().len();
+
+        state.compact(3, &settings).unwrap();
+        assert_eq!(groups(&state.done), operations[2..4].to_vec());
+        assert_eq!(groups(&state.undone), vec![operations[4]]);
+        assert!(state.length < before);
+
+        // the rewritten journal replays to the same state and the spans point to the changes
+        let rebuilt = JournalState::rebuild(&settings).unwrap();
+        assert_eq!(rebuilt.done, state.done);
+        assert_eq!(rebuilt.undone, state.undone);
+        for span in state.done.iter().chain(state.undone.iter()) {
+            assert_eq!(read_span(span, &settings).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn undo_restores_edited_task() {
+        for backend in backends() {
+            let settings = settings(backend);
+            let mut task = new_task("first", &settings);
+            begin_operation("tsk edit");
+            task.description = "edited".to_string();
+            save_task(&mut task, &settings).unwrap();
+
+            let operation = super::undo(&settings).unwrap();
+            assert_eq!(operation.command, "tsk edit");
+            let id = task.id.to_string();
+            assert_eq!(load_task(&id, &settings).unwrap().description, "first");
+
+            super::redo(&settings).unwrap();
+            assert_eq!(load_task(&id, &settings).unwrap().description, "edited");
+        }
+    }
+
+    #[cfg(feature = "note")]
+    #[test]
+    fn undo_restores_deleted_task_and_note() {
+        for backend in backends() {
+            let settings = settings(backend);
+            let task = new_task("first", &settings);
+            let id = task.id.to_string();
+            let mut note = Note::new(&task.id);
+            note.markdown = Some("# notes".to_string());
+            save_note(&mut note, &settings).unwrap();
+            begin_operation("tsk delete");
+            delete_note(&id, &settings).unwrap();
+            delete_task(&id, &settings).unwrap();
+
+            let operation = super::undo(&settings).unwrap();
+            assert_eq!(operation.changes.len(), 2);
+            assert_eq!(load_task(&id, &settings).unwrap().description, "first");
+            assert_eq!(
+                load_note(&id, &settings).unwrap().markdown,
+                Some("# notes".to_string())
+            );
+
+            super::redo(&settings).unwrap();
+            let storage = open_storage(&settings).unwrap();
+            assert!(!storage.task_exists(&task.id).unwrap());
+            assert!(!storage.note_exists(&task.id).unwrap());
+        }
+    }
+
+    #[test]
+    fn bulk_operation_is_undone_as_one() {
+        for backend in backends() {
+            let settings = settings(backend);
+            let tasks = vec![
+                new_task("first", &settings),
+                new_task("second", &settings),
+                new_task("third", &settings),
+            ];
+            begin_operation("tsk set --filter");
+            modify_tasks(
+                tasks.clone(),
+                |task| {
+                    task.project = Some("bulk".to_string());
+                    Ok(true)
+                },
+                &settings,
+            )
+            .unwrap();
+
+            let operation = super::undo(&settings).unwrap();
+            assert_eq!(operation.changes.len(), 3);
+            for task in &tasks {
+                let restored = load_task(&task.id.to_string(), &settings).unwrap();
+                assert_eq!(restored.project, None);
+            }
+            // the tasks created before are undone one by one
+            assert_eq!(super::undo(&settings).unwrap().command, "tsk new third");
+        }
+    }
+
+    #[test]
+    fn zero_limit_keeps_every_operation() {
+        let settings = TestData::new();
+        let mut state = JournalState::default();
+        for _ in 0..5 {
+            append(&change(Uuid::new_v4()), &mut state, &settings).unwrap();
+        }
+
+        state.compact(0, &settings).unwrap();
+        assert_eq!(state.done.len(), 5);
+    }
+}
+
+// eof
@@ -20,6 +20,45 @@ pub mod settings;
 /// Task abtsractions
 pub mod task;
 
+/// Storage backends for tasks and notes
+pub mod storage;
+
+/// Identifier resolution utilities
+pub mod id;
+
+/// Journal of task and note changes for undo and redo
+pub mod journal;
+
+/// Trash for deleted tasks and notes
+pub mod trash;
+
+/// Archive for completed tasks and their notes
+pub mod archive;
+
+/// Operations on several tasks at once
+pub mod bulk;
+
+/// Recurrence rules for repeating tasks
+pub mod recurrence;
+
+/// Time tracking reports
+pub mod report;
+
+/// Accuracy of effort estimates compared to tracked time
+pub mod estimate;
+
+/// Billable time and timesheets for invoicing
+pub mod invoice;
+
+/// Import tasks from other task managers
+pub mod import;
+
+/// Export tasks and notes to other formats
+pub mod export;
+
+/// Output formats and exit statuses of the command line tools
+pub mod output;
+
 /// Namespace utilities
 pub mod namespace;
 
@@ -29,8 +68,28 @@ pub mod tag;
 /// Project management utilities
 pub mod project;
 
+/// Subtasks and progress rolled up the task tree
+pub mod subtask;
+
+/// Logging and editing of tracked time spans
+pub mod track;
+
+/// Running tasks and switching between them
+pub mod active;
+
+/// Focus sessions of pomodoros on top of time tracking
+pub mod focus;
+
 /// Async database watchers through notify crate
 #[cfg(feature = "notify")]
 pub mod notify;
 
+/// Interactive terminal user interface
+#[cfg(feature = "tui")]
+pub mod tui;
+
+/// Temporary data directories for tests
+#[cfg(test)]
+mod testing;
+
 // eof
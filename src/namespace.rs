@@ -1,7 +1,34 @@
-use std::fs;
+use std::{fs, path::PathBuf};
 
-use crate::settings::Settings;
-use color_eyre::eyre::{Context, Result};
+use crate::{
+    settings::Settings,
+    task::{delete_task, load_task, save_task, Task},
+};
+use color_eyre::eyre::{bail, Context, Result};
+use thiserror::Error;
+
+#[cfg(feature = "note")]
+use crate::{
+    note::{delete_note, load_note, save_note},
+    storage::open_storage,
+};
+
+/// Errors that can occur when managing namespaces
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NamespaceError {
+    /// Namespace name can not be used as a directory name
+    #[error("invalid namespace name `{0}`, use letters, numbers, '-' and '_' only")]
+    InvalidName(String),
+    /// Namespace exists already
+    #[error("namespace `{0}` already exists")]
+    AlreadyExists(String),
+    /// Namespace does not exist
+    #[error("namespace `{0}` does not exist")]
+    NotFound(String),
+    /// Task was asked to be moved to the namespace it already is in
+    #[error("task is already in namespace `{0}`")]
+    SameNamespace(String),
+}
 
 /// Namespace abstraction and metadata
 pub struct Namespace {
@@ -20,19 +47,170 @@ pub fn list_namespaces(settings: &Settings) -> Result<Vec<Namespace>> {
         let entry = entry?;
         if entry.metadata()?.is_dir() {
             let name = entry.file_name().to_str().unwrap().to_string(); // TODO: fix unwrap
-            let is_current = if name == settings.namespace { true } else { false };
+            let is_current = name == settings.namespace;
             namespaces.push(Namespace { is_current, name });
         }
     }
 
     // if no namespaces found, add the default one and set it active
-    if namespaces.len() == 0 {
+    if namespaces.is_empty() {
         namespaces.push(
             Namespace { name: "default".into(), is_current: true }
         );
     }
+    namespaces.sort_by(|a, b| a.name.cmp(&b.name));
 
     Ok(namespaces)
 }
 
-// eof
\ No newline at end of file
+/// Returns the path of the file that holds the name of the current namespace
+fn current_namespace_pathbuf(settings: &Settings) -> PathBuf {
+    PathBuf::from(&settings.data.path).join("current-namespace")
+}
+
+/// Returns the path of the namespace directory
+fn namespace_pathbuf(name: &str, settings: &Settings) -> PathBuf {
+    PathBuf::from(&settings.data.path).join(name)
+}
+
+/// Make sure the namespace name is usable as a directory name
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!(NamespaceError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Make sure the namespace exists in the data directory
+fn require_namespace(name: &str, settings: &Settings) -> Result<()> {
+    validate_name(name)?;
+    if !namespace_pathbuf(name, settings).is_dir() {
+        bail!(NamespaceError::NotFound(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Returns the namespace used when none is given on the command line or in the environment. This
+/// is the namespace last chosen with [set_current_namespace] or "default".
+pub fn current_namespace(settings: &Settings) -> Result<String> {
+    let pathbuf = current_namespace_pathbuf(settings);
+    if !pathbuf.is_file() {
+        return Ok("default".to_string());
+    }
+    let name = fs::read_to_string(pathbuf)
+        .with_context(|| "while reading current namespace")?
+        .trim()
+        .to_string();
+    if name.is_empty() {
+        return Ok("default".to_string());
+    }
+    Ok(name)
+}
+
+/// Set the namespace used when none is given on the command line or in the environment
+pub fn set_current_namespace(name: &str, settings: &Settings) -> Result<()> {
+    require_namespace(name, settings)?;
+    fs::write(current_namespace_pathbuf(settings), format!("{}\n", name))
+        .with_context(|| "while writing current namespace")
+}
+
+/// Create a new empty namespace
+pub fn create_namespace(name: &str, settings: &Settings) -> Result<()> {
+    validate_name(name)?;
+    let pathbuf = namespace_pathbuf(name, settings);
+    if pathbuf.exists() {
+        bail!(NamespaceError::AlreadyExists(name.to_string()));
+    }
+    fs::create_dir_all(pathbuf).with_context(|| "while creating namespace directory")
+}
+
+/// Rename the namespace. If it was the current namespace the new name becomes current.
+pub fn rename_namespace(name: &str, new_name: &str, settings: &Settings) -> Result<()> {
+    require_namespace(name, settings)?;
+    validate_name(new_name)?;
+    let new_pathbuf = namespace_pathbuf(new_name, settings);
+    if new_pathbuf.exists() {
+        bail!(NamespaceError::AlreadyExists(new_name.to_string()));
+    }
+    fs::rename(namespace_pathbuf(name, settings), new_pathbuf)
+        .with_context(|| "while renaming namespace directory")?;
+
+    if current_namespace(settings)? == name {
+        set_current_namespace(new_name, settings)?;
+    }
+    Ok(())
+}
+
+/// Delete the namespace with all of its tasks and notes. If it was the current namespace the
+/// current namespace falls back to "default".
+pub fn delete_namespace(name: &str, settings: &Settings) -> Result<()> {
+    require_namespace(name, settings)?;
+    fs::remove_dir_all(namespace_pathbuf(name, settings))
+        .with_context(|| "while removing namespace directory")?;
+
+    if current_namespace(settings)? == name {
+        fs::remove_file(current_namespace_pathbuf(settings))
+            .with_context(|| "while resetting current namespace")?;
+    }
+    Ok(())
+}
+
+/// Move the task and its note (if any) to another namespace. The task gets a new short id in
+/// the target namespace.
+pub fn move_task(id: &str, namespace: &str, settings: &Settings) -> Result<Task> {
+    require_namespace(namespace, settings)?;
+    if namespace == settings.namespace {
+        bail!(NamespaceError::SameNamespace(namespace.to_string()));
+    }
+    let target_settings = Settings {
+        namespace: namespace.to_string(),
+        ..settings.clone()
+    };
+
+    let mut task = load_task(id, settings)?;
+
+    // short ids are unique only inside a namespace
+    task.metadata.remove("tsk-rs-task-short-id");
+    save_task(&mut task, &target_settings)
+        .with_context(|| "while saving task to target namespace")?;
+
+    #[cfg(feature = "note")]
+    if open_storage(settings)?.note_exists(&task.id)? {
+        let mut note = load_note(&task.id.to_string(), settings)?;
+        save_note(&mut note, &target_settings)
+            .with_context(|| "while saving note to target namespace")?;
+        delete_note(&task.id.to_string(), settings)
+            .with_context(|| "while deleting note from source namespace")?;
+    }
+
+    delete_task(&task.id.to_string(), settings)
+        .with_context(|| "while deleting task from source namespace")?;
+
+    Ok(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespace_names() {
+        assert!(validate_name("work").is_ok());
+        assert!(validate_name("side-project_2").is_ok());
+        for name in ["", "..", "a/b", "with space"] {
+            assert_eq!(
+                validate_name(name)
+                    .unwrap_err()
+                    .downcast::<NamespaceError>()
+                    .unwrap(),
+                NamespaceError::InvalidName(name.to_string())
+            );
+        }
+    }
+}
+
+// eof
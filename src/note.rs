@@ -1,6 +1,5 @@
 use color_eyre::eyre::{bail, Context, Result};
 use file_lock::{FileLock, FileOptions};
-use glob::glob;
 use markdown::{self, mdast::Node};
 use serde::{Deserialize, Serialize};
 use simple_file_rotation::FileRotation;
@@ -15,9 +14,13 @@ use thiserror::Error;
 use uuid::Uuid;
 
 use crate::{
+    id::{find_ids_by_prefix, resolve_candidates},
+    journal::{record_change, JournalSubject},
     metadata::MetadataKeyValuePair,
+    parser::filter_lexicon::Filter,
     settings::Settings,
-    task::{load_task, task_pathbuf_from_id, Task},
+    storage::open_storage,
+    task::{task_id_from_short_id, Task},
 };
 
 #[cfg(feature = "notify")]
@@ -107,7 +110,7 @@ impl Note {
     pub fn save_yaml_file_to(&mut self, note_pathbuf: &PathBuf, rotate: &usize) -> Result<()> {
         // rotate existing file with same name if present
         if note_pathbuf.is_file() && rotate > &0 {
-            FileRotation::new(&note_pathbuf)
+            FileRotation::new(note_pathbuf)
                 .max_old_files(*rotate)
                 .file_extension("yaml".to_string())
                 .rotate()
@@ -200,7 +203,7 @@ fn parse_md_component(task_id: &Uuid, node: &Node) -> Result<Option<Vec<ActionPo
     }
 
     if let Node::ListItem(list_node) = node {
-        if list_node.checked.is_some() {
+        if let Some(checked) = list_node.checked {
             let action_description_paragraphs = list_node.children.clone().pop().unwrap();
             let action_description = match action_description_paragraphs
                 .children()
@@ -218,7 +221,7 @@ fn parse_md_component(task_id: &Uuid, node: &Node) -> Result<Option<Vec<ActionPo
                     format!("tsk-rs://{}/{}", task_id, action_description).as_bytes(),
                 ),
                 description: action_description,
-                checked: list_node.checked.unwrap(),
+                checked,
             });
         }
     }
@@ -231,7 +234,7 @@ fn parse_md_component(task_id: &Uuid, node: &Node) -> Result<Option<Vec<ActionPo
 }
 
 /// ActionPoint abstraction
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ActionPoint {
     /// Unique id of this action point
     pub id: Uuid,
@@ -253,23 +256,71 @@ pub fn note_pathbuf_from_note(note: &Note, settings: &Settings) -> Result<PathBu
     note_pathbuf_from_id(&note.task_id.to_string(), settings)
 }
 
-/// Read note from the disk
-pub fn load_note(id: &String, settings: &Settings) -> Result<Note> {
-    let note_pathbuf =
-        note_pathbuf_from_id(id, settings).with_context(|| "while building path of the file")?;
-    let note = Note::load_yaml_file_from(&note_pathbuf)
-        .with_context(|| "while loading note yaml file for editing")?;
+/// Resolve the full note id from a full UUID, from the short id of the task the note belongs to or
+/// from an unique prefix of the UUID. Short ids take precedence over prefixes the same way as in
+/// [crate::task::resolve_task_id].
+pub fn resolve_note_id(id: &str, settings: &Settings) -> Result<Uuid> {
+    if let Ok(uuid) = Uuid::parse_str(id) {
+        return Ok(uuid);
+    }
+
+    if let Some(uuid) = task_id_from_short_id(id, settings)? {
+        return Ok(uuid);
+    }
+
+    let note_ids = open_storage(settings)?.note_ids()?;
+    resolve_candidates(id, find_ids_by_prefix(id, &note_ids))
+}
+
+/// Read note from the storage, identified by id, short id or an unique id prefix
+pub fn load_note(id: &str, settings: &Settings) -> Result<Note> {
+    let uuid = resolve_note_id(id, settings).with_context(|| "while resolving note id")?;
+    let note = open_storage(settings)?
+        .load_note(&uuid)
+        .with_context(|| "while loading note from storage")?;
     Ok(note)
 }
 
-/// Save note to the disk
+/// Save note to the storage
 pub fn save_note(note: &mut Note, settings: &Settings) -> Result<()> {
-    let note_pathbuf = note_pathbuf_from_note(note, settings)?;
-    note.save_yaml_file_to(&note_pathbuf, &settings.data.rotate)
-        .with_context(|| "while saving note yaml file")?;
+    let storage = open_storage(settings)?;
+    // snapshot of the previous version is kept in the journal for undo
+    let before = if storage.note_exists(&note.task_id)? {
+        Some(storage.load_note(&note.task_id)?.to_yaml_string()?)
+    } else {
+        None
+    };
+    storage
+        .save_note(note)
+        .with_context(|| "while saving note to storage")?;
+    record_change(
+        JournalSubject::Note,
+        &note.task_id,
+        before,
+        Some(note.to_yaml_string()?),
+        settings,
+    )
+    .with_context(|| "while recording note change to journal")?;
     Ok(())
 }
 
+/// Delete note from the storage permanently, identified by id, short id or an unique id prefix
+pub fn delete_note(id: &str, settings: &Settings) -> Result<Note> {
+    let note = load_note(id, settings)?;
+    open_storage(settings)?
+        .delete_note(&note.task_id)
+        .with_context(|| "while deleting note from storage")?;
+    record_change(
+        JournalSubject::Note,
+        &note.task_id,
+        Some(note.to_yaml_string()?),
+        None,
+        settings,
+    )
+    .with_context(|| "while recording note deletion to journal")?;
+    Ok(note)
+}
+
 /// Abstraction for the link between note and the task the note belongs to (if any)
 pub struct FoundNote {
     /// Note that reflects the note
@@ -278,71 +329,38 @@ pub struct FoundNote {
     pub task: Option<Task>,
 }
 
-/// Get the amount of notes on disk
+/// Get the amount of notes in the storage
 pub fn amount_of_notes(settings: &Settings, include_backups: bool) -> Result<usize> {
-    let mut notes: usize = 0;
-    let task_pathbuf: PathBuf = note_pathbuf_from_id(&"*".to_string(), settings)?;
-    for note_filename in glob(task_pathbuf.to_str().unwrap())
-        .with_context(|| "while traversing task data directory files")?
-    {
-        // if the filename is u-u-i-d.3.yaml for example it is a backup file and should be disregarded
-        if note_filename
-            .as_ref()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .split('.')
-            .collect::<Vec<_>>()[1]
-            != "yaml"
-            && !include_backups
-        {
-            continue;
-        }
-        notes += 1;
-    }
-    Ok(notes)
+    open_storage(settings)?.count_notes(include_backups)
 }
 
-/// List notes stored on disk based on a search criteria
+/// List notes in the storage based on a search criteria. When a filter is given only notes of
+/// the tasks matching it are listed and orphaned notes are left out.
 pub fn list_notes(
     id: &Option<String>,
+    filter: &Option<Filter>,
     orphaned: &bool,
     completed: &bool,
     settings: &Settings,
 ) -> Result<Vec<FoundNote>> {
-    let note_pathbuf: PathBuf = if id.is_some() {
-        note_pathbuf_from_id(&format!("*{}*", id.as_ref().unwrap()), settings)?
+    let storage = open_storage(settings)?;
+    let notes = if let Some(id) = id {
+        let uuid = resolve_note_id(id, settings).with_context(|| "while resolving note id")?;
+        vec![storage
+            .load_note(&uuid)
+            .with_context(|| "while loading note from storage")?]
     } else {
-        note_pathbuf_from_id(&"*".to_string(), settings)?
+        storage
+            .load_notes()
+            .with_context(|| "while loading notes from storage")?
     };
 
     let mut found_notes: Vec<FoundNote> = vec![];
 
-    for note_filename in glob(note_pathbuf.to_str().unwrap())
-        .with_context(|| "while traversing note data directory files")?
-    {
-        // if the filename is u-u-i-d.3.yaml for example it is a backup file and should be disregarded
-        if note_filename
-            .as_ref()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .split('.')
-            .collect::<Vec<_>>()[1]
-            != "yaml"
-        {
-            continue;
-        }
-
-        let note = Note::load_yaml_file_from(&note_filename?)
-            .with_context(|| "while loading note from disk")?;
-
-        let task_pathbuf = task_pathbuf_from_id(&note.task_id.to_string(), settings)?;
+    for note in notes {
         let mut task: Option<Task> = None;
-        if task_pathbuf.is_file() {
-            task = Some(load_task(&note.task_id.to_string(), settings)?);
+        if storage.task_exists(&note.task_id)? {
+            task = Some(storage.load_task(&note.task_id)?);
         }
 
         if let Some(task) = task {
@@ -356,6 +374,10 @@ pub fn list_notes(
                 // .. task is not done so show it
                 show_note = true;
             }
+            if let Some(filter) = filter {
+                // .. unless the task does not match the filter
+                show_note = show_note && filter.matches(&task);
+            }
 
             if show_note {
                 found_notes.push(FoundNote {
@@ -363,7 +385,7 @@ pub fn list_notes(
                     task: Some(task),
                 });
             }
-        } else if *orphaned {
+        } else if *orphaned && filter.is_none() {
             // there is no task file anymore, and orphaned is true so we add it anyway to the return value
             found_notes.push(FoundNote { note, task: None });
         }
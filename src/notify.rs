@@ -2,6 +2,8 @@ use notify::RecursiveMode;
 use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
 use uuid::Uuid;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -33,12 +35,19 @@ pub type ErrorCallback = fn(String);
 /// Filesystem monitor
 pub struct FilesystemMonitor {
     watcher_thread: Option<thread::JoinHandle<()>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Default for FilesystemMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FilesystemMonitor {
     /// Create a new Filesystem monitor
     pub fn new() -> Self {
-        FilesystemMonitor { watcher_thread: None }
+        FilesystemMonitor { watcher_thread: None, stopped: Arc::new(AtomicBool::new(false)) }
     }
 
     /// Watch the database path for changes
@@ -65,6 +74,7 @@ impl FilesystemMonitor {
         let note_db_relpath = settings.note_db_pathbuf().unwrap();  // TODO: fix unwrap
         let note_db_path = note_db_relpath.absolutize().unwrap(); // TODO: fix unwrap
         let note_db_path_str = note_db_path.to_str().unwrap().to_string();  // TODO: fix unwrap
+        let stopped = Arc::clone(&self.stopped);
 
         // Spawn a new thread to monitor the filesystem.
         self.watcher_thread = Some(thread::spawn(move || {
@@ -81,8 +91,9 @@ impl FilesystemMonitor {
                 return;
             }
 
-            loop {
-                match rx.recv() {
+            // wake up every now and then to see if the monitor has been stopped
+            while !stopped.load(Ordering::Relaxed) {
+                match rx.recv_timeout(Duration::from_millis(500)) {
                     Ok(event) => {
                         match event {
                             Ok(events) => {
@@ -118,6 +129,7 @@ impl FilesystemMonitor {
                             Err(e) => on_error(format!("Event error: {:?}", e))
                         };
                     }
+                    Err(RecvTimeoutError::Timeout) => {},
                     Err(e) => {
                         // Handle the error.
                         on_error(format!("Watch error: {:?}", e));
@@ -126,6 +138,14 @@ impl FilesystemMonitor {
             }
         }));
     }
+
+    /// Stop watching and wait for the watcher thread to finish
+    pub fn stop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.watcher_thread.take() {
+            handle.join().unwrap_or(());
+        }
+    }
 }
 
 impl Drop for FilesystemMonitor {
@@ -0,0 +1,255 @@
+use color_eyre::eyre::{Context, Report, Result};
+use serde::{Deserialize, Serialize};
+use strum::{EnumString, IntoStaticStr};
+
+#[cfg(feature = "note")]
+use crate::note::NoteError;
+use crate::{
+    archive::ArchiveError,
+    bulk::BulkError,
+    focus::FocusError,
+    id::IdError,
+    import::{ical::IcalError, taskwarrior::TaskwarriorError},
+    invoice::InvoiceError,
+    journal::JournalError,
+    metadata::MetadataKeyValuePairError,
+    namespace::NamespaceError,
+    parser::{
+        due_date::DueDateError, filter_lexicon::FilterError, task_lexicon::LexiconError,
+        track_time::TrackTimeError,
+    },
+    recurrence::RecurrenceError,
+    report::ReportError,
+    settings::{Settings, SettingsError},
+    storage::StorageError,
+    task::TaskError,
+    track::TrackError,
+    trash::TrashError,
+};
+
+/// Format the command line tools print their results in
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    IntoStaticStr,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human readable tables and messages
+    #[default]
+    Table,
+    /// JSON document
+    Json,
+    /// YAML document
+    Yaml,
+}
+
+impl OutputFormat {
+    /// Returns true if the output is meant to be read by other programs
+    pub fn is_structured(&self) -> bool {
+        *self != OutputFormat::Table
+    }
+}
+
+/// Print the value as JSON or YAML if the output format asks for it. Returns false without
+/// printing anything when the output format is table, the caller prints its own human readable
+/// output then.
+pub fn print_structured<T: Serialize + ?Sized>(value: &T, settings: &Settings) -> Result<bool> {
+    match settings.output.format {
+        OutputFormat::Table => return Ok(false),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value)
+                .with_context(|| "while serializing output to json")?
+        ),
+        OutputFormat::Yaml => print!(
+            "{}",
+            serde_yaml::to_string(value).with_context(|| "while serializing output to yaml")?
+        ),
+    }
+    Ok(true)
+}
+
+/// Shorten the description to the configured length for tables. The description is cut at a
+/// character boundary and three dots are added, unless the dots would not make it any shorter.
+pub fn truncate_description(description: &str, settings: &Settings) -> String {
+    let length = settings.output.descriptionlength;
+    if description.chars().count() > length + 3 {
+        format!("{}...", description.chars().take(length).collect::<String>())
+    } else {
+        description.to_string()
+    }
+}
+
+/// Exit status of the command line tools, chosen by the error that stopped the command. Command
+/// line parsing errors exit with status 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Command completed successfully
+    Success = 0,
+    /// Any other error, e.g reading or writing files failed
+    Failure = 1,
+    /// Task, note, trash entry or namespace does not exist
+    NotFound = 3,
+    /// Identifier matched more than one task or note
+    Ambiguous = 4,
+    /// Task descriptor, filter expression, date, metadata or imported data could not be parsed
+    InvalidInput = 5,
+    /// State of the task or the data does not allow the command, e.g the task is already
+    /// completed or running
+    Conflict = 6,
+    /// Configuration does not allow the command, e.g the data directory does not exist
+    Configuration = 7,
+}
+
+impl ExitStatus {
+    /// Exit status for the error. The whole chain of errors is searched, so errors with added
+    /// context are recognized as well.
+    pub fn from_error(error: &Report) -> Self {
+        error
+            .chain()
+            .find_map(Self::from_cause)
+            .unwrap_or(ExitStatus::Failure)
+    }
+
+    fn from_cause(cause: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if let Some(error) = cause.downcast_ref::<IdError>() {
+            return Some(match error {
+                IdError::NotFound(_) => ExitStatus::NotFound,
+                IdError::Ambiguous { .. } => ExitStatus::Ambiguous,
+            });
+        }
+        if let Some(error) = cause.downcast_ref::<TaskError>() {
+            return Some(match error {
+                TaskError::TaskAlreadyCompleted
+                | TaskError::TaskAlreadyRunning
+                | TaskError::TaskNotRunning
+                | TaskError::TaskBlocked
+                | TaskError::AnotherTaskRunning(_) => ExitStatus::Conflict,
+                #[cfg(feature = "notify")]
+                TaskError::IncompatibleNotifyKind => ExitStatus::Failure,
+                _ => ExitStatus::InvalidInput,
+            });
+        }
+        #[cfg(feature = "note")]
+        if let Some(error) = cause.downcast_ref::<NoteError>() {
+            return Some(match error {
+                NoteError::ActionPointParseError => ExitStatus::InvalidInput,
+                #[cfg(feature = "notify")]
+                NoteError::IncompatibleNotifyKind => ExitStatus::Failure,
+            });
+        }
+        if let Some(error) = cause.downcast_ref::<StorageError>() {
+            return Some(match error {
+                StorageError::NotFound(_) => ExitStatus::NotFound,
+                StorageError::SameBackend => ExitStatus::Conflict,
+                StorageError::BackendNotAvailable(_) => ExitStatus::Configuration,
+            });
+        }
+        if let Some(error) = cause.downcast_ref::<NamespaceError>() {
+            return Some(match error {
+                NamespaceError::NotFound(_) => ExitStatus::NotFound,
+                NamespaceError::InvalidName(_) => ExitStatus::InvalidInput,
+                NamespaceError::AlreadyExists(_) | NamespaceError::SameNamespace(_) => {
+                    ExitStatus::Conflict
+                }
+            });
+        }
+        if let Some(error) = cause.downcast_ref::<TrackError>() {
+            return Some(match error {
+                TrackError::NotFound(_) => ExitStatus::NotFound,
+                TrackError::Overlap(_, _) | TrackError::RunningNotLatest => ExitStatus::Conflict,
+                _ => ExitStatus::InvalidInput,
+            });
+        }
+        if cause.downcast_ref::<SettingsError>().is_some() {
+            return Some(ExitStatus::Configuration);
+        }
+        if cause.downcast_ref::<JournalError>().is_some()
+            || cause.downcast_ref::<TrashError>().is_some()
+        {
+            return Some(ExitStatus::Conflict);
+        }
+        if cause.downcast_ref::<LexiconError>().is_some()
+            || cause.downcast_ref::<FilterError>().is_some()
+            || cause.downcast_ref::<DueDateError>().is_some()
+            || cause.downcast_ref::<TrackTimeError>().is_some()
+            || cause.downcast_ref::<MetadataKeyValuePairError>().is_some()
+            || cause.downcast_ref::<RecurrenceError>().is_some()
+            || cause.downcast_ref::<ReportError>().is_some()
+            || cause.downcast_ref::<TaskwarriorError>().is_some()
+            || cause.downcast_ref::<IcalError>().is_some()
+            || cause.downcast_ref::<BulkError>().is_some()
+            || cause.downcast_ref::<ArchiveError>().is_some()
+            || cause.downcast_ref::<InvoiceError>().is_some()
+            || cause.downcast_ref::<FocusError>().is_some()
+        {
+            return Some(ExitStatus::InvalidInput);
+        }
+        None
+    }
+
+    /// Numeric exit code of the status
+    pub fn code(&self) -> i32 {
+        *self as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    use super::*;
+
+    #[test]
+    fn exit_status_from_error_chain() {
+        let error: Report = Err::<(), _>(TaskError::TaskAlreadyRunning)
+            .wrap_err("while starting a task")
+            .unwrap_err();
+        assert_eq!(ExitStatus::from_error(&error), ExitStatus::Conflict);
+
+        let error: Report = IdError::Ambiguous {
+            id: "1b".to_string(),
+            candidates: "1b61, 1b62".to_string(),
+        }
+        .into();
+        assert_eq!(ExitStatus::from_error(&error).code(), 4);
+
+        let error: Report = Err::<(), _>(SettingsError::DataDirectoryDoesNotExist)
+            .wrap_err("while loading settings")
+            .unwrap_err();
+        assert_eq!(ExitStatus::from_error(&error), ExitStatus::Configuration);
+
+        let error: Report = ReportError::InvalidRange(
+            chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+        )
+        .into();
+        assert_eq!(ExitStatus::from_error(&error), ExitStatus::InvalidInput);
+
+        assert_eq!(
+            ExitStatus::from_error(&eyre!("something else")),
+            ExitStatus::Failure
+        );
+    }
+
+    #[test]
+    fn truncate_description_cuts_at_characters() {
+        let mut settings = Settings::default();
+        settings.output.descriptionlength = 5;
+
+        assert_eq!(truncate_description("ääääääääää", &settings), "äääää...");
+        assert_eq!(truncate_description("äääääääa", &settings), "äääääääa");
+        assert_eq!(truncate_description("short", &settings), "short");
+    }
+}
+
+// eof
@@ -0,0 +1,262 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday,
+};
+use color_eyre::eyre::{bail, Result};
+use nom::{
+    character::complete::{char, digit1, one_of},
+    combinator::{all_consuming, map_res},
+    sequence::{pair, preceded, separated_pair},
+    IResult,
+};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Errors that can happen while parsing a due date
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DueDateError {
+    /// The due date was not in any of the known formats
+    #[error("unknown due date `{0}`. try tomorrow, fri, +3d, eow, 2024-05-01, 17:00 or 2024-05-01T17:00:00")]
+    UnknownFormat(String),
+    /// The due date would be too far in the future to be represented
+    #[error("due date `{0}` is too far in the future")]
+    OutOfRange(String),
+    /// The due date does not exist in the local time zone, e.g it is skipped by daylight saving
+    #[error("due date {0} does not exist in the local time zone")]
+    InvalidLocalTime(NaiveDateTime),
+}
+
+fn number(input: &str) -> IResult<&str, u32> {
+    map_res(digit1, u32::from_str)(input)
+}
+
+fn relative_offset(input: &str) -> IResult<&str, (u32, char)> {
+    preceded(char('+'), pair(number, one_of("hdw")))(input)
+}
+
+fn time_of_day(input: &str) -> IResult<&str, (u32, u32)> {
+    separated_pair(number, char(':'), number)(input)
+}
+
+fn end_of_day(date: NaiveDate) -> NaiveDateTime {
+    date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap())
+}
+
+/// Parse a due date relative to the moment given as now. Returns the due date as local naive
+/// date time.
+///
+/// Supported formats are:
+/// - full date and time: `2024-05-01T17:00:00`
+/// - date only: `2024-05-01` (end of the day)
+/// - time only: `17:00` (today, or tomorrow if the time has already passed)
+/// - named days: `now`, `today`, `tomorrow` and weekdays `mon`..`sun` (end of the day, weekdays
+///   always point to the upcoming day and never to today)
+/// - end of period: `eod`, `eow`, `eom`, `eoy`
+/// - offsets from now: `+3h`, `+3d`, `+2w`
+pub fn parse_due_date(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let text = input.trim().to_lowercase();
+    let today = now.date();
+
+    if let Ok(datetime) = NaiveDateTime::from_str(input.trim()) {
+        return Ok(datetime);
+    }
+
+    if let Ok(date) = NaiveDate::from_str(&text) {
+        return Ok(end_of_day(date));
+    }
+
+    if let Ok((_, (hour, minute))) = all_consuming(time_of_day)(text.as_str()) {
+        if let Some(time) = NaiveTime::from_hms_opt(hour, minute, 0) {
+            let due = today.and_time(time);
+            if due > now {
+                return Ok(due);
+            }
+            return Ok(due + Duration::days(1));
+        }
+        bail!(DueDateError::UnknownFormat(input.to_string()));
+    }
+
+    if let Ok((_, (amount, unit))) = all_consuming(relative_offset)(text.as_str()) {
+        let offset = match unit {
+            'h' => Duration::try_hours(amount as i64),
+            'd' => Duration::try_days(amount as i64),
+            _ => Duration::try_weeks(amount as i64),
+        };
+        match offset.and_then(|offset| now.checked_add_signed(offset)) {
+            Some(due) => return Ok(due),
+            None => bail!(DueDateError::OutOfRange(input.to_string())),
+        }
+    }
+
+    let due = match text.as_str() {
+        "now" => now,
+        "today" | "eod" => end_of_day(today),
+        "tomorrow" => end_of_day(today + Duration::days(1)),
+        "eow" => end_of_day(
+            today + Duration::days(6 - today.weekday().num_days_from_monday() as i64),
+        ),
+        "eom" => {
+            let first_of_next_month = if today.month() == 12 {
+                NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(today.year(), today.month() + 1, 1)
+            }
+            .unwrap();
+            end_of_day(first_of_next_month - Duration::days(1))
+        }
+        "eoy" => end_of_day(NaiveDate::from_ymd_opt(today.year(), 12, 31).unwrap()),
+        _ => match Weekday::from_str(&text) {
+            Ok(weekday) => {
+                let mut date = today + Duration::days(1);
+                while date.weekday() != weekday {
+                    date += Duration::days(1);
+                }
+                end_of_day(date)
+            }
+            Err(_) => bail!(DueDateError::UnknownFormat(input.to_string())),
+        },
+    };
+
+    Ok(due)
+}
+
+/// Convert the parsed due date to the local time zone. An ambiguous time, repeated when daylight
+/// saving ends, resolves to the earlier one.
+pub fn local_due_date(datetime: NaiveDateTime) -> Result<DateTime<Local>> {
+    match Local.from_local_datetime(&datetime).earliest() {
+        Some(local) => Ok(local),
+        None => bail!(DueDateError::InvalidLocalTime(datetime)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // wednesday afternoon
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::from_str("2024-05-15T14:30:00").unwrap()
+    }
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::from_str(datetime).unwrap()
+    }
+
+    #[test]
+    fn full_datetime() {
+        assert_eq!(
+            parse_due_date("2022-08-16T16:56:00", now()).unwrap(),
+            at("2022-08-16T16:56:00")
+        );
+    }
+
+    #[test]
+    fn date_only() {
+        assert_eq!(
+            parse_due_date("2024-05-01", now()).unwrap(),
+            at("2024-05-01T23:59:59")
+        );
+    }
+
+    #[test]
+    fn time_only() {
+        assert_eq!(
+            parse_due_date("17:00", now()).unwrap(),
+            at("2024-05-15T17:00:00")
+        );
+        assert_eq!(
+            parse_due_date("09:00", now()).unwrap(),
+            at("2024-05-16T09:00:00")
+        );
+        assert!(parse_due_date("25:00", now()).is_err());
+    }
+
+    #[test]
+    fn named_days() {
+        assert_eq!(parse_due_date("now", now()).unwrap(), now());
+        assert_eq!(
+            parse_due_date("today", now()).unwrap(),
+            at("2024-05-15T23:59:59")
+        );
+        assert_eq!(
+            parse_due_date("Tomorrow", now()).unwrap(),
+            at("2024-05-16T23:59:59")
+        );
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(
+            parse_due_date("fri", now()).unwrap(),
+            at("2024-05-17T23:59:59")
+        );
+        // same weekday as today points to next week
+        assert_eq!(
+            parse_due_date("wednesday", now()).unwrap(),
+            at("2024-05-22T23:59:59")
+        );
+    }
+
+    #[test]
+    fn end_of_periods() {
+        assert_eq!(
+            parse_due_date("eow", now()).unwrap(),
+            at("2024-05-19T23:59:59")
+        );
+        assert_eq!(
+            parse_due_date("eom", now()).unwrap(),
+            at("2024-05-31T23:59:59")
+        );
+        assert_eq!(
+            parse_due_date("eoy", now()).unwrap(),
+            at("2024-12-31T23:59:59")
+        );
+    }
+
+    #[test]
+    fn relative_offsets() {
+        assert_eq!(
+            parse_due_date("+3d", now()).unwrap(),
+            at("2024-05-18T14:30:00")
+        );
+        assert_eq!(
+            parse_due_date("+2w", now()).unwrap(),
+            at("2024-05-29T14:30:00")
+        );
+        assert_eq!(
+            parse_due_date("+5h", now()).unwrap(),
+            at("2024-05-15T19:30:00")
+        );
+    }
+
+    #[test]
+    fn relative_offset_out_of_range() {
+        for input in ["+99999999w", "+4000000000d"] {
+            assert_eq!(
+                parse_due_date(input, now())
+                    .unwrap_err()
+                    .downcast::<DueDateError>()
+                    .unwrap(),
+                DueDateError::OutOfRange(input.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(
+            parse_due_date("someday", now())
+                .unwrap_err()
+                .downcast::<DueDateError>()
+                .unwrap(),
+            DueDateError::UnknownFormat("someday".to_string())
+        );
+    }
+
+    #[test]
+    fn local_due_date_keeps_wall_clock_time() {
+        let due = parse_due_date("17:00", now()).unwrap();
+        assert_eq!(local_due_date(due).unwrap().naive_local(), due);
+    }
+}
+
+// eof
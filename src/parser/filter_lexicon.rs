@@ -0,0 +1,636 @@
+use crate::{
+    parser::due_date::parse_due_date,
+    project::is_in_project,
+    storage::index::TaskSummary,
+    task::{Task, TaskPriority},
+};
+use chrono::{DateTime, Local, NaiveDateTime};
+use color_eyre::eyre::{bail, Context, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_while1},
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, map, map_opt, verify},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
+    IResult,
+};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, PartialEq, Eq)]
+enum FilterPrototype<'a> {
+    And(Box<FilterPrototype<'a>>, Box<FilterPrototype<'a>>),
+    Or(Box<FilterPrototype<'a>>, Box<FilterPrototype<'a>>),
+    Not(Box<FilterPrototype<'a>>),
+    Project(&'a str),
+    Tag(&'a str),
+    WithoutTag(&'a str),
+    Priority(&'a str, &'a str),
+    DueBefore(&'a str),
+    DueAfter(&'a str),
+    Metadata { key: &'a str, value: &'a str },
+    Running,
+    Done,
+    Text(&'a str),
+}
+
+/// Comparison operators available for ordered values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// Equal to
+    Equal,
+    /// Not equal to
+    NotEqual,
+    /// Less than
+    Less,
+    /// Less than or equal to
+    LessOrEqual,
+    /// Greater than
+    Greater,
+    /// Greater than or equal to
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn from_operator(operator: &str) -> Self {
+        match operator {
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            _ => Comparison::Equal,
+        }
+    }
+
+    fn compare<T: Ord>(&self, left: &T, right: &T) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// Filter expression that can be evaluated against a task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Both of the filters must match
+    And(Box<Filter>, Box<Filter>),
+    /// Either of the filters must match
+    Or(Box<Filter>, Box<Filter>),
+    /// The filter must not match
+    Not(Box<Filter>),
+    /// Task belongs to the project or one of its subprojects
+    Project(String),
+    /// Task has the tag
+    Tag(String),
+    /// Task priority compared to the given priority. Tasks without priority only match `!=`.
+    Priority(Comparison, TaskPriority),
+    /// Task is due before the moment. Tasks without due date do not match.
+    DueBefore(NaiveDateTime),
+    /// Task is due after the moment. Tasks without due date do not match.
+    DueAfter(NaiveDateTime),
+    /// Task has the metadata key with the value
+    Metadata {
+        /// Key of the metadata value
+        key: String,
+        /// Value of the metadata
+        value: String,
+    },
+    /// Task time tracking is running
+    Running,
+    /// Task is completed
+    Done,
+    /// Text found loosely from the description, project or tags
+    Text(String),
+}
+
+impl Filter {
+    fn from_prototype(prototype: &FilterPrototype, now: NaiveDateTime) -> Result<Self> {
+        Ok(match prototype {
+            FilterPrototype::And(left, right) => Filter::And(
+                Box::new(Filter::from_prototype(left, now)?),
+                Box::new(Filter::from_prototype(right, now)?),
+            ),
+            FilterPrototype::Or(left, right) => Filter::Or(
+                Box::new(Filter::from_prototype(left, now)?),
+                Box::new(Filter::from_prototype(right, now)?),
+            ),
+            FilterPrototype::Not(inner) => {
+                Filter::Not(Box::new(Filter::from_prototype(inner, now)?))
+            }
+            FilterPrototype::Project(text) => Filter::Project(String::from(*text)),
+            FilterPrototype::Tag(text) => Filter::Tag(String::from(*text)),
+            FilterPrototype::WithoutTag(text) => {
+                Filter::Not(Box::new(Filter::Tag(String::from(*text))))
+            }
+            FilterPrototype::Priority(operator, text) => Filter::Priority(
+                Comparison::from_operator(operator),
+                TaskPriority::from_str(text)
+                    .map_err(|_| FilterError::InvalidPriority(text.to_string()))?,
+            ),
+            FilterPrototype::DueBefore(text) => Filter::DueBefore(
+                parse_due_date(text, now).with_context(|| "invalid due date in filter")?,
+            ),
+            FilterPrototype::DueAfter(text) => Filter::DueAfter(
+                parse_due_date(text, now).with_context(|| "invalid due date in filter")?,
+            ),
+            FilterPrototype::Metadata { key, value } => Filter::Metadata {
+                key: key.to_ascii_lowercase(),
+                value: String::from(*value),
+            },
+            FilterPrototype::Running => Filter::Running,
+            FilterPrototype::Done => Filter::Done,
+            FilterPrototype::Text(text) => Filter::Text(String::from(*text)),
+        })
+    }
+
+    /// Returns true if the task matches the filter
+    pub fn matches(&self, task: &Task) -> bool {
+        // every term can be decided when the whole task is at hand
+        self.matches_fields(&FilterFields::from(task))
+            .unwrap_or(false)
+    }
+
+    /// Match the filter against the summary of a task. Returns None if the filter has terms that
+    /// can not be decided without the whole task, e.g metadata or running time tracking.
+    pub fn matches_summary(&self, summary: &TaskSummary) -> Option<bool> {
+        self.matches_fields(&FilterFields::from(summary))
+    }
+
+    fn matches_fields(&self, fields: &FilterFields) -> Option<bool> {
+        match self {
+            Filter::And(left, right) => {
+                match (left.matches_fields(fields), right.matches_fields(fields)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            Filter::Or(left, right) => {
+                match (left.matches_fields(fields), right.matches_fields(fields)) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+            Filter::Not(inner) => inner.matches_fields(fields).map(|matches| !matches),
+            Filter::Project(project) => Some(
+                fields
+                    .project
+                    .is_some_and(|task_project| is_in_project(task_project, project)),
+            ),
+            Filter::Tag(tag) => Some(fields.tags.is_some_and(|tags| {
+                tags.iter()
+                    .any(|task_tag| task_tag.eq_ignore_ascii_case(tag))
+            })),
+            Filter::Priority(comparison, priority) => Some(
+                match fields
+                    .priority
+                    .and_then(|task_priority| TaskPriority::from_str(task_priority).ok())
+                {
+                    Some(task_priority) => comparison.compare(&task_priority, priority),
+                    None => *comparison == Comparison::NotEqual,
+                },
+            ),
+            Filter::DueBefore(moment) => {
+                Some(due_time(fields.due).is_some_and(|due| due < *moment))
+            }
+            Filter::DueAfter(moment) => Some(due_time(fields.due).is_some_and(|due| due > *moment)),
+            Filter::Metadata { key, value } => fields
+                .task
+                .map(|task| task.metadata.get(key) == Some(value)),
+            Filter::Running => fields.task.map(|task| task.is_running()),
+            Filter::Done => Some(fields.done),
+            Filter::Text(text) => {
+                let text = text.to_lowercase();
+                Some(
+                    fields.description.to_lowercase().contains(&text)
+                        || fields
+                            .project
+                            .is_some_and(|project| project.to_lowercase().contains(&text))
+                        || fields.tags.is_some_and(|tags| {
+                            tags.iter().any(|tag| tag.to_lowercase().contains(&text))
+                        }),
+                )
+            }
+        }
+    }
+}
+
+/// Fields of a task the filter terms are matched against, taken either from the task itself or
+/// from its summary in the index
+struct FilterFields<'a> {
+    description: &'a str,
+    done: bool,
+    project: Option<&'a str>,
+    tags: Option<&'a [String]>,
+    priority: Option<&'a str>,
+    due: Option<&'a str>,
+    task: Option<&'a Task>,
+}
+
+impl<'a> From<&'a Task> for FilterFields<'a> {
+    fn from(task: &'a Task) -> Self {
+        Self {
+            description: &task.description,
+            done: task.done,
+            project: task.project.as_deref(),
+            tags: task.tags.as_deref(),
+            priority: task
+                .metadata
+                .get("tsk-rs-task-priority")
+                .map(String::as_str),
+            due: task
+                .metadata
+                .get("tsk-rs-task-due-time")
+                .map(String::as_str),
+            task: Some(task),
+        }
+    }
+}
+
+impl<'a> From<&'a TaskSummary> for FilterFields<'a> {
+    fn from(summary: &'a TaskSummary) -> Self {
+        Self {
+            description: &summary.description,
+            done: summary.done,
+            project: summary.project.as_deref(),
+            tags: summary.tags.as_deref(),
+            priority: summary.priority.as_deref(),
+            due: summary.due.as_deref(),
+            task: None,
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_filter(s, chrono::offset::Local::now().naive_local())
+    }
+}
+
+fn due_time(due: Option<&str>) -> Option<NaiveDateTime> {
+    due.and_then(|due| DateTime::<Local>::from_str(due).ok())
+        .map(|due| due.naive_local())
+}
+
+fn is_keyword(word: &str) -> bool {
+    ["and", "or", "not"].contains(&word.to_lowercase().as_str())
+}
+
+fn filter_char(c: char) -> bool {
+    !c.is_whitespace() && c != '(' && c != ')'
+}
+
+fn allowed_meta_character(c: char) -> bool {
+    filter_char(c) && c != '='
+}
+
+fn word(input: &str) -> IResult<&str, &str> {
+    take_while1(filter_char)(input)
+}
+
+fn meta_word(input: &str) -> IResult<&str, &str> {
+    take_while1(allowed_meta_character)(input)
+}
+
+fn project(input: &str) -> IResult<&str, &str> {
+    preceded(
+        alt((
+            tag_no_case("project:"),
+            tag_no_case("proj:"),
+            tag_no_case("prj:"),
+            tag("@"),
+        )),
+        word,
+    )(input)
+}
+
+fn with_tag(input: &str) -> IResult<&str, &str> {
+    preceded(alt((tag("+"), tag("#"), tag_no_case("tag:"))), word)(input)
+}
+
+fn without_tag(input: &str) -> IResult<&str, &str> {
+    preceded(char('-'), word)(input)
+}
+
+fn priority(input: &str) -> IResult<&str, (&str, &str)> {
+    preceded(
+        alt((tag_no_case("priority"), tag_no_case("prio"))),
+        pair(
+            alt((
+                tag(">="),
+                tag("<="),
+                tag("!="),
+                tag(">"),
+                tag("<"),
+                tag("="),
+                tag(":"),
+            )),
+            word,
+        ),
+    )(input)
+}
+
+fn due_before(input: &str) -> IResult<&str, &str> {
+    preceded(tag_no_case("due.before:"), word)(input)
+}
+
+fn due_after(input: &str) -> IResult<&str, &str> {
+    preceded(tag_no_case("due.after:"), word)(input)
+}
+
+fn metadata(input: &str) -> IResult<&str, (&str, &str)> {
+    verify(
+        separated_pair(meta_word, char('='), meta_word),
+        |(key, _): &(&str, &str)| key.to_ascii_lowercase().starts_with("x-"),
+    )(input)
+}
+
+fn state(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    map_opt(word, |state| match state.to_lowercase().as_str() {
+        "running" => Some(FilterPrototype::Running),
+        "done" => Some(FilterPrototype::Done),
+        _ => None,
+    })(input)
+}
+
+fn text(input: &str) -> IResult<&str, &str> {
+    verify(word, |text: &str| !is_keyword(text))(input)
+}
+
+fn term(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    alt((
+        map(project, FilterPrototype::Project),
+        map(with_tag, FilterPrototype::Tag),
+        map(without_tag, FilterPrototype::WithoutTag),
+        map(priority, |(operator, level)| {
+            FilterPrototype::Priority(operator, level)
+        }),
+        map(due_before, FilterPrototype::DueBefore),
+        map(due_after, FilterPrototype::DueAfter),
+        map(metadata, |(key, value)| FilterPrototype::Metadata {
+            key,
+            value,
+        }),
+        state,
+        map(text, FilterPrototype::Text),
+    ))(input)
+}
+
+fn group(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    delimited(
+        pair(char('('), multispace0),
+        or_expression,
+        pair(multispace0, char(')')),
+    )(input)
+}
+
+fn not_expression(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    alt((
+        map(
+            preceded(pair(tag_no_case("not"), multispace1), not_expression),
+            |inner| FilterPrototype::Not(Box::new(inner)),
+        ),
+        map(preceded(char('!'), not_expression), |inner| {
+            FilterPrototype::Not(Box::new(inner))
+        }),
+        group,
+        term,
+    ))(input)
+}
+
+fn and_expression(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    let (mut input, mut left) = not_expression(input)?;
+    loop {
+        // terms next to each other without an explicit operator are joined with "and"
+        let next = alt((
+            preceded(
+                tuple((multispace1, tag_no_case("and"), multispace1)),
+                not_expression,
+            ),
+            preceded(multispace1, not_expression),
+        ))(input);
+        match next {
+            Ok((remaining, right)) => {
+                left = FilterPrototype::And(Box::new(left), Box::new(right));
+                input = remaining;
+            }
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((input, left))
+}
+
+fn or_expression(input: &str) -> IResult<&str, FilterPrototype<'_>> {
+    let (mut input, mut left) = and_expression(input)?;
+    loop {
+        let next = preceded(
+            tuple((multispace1, tag_no_case("or"), multispace1)),
+            and_expression,
+        )(input);
+        match next {
+            Ok((remaining, right)) => {
+                left = FilterPrototype::Or(Box::new(left), Box::new(right));
+                input = remaining;
+            }
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((input, left))
+}
+
+/// Errors that can happen during parsing of the filter expression
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// There was a problem while parsing the filter expression
+    #[error("unable to parse filter expression: {0}")]
+    ParserError(String),
+    /// Priority in the filter expression is not one of the known priorities
+    #[error("invalid priority `{0}` in filter. try low, medium, high or critical")]
+    InvalidPriority(String),
+}
+
+/// Parses filter expression and resolves relative dates in it against the moment given as now
+///
+/// Example: `project:work and (+urgent or prio>=high) and not +hold due.before:eow`
+pub fn parse_filter(input: &str, now: NaiveDateTime) -> Result<Filter> {
+    let parsed = all_consuming(delimited(multispace0, or_expression, multispace0))(input)
+        .map(|(_, prototype)| prototype);
+
+    match parsed {
+        Ok(prototype) => {
+            Filter::from_prototype(&prototype, now).with_context(|| "malformed filter expression")
+        }
+        Err(error) => bail!(FilterError::ParserError(error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::from_str("2024-05-15T14:30:00").unwrap()
+    }
+
+    fn task(descriptor: &str) -> Task {
+        Task::from_task_descriptor(&descriptor.to_string()).unwrap()
+    }
+
+    #[test]
+    fn parse_single_terms() {
+        assert_eq!(
+            parse_filter("project:foo", now()).unwrap(),
+            Filter::Project("foo".to_string())
+        );
+        assert_eq!(
+            parse_filter("+bar", now()).unwrap(),
+            Filter::Tag("bar".to_string())
+        );
+        assert_eq!(
+            parse_filter("-bar", now()).unwrap(),
+            Filter::Not(Box::new(Filter::Tag("bar".to_string())))
+        );
+        assert_eq!(
+            parse_filter("prio>=high", now()).unwrap(),
+            Filter::Priority(Comparison::GreaterOrEqual, TaskPriority::High)
+        );
+        assert_eq!(
+            parse_filter("x-client=acme", now()).unwrap(),
+            Filter::Metadata {
+                key: "x-client".to_string(),
+                value: "acme".to_string()
+            }
+        );
+        assert_eq!(parse_filter("running", now()).unwrap(), Filter::Running);
+        assert_eq!(
+            parse_filter("due.before:eow", now()).unwrap(),
+            Filter::DueBefore(NaiveDateTime::from_str("2024-05-19T23:59:59").unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_operator_precedence() {
+        // and binds tighter than or
+        assert_eq!(
+            parse_filter("+a or +b and +c", now()).unwrap(),
+            Filter::Or(
+                Box::new(Filter::Tag("a".to_string())),
+                Box::new(Filter::And(
+                    Box::new(Filter::Tag("b".to_string())),
+                    Box::new(Filter::Tag("c".to_string()))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_parentheses_and_not() {
+        assert_eq!(
+            parse_filter("not (+a or +b) running", now()).unwrap(),
+            Filter::And(
+                Box::new(Filter::Not(Box::new(Filter::Or(
+                    Box::new(Filter::Tag("a".to_string())),
+                    Box::new(Filter::Tag("b".to_string()))
+                )))),
+                Box::new(Filter::Running)
+            )
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_filter("(+a or +b", now()).is_err());
+        assert!(parse_filter("prio>=urgent", now()).is_err());
+        assert!(parse_filter("prio:ñ", now()).is_err());
+        assert!(parse_filter("due.before:someday", now()).is_err());
+        assert!(parse_filter("+a or", now()).is_err());
+    }
+
+    #[test]
+    fn parse_priority_case_insensitive() {
+        assert_eq!(
+            parse_filter("prio:CRITICAL", now()).unwrap(),
+            Filter::Priority(Comparison::Equal, TaskPriority::Critical)
+        );
+    }
+
+    #[test]
+    fn parse_non_ascii_priority() {
+        let error = parse_filter("prio:ñoño", now()).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<FilterError>(),
+            Some(&FilterError::InvalidPriority("ñoño".to_string()))
+        );
+    }
+
+    #[test]
+    fn match_summaries() {
+        let work = task("write report @work #urgent prio:high %x-client=acme");
+        let summary = TaskSummary::from(&work);
+
+        for (filter, matches) in [
+            ("project:work +urgent", Some(true)),
+            ("prio<high", Some(false)),
+            ("report or running", Some(true)),
+            ("-urgent and x-client=acme", Some(false)),
+            // metadata and running time tracking are not in the summary
+            ("x-client=acme", None),
+            ("not running", None),
+            ("project:work and running", None),
+        ] {
+            assert_eq!(
+                parse_filter(filter, now()).unwrap().matches_summary(&summary),
+                matches,
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn match_tasks() {
+        let work =
+            task("write report @work #urgent prio:high %x-client=acme due:2024-05-16T12:00:00");
+        let home = task("mow the lawn @home #hold prio:low");
+
+        let filter = parse_filter("project:work and (+urgent or prio>=high) -hold", now()).unwrap();
+        assert!(filter.matches(&work));
+        assert!(!filter.matches(&home));
+
+        let filter = parse_filter("x-client=acme due.before:eow", now()).unwrap();
+        assert!(filter.matches(&work));
+        assert!(!filter.matches(&home));
+
+        let filter = parse_filter("prio<medium or lawn", now()).unwrap();
+        assert!(!filter.matches(&work));
+        assert!(filter.matches(&home));
+
+        let filter = parse_filter("running or done", now()).unwrap();
+        assert!(!filter.matches(&work));
+
+        let backend = task("deploy @work.clientA.backend");
+        for (filter, matches) in [
+            ("project:work", true),
+            ("project:work.clienta", true),
+            ("project:work.clientB", false),
+        ] {
+            assert_eq!(
+                parse_filter(filter, now()).unwrap().matches(&backend),
+                matches
+            );
+        }
+    }
+}
+
+// eof
@@ -1,2 +1,11 @@
 /// Task description syntax and parser implementation
 pub mod task_lexicon;
+
+/// Due date syntax and parser implementation
+pub mod due_date;
+
+/// Filter expression syntax and parser implementation
+pub mod filter_lexicon;
+
+/// Tracked time and duration syntax and parser implementation
+pub mod track_time;
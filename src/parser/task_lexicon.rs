@@ -1,5 +1,9 @@
-use crate::task::TaskPriority;
-use chrono::NaiveDateTime;
+use crate::{
+    parser::{due_date::parse_due_date, track_time::parse_estimate},
+    recurrence::Recurrence,
+    task::TaskPriority,
+};
+use chrono::{Duration, NaiveDateTime};
 use color_eyre::eyre::{bail, Context, Result};
 use nom::{
     branch::alt,
@@ -22,6 +26,9 @@ enum ExpressionPrototype<'a> {
     Metadata { key: &'a str, value: &'a str },
     Priority(&'a str),
     Duedate(&'a str),
+    Dependency(&'a str),
+    Recurrence(&'a str),
+    Estimate(&'a str),
 }
 
 /// Expression components
@@ -44,6 +51,13 @@ pub enum Expression {
     Priority(TaskPriority),
     /// Duedate component from task definition string
     Duedate(NaiveDateTime),
+    /// Dependency component from task definition string. Contains the id of the task (full,
+    /// short or a prefix) that has to be completed first.
+    Dependency(String),
+    /// Recurrence rule component from task definition string
+    Recurrence(Recurrence),
+    /// Estimated effort component from task definition string
+    Estimate(Duration),
 }
 
 impl Expression {
@@ -56,18 +70,22 @@ impl Expression {
                 key: String::from(*key),
                 value: String::from(*value),
             },
-            ExpressionPrototype::Priority(text) => {
-                let mut prio_string = text.to_string().to_lowercase();
-                prio_string = prio_string[0..1].to_uppercase() + &prio_string[1..];
-                Expression::Priority(
-                    TaskPriority::from_str(&prio_string)
-                        .with_context(|| "invalid priority specified in descriptor")?,
-                )
-            }
+            ExpressionPrototype::Priority(text) => Expression::Priority(
+                TaskPriority::from_str(text)
+                    .with_context(|| "invalid priority specified in descriptor")?,
+            ),
             ExpressionPrototype::Duedate(text) => Expression::Duedate(
-                NaiveDateTime::from_str(text)
+                parse_due_date(text, chrono::offset::Local::now().naive_local())
                     .with_context(|| "invalid date time format for duedate in descriptor")?,
             ),
+            ExpressionPrototype::Dependency(text) => Expression::Dependency(String::from(*text)),
+            ExpressionPrototype::Recurrence(text) => Expression::Recurrence(
+                Recurrence::from_str(text)
+                    .with_context(|| "invalid recurrence rule in descriptor")?,
+            ),
+            ExpressionPrototype::Estimate(text) => Expression::Estimate(
+                parse_estimate(text).with_context(|| "invalid estimate in descriptor")?,
+            ),
         })
     }
 }
@@ -130,7 +148,25 @@ fn due_date(input: &str) -> IResult<&str, &str> {
     )(input)
 }
 
-fn directive(input: &str) -> IResult<&str, ExpressionPrototype> {
+fn dependency(input: &str) -> IResult<&str, &str> {
+    preceded(
+        alt((tag("dep:"), tag("DEP:"), tag("depends:"), tag("DEPENDS:"))),
+        word,
+    )(input)
+}
+
+fn recurrence(input: &str) -> IResult<&str, &str> {
+    preceded(alt((tag("recur:"), tag("RECUR:"))), word)(input)
+}
+
+fn estimate(input: &str) -> IResult<&str, &str> {
+    preceded(
+        alt((tag("est:"), tag("EST:"), tag("estimate:"), tag("ESTIMATE:"))),
+        word,
+    )(input)
+}
+
+fn directive(input: &str) -> IResult<&str, ExpressionPrototype<'_>> {
     alt((
         map(hashtag, ExpressionPrototype::Tag),
         map(hashtag2, ExpressionPrototype::Tag),
@@ -149,7 +185,17 @@ fn directive(input: &str) -> IResult<&str, ExpressionPrototype> {
     ))(input)
 }
 
-fn parse_inline(input: &str) -> IResult<&str, Vec<ExpressionPrototype>> {
+/// Directives that are keywords ending in words of their own, like `test:` contains `est:`. These
+/// are only matched at the start of a word.
+fn keyword_directive(input: &str) -> IResult<&str, ExpressionPrototype<'_>> {
+    alt((
+        map(dependency, ExpressionPrototype::Dependency),
+        map(recurrence, ExpressionPrototype::Recurrence),
+        map(estimate, ExpressionPrototype::Estimate),
+    ))(input)
+}
+
+fn parse_inline(input: &str) -> IResult<&str, Vec<ExpressionPrototype<'_>>> {
     let mut output = Vec::with_capacity(4);
     let mut current_input = input;
 
@@ -157,7 +203,17 @@ fn parse_inline(input: &str) -> IResult<&str, Vec<ExpressionPrototype>> {
         let mut found_directive = false;
         for (current_index, _) in current_input.char_indices() {
             // println!("{} {}", current_index, current_input);
-            match directive(&current_input[current_index..]) {
+            let at_word_start = current_input[..current_index]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !nonws_char(c));
+            let parsed = match directive(&current_input[current_index..]) {
+                Err(nom::Err::Error(_)) if at_word_start => {
+                    keyword_directive(&current_input[current_index..])
+                }
+                parsed => parsed,
+            };
+            match parsed {
                 Ok((remaining, parsed)) => {
                     // println!("Matched {:?} remaining {}", parsed, remaining);
                     let leading_text = &current_input[0..current_index].trim();
@@ -221,6 +277,7 @@ pub fn parse_task(input: String) -> Result<Vec<Expression>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{parser::track_time::TrackTimeError, recurrence::RecurrenceError};
 
     #[test]
     fn nonws_char_allowed() {
@@ -305,6 +362,40 @@ mod tests {
         assert_eq!(project2("PRJ:fubar").unwrap(), ("", "fubar"));
     }
 
+    #[test]
+    fn dependency_valid() {
+        assert_eq!(dependency("dep:bd6f75aa").unwrap(), ("", "bd6f75aa"));
+        assert_eq!(dependency("DEPENDS:12").unwrap(), ("", "12"));
+    }
+
+    #[test]
+    fn recurrence_valid() {
+        assert_eq!(recurrence("recur:mon,fri").unwrap(), ("", "mon,fri"));
+    }
+
+    #[test]
+    fn estimate_valid() {
+        assert_eq!(estimate("est:1d4h").unwrap(), ("", "1d4h"));
+        assert_eq!(estimate("ESTIMATE:90m").unwrap(), ("", "90m"));
+    }
+
+    #[test]
+    fn estimate_without_value() {
+        assert!(estimate("est:").is_err());
+    }
+
+    #[test]
+    fn priority_non_ascii_invalid() {
+        assert!(parse_task("fix it prio:ñ".to_string()).is_err());
+        assert_eq!(
+            parse_task("fix it prio:HIGH".to_string()).unwrap(),
+            vec![
+                Expression::Description("fix it".to_string()),
+                Expression::Priority(TaskPriority::High)
+            ]
+        );
+    }
+
     #[test]
     fn metadata_pair_broken() {
         assert!(metadata_pair("x-meta = value").is_err());
@@ -397,6 +488,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_relative_duedate() {
+        let expressions = parse_task("call back due:tomorrow".to_string()).unwrap();
+
+        let tomorrow = chrono::offset::Local::now().date_naive() + chrono::Duration::days(1);
+        assert_eq!(
+            expressions[1],
+            Expression::Duedate(tomorrow.and_hms_opt(23, 59, 59).unwrap())
+        );
+    }
+
+    #[test]
+    fn keywords_inside_words_are_text() {
+        let input = "fix test:integration failure of undep:1 and precur:daily";
+
+        let (_, meta) = parse_inline(input).unwrap();
+
+        assert_eq!(meta, vec![ExpressionPrototype::Description(input)]);
+        assert!(parse_task(input.to_string()).is_ok());
+    }
+
+    #[test]
+    fn keywords_at_word_start() {
+        let (_, meta) = parse_inline("est:2h write tests dep:12").unwrap();
+
+        assert_eq!(
+            meta,
+            vec![
+                ExpressionPrototype::Estimate("2h"),
+                ExpressionPrototype::Description("write tests"),
+                ExpressionPrototype::Dependency("12"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_estimate_is_an_error() {
+        let error = parse_task("est:soon plan the trip".to_string()).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<TrackTimeError>(),
+            Some(&TrackTimeError::UnknownEstimate("soon".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_recurrence_is_an_error() {
+        let error = parse_task("recur:sometimes water the plants".to_string()).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<RecurrenceError>(),
+            Some(&RecurrenceError::InvalidRule("sometimes".to_string()))
+        );
+    }
+
     #[test]
     fn parse_full_testcase_no_expressions() {
         let input = "some task description here without expressions";
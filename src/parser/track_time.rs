@@ -0,0 +1,285 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime};
+use color_eyre::eyre::{bail, Result};
+use nom::{
+    character::complete::{char, digit1, one_of},
+    combinator::{all_consuming, map_res},
+    multi::many1,
+    sequence::{pair, preceded, separated_pair},
+    IResult,
+};
+use std::{ops::RangeInclusive, str::FromStr};
+use thiserror::Error;
+
+/// Errors that can happen while parsing tracked time
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TrackTimeError {
+    /// The time was not in any of the known formats
+    #[error("unknown time `{0}`. try 14:00, yesterday 14:00, -45m, 2024-05-01 14:00 or 2024-05-01T14:00:00")]
+    UnknownTime(String),
+    /// The duration was not in any of the known formats
+    #[error("unknown duration `{0}`. try 45m, 1h30m, 2h or 1:30")]
+    UnknownDuration(String),
+    /// The estimate was not in any of the known formats or it was zero
+    #[error("unknown estimate `{0}`. try 90m, 2h, 1d or 1d4h")]
+    UnknownEstimate(String),
+    /// The time or duration is too far from now to be tracked
+    #[error("`{0}` is out of range, tracked time must fall between years 0 and 9999")]
+    OutOfRange(String),
+}
+
+/// Hours of work in a day of estimated effort
+pub const WORKDAY_HOURS: i64 = 8;
+
+/// Parse an unsigned number of the amount of time units
+pub(crate) fn number(input: &str) -> IResult<&str, u32> {
+    map_res(digit1, u32::from_str)(input)
+}
+
+fn time_of_day(input: &str) -> IResult<&str, (u32, u32)> {
+    separated_pair(number, char(':'), number)(input)
+}
+
+fn duration_parts(input: &str) -> IResult<&str, Vec<(u32, char)>> {
+    many1(pair(number, one_of("hms")))(input)
+}
+
+fn estimate_parts(input: &str) -> IResult<&str, Vec<(u32, char)>> {
+    many1(pair(number, one_of("dhms")))(input)
+}
+
+/// Years tracked time can fall in, the ones the four digit years of stored times can hold
+pub const TRACKED_YEARS: RangeInclusive<i32> = 0..=9999;
+
+/// Total duration of the parts, none if it does not fit in a duration
+fn parts_to_duration(parts: &[(u32, char)]) -> Option<Duration> {
+    parts
+        .iter()
+        .map(|(amount, unit)| match unit {
+            'd' => Duration::try_hours(*amount as i64 * WORKDAY_HOURS),
+            'h' => Duration::try_hours(*amount as i64),
+            'm' => Duration::try_minutes(*amount as i64),
+            _ => Duration::try_seconds(*amount as i64),
+        })
+        .try_fold(Duration::zero(), |total, duration| {
+            total.checked_add(&duration?)
+        })
+}
+
+/// Parse a duration of tracked time
+///
+/// Supported formats are:
+/// - hours, minutes and seconds in any combination: `45m`, `1h30m`, `2h`, `90s`
+/// - hours and minutes: `1:30`
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let text = input.trim().to_lowercase();
+
+    if let Ok((_, (hours, minutes))) = all_consuming(time_of_day)(text.as_str()) {
+        if minutes < 60 {
+            return Ok(Duration::hours(hours as i64) + Duration::minutes(minutes as i64));
+        }
+    }
+
+    if let Ok((_, parts)) = all_consuming(duration_parts)(text.as_str()) {
+        return match parts_to_duration(&parts) {
+            Some(duration) => Ok(duration),
+            None => bail!(TrackTimeError::OutOfRange(input.to_string())),
+        };
+    }
+
+    bail!(TrackTimeError::UnknownDuration(input.to_string()));
+}
+
+/// Parse an estimate of the effort a task takes. Days are days of work, one day being
+/// [WORKDAY_HOURS] hours.
+///
+/// Supported formats are the ones of [parse_duration] along with days: `90m`, `2h`, `1d`, `1d4h`
+pub fn parse_estimate(input: &str) -> Result<Duration> {
+    let text = input.trim().to_lowercase();
+
+    let estimate = match all_consuming(estimate_parts)(text.as_str()) {
+        Ok((_, parts)) => parts_to_duration(&parts)
+            .ok_or_else(|| TrackTimeError::OutOfRange(input.to_string()))?,
+        Err(_) => {
+            parse_duration(&text).map_err(|_| TrackTimeError::UnknownEstimate(input.to_string()))?
+        }
+    };
+    if estimate <= Duration::zero() {
+        bail!(TrackTimeError::UnknownEstimate(input.to_string()));
+    }
+    Ok(estimate)
+}
+
+/// Parse a moment of tracked time relative to the moment given as now. Unlike due dates these
+/// point to the past, so a time of day that is still ahead means yesterday.
+///
+/// Supported formats are:
+/// - full date and time: `2024-05-01T14:00:00`, `2024-05-01T14:00` or `2024-05-01 14:00`
+/// - time only: `14:00` (today, or yesterday if the time is still ahead)
+/// - time of a named day: `today 14:00`, `yesterday 14:00`
+/// - offsets back from now: `-45m`, `-1h30m`
+/// - `now`
+///
+/// Times outside the years 0 to 9999 are rejected.
+pub fn parse_track_time(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let time = parse_moment(input, now)?;
+    if !TRACKED_YEARS.contains(&time.year()) {
+        bail!(TrackTimeError::OutOfRange(input.to_string()));
+    }
+    Ok(time)
+}
+
+fn parse_moment(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let text = input.trim().to_lowercase();
+    let unknown = || TrackTimeError::UnknownTime(input.to_string());
+
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input.trim(), format) {
+            return Ok(datetime);
+        }
+    }
+
+    if text == "now" {
+        return Ok(now);
+    }
+
+    if let Ok((_, parts)) = all_consuming(preceded(char('-'), duration_parts))(text.as_str()) {
+        return match parts_to_duration(&parts).and_then(|offset| now.checked_sub_signed(offset)) {
+            Some(time) => Ok(time),
+            None => bail!(TrackTimeError::OutOfRange(input.to_string())),
+        };
+    }
+
+    let (day, time) = match text.split_once(char::is_whitespace) {
+        Some((day, time)) => (Some(day), time.trim()),
+        None => (None, text.as_str()),
+    };
+    let Ok((_, (hour, minute))) = all_consuming(time_of_day)(time) else {
+        bail!(unknown());
+    };
+    let Some(time) = NaiveTime::from_hms_opt(hour, minute, 0) else {
+        bail!(unknown());
+    };
+    let today = now.date().and_time(time);
+    match day {
+        None if today > now => Ok(today - Duration::days(1)),
+        None | Some("today") => Ok(today),
+        Some("yesterday") => Ok(today - Duration::days(1)),
+        Some(_) => bail!(unknown()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::from_str(datetime).unwrap()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("45m").unwrap(), Duration::minutes(45));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("1:30").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+        for duration in ["", "45", "1:75", "1d", "m45"] {
+            assert_eq!(
+                parse_duration(duration)
+                    .unwrap_err()
+                    .downcast::<TrackTimeError>()
+                    .unwrap(),
+                TrackTimeError::UnknownDuration(duration.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn estimates() {
+        assert_eq!(parse_estimate("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_estimate("2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_estimate("1d").unwrap(), Duration::hours(8));
+        assert_eq!(parse_estimate("1d4h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_estimate("1:30").unwrap(), Duration::minutes(90));
+        for estimate in ["", "0m", "2", "1w", "d1"] {
+            assert_eq!(
+                parse_estimate(estimate)
+                    .unwrap_err()
+                    .downcast::<TrackTimeError>()
+                    .unwrap(),
+                TrackTimeError::UnknownEstimate(estimate.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn times_point_to_the_past() {
+        let now = at("2024-05-15T14:30:00");
+        for (input, expected) in [
+            ("2024-05-01T09:15:00", "2024-05-01T09:15:00"),
+            ("2024-05-01 09:15", "2024-05-01T09:15:00"),
+            ("now", "2024-05-15T14:30:00"),
+            ("-45m", "2024-05-15T13:45:00"),
+            ("-1h30m", "2024-05-15T13:00:00"),
+            ("9:00", "2024-05-15T09:00:00"),
+            ("16:00", "2024-05-14T16:00:00"),
+            ("today 16:00", "2024-05-15T16:00:00"),
+            ("yesterday 9:00", "2024-05-14T09:00:00"),
+        ] {
+            assert_eq!(
+                parse_track_time(input, now).unwrap(),
+                at(expected),
+                "{}",
+                input
+            );
+        }
+        for input in ["", "25:00", "tomorrow 9:00", "+1h"] {
+            assert_eq!(
+                parse_track_time(input, now)
+                    .unwrap_err()
+                    .downcast::<TrackTimeError>()
+                    .unwrap(),
+                TrackTimeError::UnknownTime(input.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn times_out_of_range() {
+        let now = at("2024-05-15T14:30:00");
+        for input in ["-99999999h", "-4294967295h4294967295h", "-0001-01-01T00:00:00"] {
+            assert_eq!(
+                parse_track_time(input, now)
+                    .unwrap_err()
+                    .downcast::<TrackTimeError>()
+                    .unwrap(),
+                TrackTimeError::OutOfRange(input.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn durations_out_of_range() {
+        let input = "4294967295h".repeat(1000);
+        assert_eq!(
+            parse_duration(&input)
+                .unwrap_err()
+                .downcast::<TrackTimeError>()
+                .unwrap(),
+            TrackTimeError::OutOfRange(input.clone())
+        );
+        assert_eq!(
+            parse_estimate(&input)
+                .unwrap_err()
+                .downcast::<TrackTimeError>()
+                .unwrap(),
+            TrackTimeError::OutOfRange(input)
+        );
+    }
+}
+
+// eof
@@ -2,22 +2,69 @@ use std::collections::HashMap;
 
 use color_eyre::eyre::{Context, Result};
 
-use crate::{settings::Settings, task::list_tasks};
+use crate::{archive::archived_summaries, settings::Settings, storage::open_storage};
 
-/// scan all active and done tasks to find projects in use
+/// Separates the levels of a hierarchical project name, e.g `work.clienta.backend`
+pub const PROJECT_SEPARATOR: char = '.';
+
+/// Returns the project along with all of its parent projects, top level project first
+pub fn project_hierarchy(project: &str) -> Vec<String> {
+    project
+        .match_indices(PROJECT_SEPARATOR)
+        .map(|(index, _)| project[..index].to_string())
+        .chain(std::iter::once(project.to_string()))
+        .collect()
+}
+
+/// Returns true if the project is the other project or one of its subprojects. Project names
+/// are compared case insensitively.
+pub fn is_in_project(project: &str, other: &str) -> bool {
+    project_hierarchy(project)
+        .iter()
+        .any(|level| level.eq_ignore_ascii_case(other))
+}
+
+/// scan all active, done and archived tasks to find projects in use. Tasks of a subproject are
+/// counted to all of its parent projects as well.
 pub fn scan_projects(settings: &Settings) -> Result<HashMap<String, usize>> {
-    let tasks = list_tasks(&None, &true, settings)
+    let mut tasks = open_storage(settings)?
+        .task_summaries()
         .with_context(|| "while scanning through all tasks")?;
+    tasks.extend(
+        archived_summaries(settings).with_context(|| "while scanning through archived tasks")?,
+    );
 
     let mut collected_projects: HashMap<String, usize> = HashMap::new();
 
     for task in tasks {
         if let Some(project) = task.project {
-                *collected_projects.entry(project).or_insert(0) += 1;
+            for level in project_hierarchy(&project) {
+                *collected_projects.entry(level).or_insert(0) += 1;
+            }
         }
     }
 
     Ok(collected_projects)
 }
 
-// eof
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchical_projects() {
+        assert_eq!(
+            project_hierarchy("work.clientA.backend"),
+            vec!["work", "work.clientA", "work.clientA.backend"]
+        );
+        assert_eq!(project_hierarchy("home"), vec!["home"]);
+
+        assert!(is_in_project("work.clientA.backend", "work"));
+        assert!(is_in_project("work.clientA.backend", "work.clienta"));
+        assert!(is_in_project("work", "work"));
+        assert!(!is_in_project("workshop", "work"));
+        assert!(!is_in_project("work", "work.clientA"));
+    }
+}
+
+// eof
@@ -0,0 +1,330 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, Local, Months, Weekday};
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    settings::Settings,
+    task::{list_tasks, load_task, save_task, Task},
+};
+
+/// Errors that can occur while handling recurrence rules
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RecurrenceError {
+    /// Recurrence rule could not be understood
+    #[error("invalid recurrence rule `{0}`. try daily, weekly, monthly, 3d or mon,wed,fri")]
+    InvalidRule(String),
+    /// Next instance would be due beyond the supported calendar
+    #[error("next instance of the recurrence rule `{0}` is beyond the supported calendar")]
+    Overflow(String),
+}
+
+/// Longest interval accepted for a `Nd` rule, one hundred years
+pub const MAX_RECURRENCE_DAYS: u32 = 36_500;
+
+/// Rule that defines when the next instance of a recurring task is due
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recurrence {
+    /// Every day
+    Daily,
+    /// Every seven days
+    Weekly,
+    /// Same day of the next month, or the last day of the month if the day does not exist
+    Monthly,
+    /// Every N days
+    EveryDays(u32),
+    /// On the next day that is one of the listed weekdays
+    Weekdays(Vec<Weekday>),
+}
+
+impl Recurrence {
+    /// Returns the moment the next instance is due after the previous due time. Monthly
+    /// instances are counted from the anchor, the due time of the first instance, so a chain
+    /// anchored to the 31st falls on the last day of the shorter months and returns to the 31st.
+    pub fn next_after(
+        &self,
+        anchor: DateTime<Local>,
+        previous: DateTime<Local>,
+    ) -> Result<DateTime<Local>, RecurrenceError> {
+        let add_days = |moment: DateTime<Local>, days: i64| {
+            Duration::try_days(days)
+                .and_then(|delta| moment.checked_add_signed(delta))
+                .ok_or_else(|| RecurrenceError::Overflow(self.to_string()))
+        };
+
+        match self {
+            Recurrence::Daily => add_days(previous, 1),
+            Recurrence::Weekly => add_days(previous, 7),
+            Recurrence::Monthly => {
+                // start from the month of the previous instance, earlier months are all before it
+                let elapsed = (previous.year() - anchor.year()) * 12 + previous.month() as i32
+                    - anchor.month() as i32;
+                let mut months = elapsed.max(1) as u32;
+                loop {
+                    match anchor.checked_add_months(Months::new(months)) {
+                        Some(next) if next > previous => return Ok(next),
+                        Some(_) => months += 1,
+                        None => return add_days(previous, 30),
+                    }
+                }
+            }
+            Recurrence::EveryDays(days) => add_days(previous, *days as i64),
+            Recurrence::Weekdays(weekdays) => {
+                let mut next = add_days(previous, 1)?;
+                // the set is never empty so a match is found within a week
+                while !weekdays.contains(&next.weekday()) {
+                    next = add_days(next, 1)?;
+                }
+                Ok(next)
+            }
+        }
+    }
+
+    /// Returns the next due time after the previous one that is also in the future compared to
+    /// now. Instances missed while the task was overdue are skipped.
+    pub fn next_due(
+        &self,
+        anchor: DateTime<Local>,
+        previous: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> Result<DateTime<Local>, RecurrenceError> {
+        let mut next = self.next_after(anchor, previous)?;
+        while next <= now {
+            next = self.next_after(anchor, next)?;
+        }
+        Ok(next)
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = s.trim().to_lowercase();
+        match rule.as_str() {
+            "daily" => return Ok(Recurrence::Daily),
+            "weekly" => return Ok(Recurrence::Weekly),
+            "monthly" => return Ok(Recurrence::Monthly),
+            "weekdays" => {
+                return Ok(Recurrence::Weekdays(vec![
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ]))
+            }
+            _ => {}
+        }
+
+        if let Some(days) = rule.strip_suffix('d') {
+            if !days.is_empty() && days.chars().all(|c| c.is_ascii_digit()) {
+                return match days.parse::<u32>() {
+                    Ok(days) if days > 0 && days <= MAX_RECURRENCE_DAYS => {
+                        Ok(Recurrence::EveryDays(days))
+                    }
+                    _ => Err(RecurrenceError::InvalidRule(s.to_string())),
+                };
+            }
+        }
+
+        let mut weekdays: Vec<Weekday> = vec![];
+        for day in rule.split(',') {
+            let weekday = Weekday::from_str(day)
+                .map_err(|_| RecurrenceError::InvalidRule(s.to_string()))?;
+            if !weekdays.contains(&weekday) {
+                weekdays.push(weekday);
+            }
+        }
+        Ok(Recurrence::Weekdays(weekdays))
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = RecurrenceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Recurrence::from_str(&value)
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly => write!(f, "weekly"),
+            Recurrence::Monthly => write!(f, "monthly"),
+            Recurrence::EveryDays(days) => write!(f, "{}d", days),
+            Recurrence::Weekdays(weekdays) => write!(
+                f,
+                "{}",
+                weekdays
+                    .iter()
+                    .map(|weekday| weekday.to_string().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// Recurrence template and the instance of it that is currently open (if any)
+pub struct RecurringTask {
+    /// The task that started the recurrence chain
+    pub template: Task,
+    /// Latest instance of the chain that is not completed yet
+    pub open_instance: Option<Task>,
+}
+
+/// List all recurrence templates that still have an active recurrence rule
+pub fn list_recurrence_templates(settings: &Settings) -> Result<Vec<RecurringTask>> {
+    let tasks = list_tasks(&None, &true, settings)
+        .with_context(|| "while scanning through all tasks")?;
+
+    let mut templates: Vec<RecurringTask> = vec![];
+    for task in &tasks {
+        if task.recurrence.is_none() || task.recurrence_parent().is_some() {
+            continue;
+        }
+        let open_instance = tasks
+            .iter()
+            .find(|instance| {
+                !instance.done
+                    && (instance.id == task.id || instance.recurrence_parent() == Some(task.id))
+            })
+            .cloned();
+        templates.push(RecurringTask {
+            template: task.clone(),
+            open_instance,
+        });
+    }
+
+    Ok(templates)
+}
+
+/// Stop the recurrence chain the task belongs to by removing the rule from the template and all
+/// its instances. Returns the number of tasks modified.
+pub fn stop_recurrence(id: &str, settings: &Settings) -> Result<usize> {
+    let task = load_task(id, settings)?;
+    let template_id = task.recurrence_parent().unwrap_or(task.id);
+
+    let mut modified: usize = 0;
+    for mut chain_task in list_tasks(&None, &true, settings)
+        .with_context(|| "while scanning through all tasks")?
+    {
+        if chain_task.recurrence.is_none() {
+            continue;
+        }
+        if chain_task.id == template_id || chain_task.recurrence_parent() == Some(template_id) {
+            chain_task.recurrence = None;
+            save_task(&mut chain_task, settings)
+                .with_context(|| "while saving task with recurrence removed")?;
+            modified += 1;
+        }
+    }
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_named_rules() {
+        assert_eq!(Recurrence::from_str("daily").unwrap(), Recurrence::Daily);
+        assert_eq!(Recurrence::from_str("Weekly").unwrap(), Recurrence::Weekly);
+        assert_eq!(Recurrence::from_str("monthly").unwrap(), Recurrence::Monthly);
+        assert_eq!(Recurrence::from_str("3d").unwrap(), Recurrence::EveryDays(3));
+    }
+
+    #[test]
+    fn parse_weekdays() {
+        assert_eq!(
+            Recurrence::from_str("mon,wed,fri").unwrap(),
+            Recurrence::Weekdays(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri])
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Recurrence::from_str("0d").is_err());
+        assert!(Recurrence::from_str("36501d").is_err());
+        assert!(Recurrence::from_str("4000000000d").is_err());
+        assert!(Recurrence::from_str("sometimes").is_err());
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for rule in ["daily", "weekly", "monthly", "14d", "tue,thu"] {
+            assert_eq!(Recurrence::from_str(rule).unwrap().to_string(), rule);
+        }
+    }
+
+    #[test]
+    fn monthly_clamps_to_month_end() {
+        let anchor = local(2024, 1, 31);
+        assert_eq!(
+            Recurrence::Monthly.next_after(anchor, anchor).unwrap(),
+            local(2024, 2, 29)
+        );
+    }
+
+    #[test]
+    fn monthly_returns_to_anchor_day() {
+        let anchor = local(2024, 1, 31);
+        let february = Recurrence::Monthly.next_after(anchor, anchor).unwrap();
+        let march = Recurrence::Monthly.next_after(anchor, february).unwrap();
+        let april = Recurrence::Monthly.next_after(anchor, march).unwrap();
+        assert_eq!(february, local(2024, 2, 29));
+        assert_eq!(march, local(2024, 3, 31));
+        assert_eq!(april, local(2024, 4, 30));
+    }
+
+    #[test]
+    fn weekdays_next_match() {
+        // 2024-05-03 is a friday
+        let rule = Recurrence::from_str("mon,wed").unwrap();
+        assert_eq!(
+            rule.next_after(local(2024, 5, 3), local(2024, 5, 3)).unwrap(),
+            local(2024, 5, 6)
+        );
+    }
+
+    #[test]
+    fn next_due_skips_past_instances() {
+        assert_eq!(
+            Recurrence::Weekly
+                .next_due(local(2024, 5, 1), local(2024, 5, 1), local(2024, 5, 20))
+                .unwrap(),
+            local(2024, 5, 22)
+        );
+    }
+
+    #[test]
+    fn next_after_overflow_is_an_error() {
+        let last = DateTime::<Local>::MAX_UTC.with_timezone(&Local);
+        assert_eq!(
+            Recurrence::EveryDays(MAX_RECURRENCE_DAYS).next_after(last, last),
+            Err(RecurrenceError::Overflow("36500d".to_string()))
+        );
+        assert!(Recurrence::Monthly.next_after(last, last).is_err());
+    }
+}
+
+// eof
@@ -0,0 +1,314 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use serde::Serialize;
+use strum::{EnumString, IntoStaticStr};
+use thiserror::Error;
+
+use crate::task::Task;
+
+/// Errors that can occur while building a time report
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReportError {
+    /// Grouping was not one of the known ones
+    #[error("invalid grouping `{0}`. try project, tag or a metadata key like x-client")]
+    InvalidGrouping(String),
+    /// Report would start after it ends
+    #[error("report can not start after it ends ({0} > {1})")]
+    InvalidRange(NaiveDate, NaiveDate),
+}
+
+/// Length of a single period in the report
+#[derive(EnumString, IntoStaticStr, clap::ValueEnum, Clone, Eq, PartialEq, Debug)]
+pub enum ReportPeriod {
+    /// Calendar day
+    Day,
+    /// ISO week starting from monday
+    Week,
+    /// Calendar month
+    Month,
+}
+
+impl ReportPeriod {
+    /// Returns the first day of the period the date belongs to
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            ReportPeriod::Day => date,
+            ReportPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            ReportPeriod::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// Returns human readable label for the period starting from the date
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            ReportPeriod::Day => start.format("%Y-%m-%d").to_string(),
+            ReportPeriod::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            ReportPeriod::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// Task attribute that the tracked time is grouped by
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ReportGrouping {
+    /// Group by the project of the task
+    Project,
+    /// Group by each tag of the task. Time of a task with several tags is counted to each of them.
+    Tag,
+    /// Group by the value of the metadata key
+    Metadata(String),
+}
+
+impl ReportGrouping {
+    /// Returns the groups the task belongs to
+    pub fn groups_of(&self, task: &Task) -> Vec<String> {
+        let groups = match self {
+            ReportGrouping::Project => task.project.clone().into_iter().collect(),
+            ReportGrouping::Tag => task.tags.clone().unwrap_or_default(),
+            ReportGrouping::Metadata(key) => task.metadata.get(key).cloned().into_iter().collect(),
+        };
+        if groups.is_empty() {
+            vec!["(none)".to_string()]
+        } else {
+            groups
+        }
+    }
+}
+
+impl FromStr for ReportGrouping {
+    type Err = ReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let grouping = s.trim().to_lowercase();
+        match grouping.as_str() {
+            "project" => Ok(ReportGrouping::Project),
+            "tag" => Ok(ReportGrouping::Tag),
+            key if key.starts_with("x-") && key.len() > 2 => {
+                Ok(ReportGrouping::Metadata(key.to_string()))
+            }
+            _ => Err(ReportError::InvalidGrouping(s.to_string())),
+        }
+    }
+}
+
+impl Display for ReportGrouping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportGrouping::Project => write!(f, "project"),
+            ReportGrouping::Tag => write!(f, "tag"),
+            ReportGrouping::Metadata(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+/// Single row of the time report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportEntry {
+    /// Human readable label of the period
+    pub period: String,
+    /// First day of the period
+    pub period_start: NaiveDate,
+    /// Group the time was tracked to
+    pub group: String,
+    /// Tracked time in seconds
+    pub seconds: i64,
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Local> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    // midnight can be skipped or repeated on days when daylight saving time changes
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&midnight))
+}
+
+/// Split the time span into pieces at each local midnight. Returns the date and the length of
+/// each piece.
+pub fn split_by_day(start: DateTime<Local>, end: DateTime<Local>) -> Vec<(NaiveDate, Duration)> {
+    let mut pieces: Vec<(NaiveDate, Duration)> = vec![];
+    let mut current = start;
+    while current < end {
+        let date = current.date_naive();
+        let next_midnight = start_of_day(date + Duration::days(1));
+        let piece_end = if next_midnight < end {
+            next_midnight
+        } else {
+            end
+        };
+        pieces.push((date, piece_end - current));
+        current = piece_end;
+    }
+    pieces
+}
+
+/// Sum the time tracked to the tasks between the dates (both inclusive) per period and group.
+/// Running time tracking is counted up to now. Entries are sorted by period and group.
+pub fn time_report(
+    tasks: &[Task],
+    from: NaiveDate,
+    to: NaiveDate,
+    period: &ReportPeriod,
+    grouping: &ReportGrouping,
+    now: DateTime<Local>,
+) -> Vec<ReportEntry> {
+    let range_start = start_of_day(from);
+    let range_end = start_of_day(to + Duration::days(1));
+
+    let mut totals: BTreeMap<(NaiveDate, String), i64> = BTreeMap::new();
+    for task in tasks {
+        let Some(timetracker) = &task.timetracker else {
+            continue;
+        };
+        let groups = grouping.groups_of(task);
+        for track in timetracker {
+            // clip the span to the range before splitting it to days
+            let start = track.start_time.max(range_start);
+            let end = track.end_time.unwrap_or(now).min(range_end);
+            for (date, duration) in split_by_day(start, end) {
+                for group in &groups {
+                    *totals
+                        .entry((period.start_of(date), group.clone()))
+                        .or_insert(0) += duration.num_seconds();
+                }
+            }
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((period_start, group), seconds)| ReportEntry {
+            period: period.label(period_start),
+            period_start,
+            group,
+            seconds,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TimeTrack;
+
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    fn tracked_task(
+        descriptor: &str,
+        spans: Vec<(DateTime<Local>, Option<DateTime<Local>>)>,
+    ) -> Task {
+        let mut task = Task::from_task_descriptor(&descriptor.to_string()).unwrap();
+        task.timetracker = Some(
+            spans
+                .into_iter()
+                .map(|(start_time, end_time)| TimeTrack {
+                    start_time,
+                    end_time,
+                    annotation: None,
+                })
+                .collect(),
+        );
+        task
+    }
+
+    #[test]
+    fn split_span_at_midnight() {
+        assert_eq!(
+            split_by_day(local(14, 22, 0), local(16, 1, 30)),
+            vec![
+                (date(14), Duration::hours(2)),
+                (date(15), Duration::hours(24)),
+                (date(16), Duration::minutes(90)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_grouping() {
+        assert_eq!(
+            ReportGrouping::from_str("project").unwrap(),
+            ReportGrouping::Project
+        );
+        assert_eq!(
+            ReportGrouping::from_str("x-client").unwrap(),
+            ReportGrouping::Metadata("x-client".to_string())
+        );
+        assert!(ReportGrouping::from_str("client").is_err());
+    }
+
+    #[test]
+    fn report_clips_to_range() {
+        let tasks = vec![tracked_task(
+            "report @work",
+            vec![(local(13, 23, 0), Some(local(14, 2, 0)))],
+        )];
+        let report = time_report(
+            &tasks,
+            date(14),
+            date(20),
+            &ReportPeriod::Day,
+            &ReportGrouping::Project,
+            local(20, 12, 0),
+        );
+        assert_eq!(
+            report,
+            vec![ReportEntry {
+                period: "2024-05-14".to_string(),
+                period_start: date(14),
+                group: "work".to_string(),
+                seconds: 2 * 3600,
+            }]
+        );
+    }
+
+    #[test]
+    fn report_groups_by_week_and_tag() {
+        let tasks = vec![
+            tracked_task(
+                "first #a #b",
+                vec![
+                    (local(13, 9, 0), Some(local(13, 10, 0))),
+                    (local(15, 9, 0), Some(local(15, 9, 30))),
+                ],
+            ),
+            // running time tracking is counted up to now
+            tracked_task("second #a", vec![(local(20, 9, 0), None)]),
+        ];
+        let report = time_report(
+            &tasks,
+            date(1),
+            date(31),
+            &ReportPeriod::Week,
+            &ReportGrouping::Tag,
+            local(20, 10, 0),
+        );
+        let summary: Vec<(String, String, i64)> = report
+            .into_iter()
+            .map(|entry| (entry.period, entry.group, entry.seconds))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2024-W20".to_string(), "a".to_string(), 5400),
+                ("2024-W20".to_string(), "b".to_string(), 5400),
+                ("2024-W21".to_string(), "a".to_string(), 3600),
+            ]
+        );
+    }
+}
+
+// eof
@@ -3,9 +3,15 @@ use color_eyre::eyre::{bail, Context, Result};
 use config::Config;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, fs::create_dir_all, path::PathBuf};
+use std::{collections::BTreeMap, fmt::Display, fs::create_dir_all, path::PathBuf};
 use thiserror::Error;
 
+use crate::{
+    namespace::current_namespace,
+    output::{print_structured, OutputFormat},
+    storage::StorageBackend,
+};
+
 /// Errors that can occur during settings handling
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum SettingsError {
@@ -14,6 +20,19 @@ pub enum SettingsError {
     DataDirectoryDoesNotExist,
 }
 
+/// Where only one task at a time is allowed to be tracked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SingleActive {
+    /// Any number of tasks can be tracked at the same time
+    #[default]
+    Off,
+    /// One task at a time in each namespace
+    Namespace,
+    /// One task at a time across all namespaces
+    Global,
+}
+
 /// Task spesific settings
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
@@ -31,6 +50,11 @@ pub struct TaskSettings {
     /// If true when marking task done the special tags that might be in effect for the task are
     /// also removed.
     pub clearpsecialtags: bool,
+    /// If true then time tracking can be started for a task that still has unfinished
+    /// dependencies.
+    pub blockedstart: bool,
+    /// Where starting a task is refused while another task is being tracked
+    pub singleactive: SingleActive,
 }
 
 impl Default for TaskSettings {
@@ -41,6 +65,8 @@ impl Default for TaskSettings {
             specialvisible: true,
             stopondone: true,
             clearpsecialtags: true,
+            blockedstart: false,
+            singleactive: SingleActive::Off,
         }
     }
 }
@@ -63,6 +89,8 @@ pub struct OutputSettings {
     pub totals: bool,
     /// Score multiplier that can be used to adjust the weight given by score algorithm
     pub scoremultiplier: f64,
+    /// Format of the command output, tables for humans or JSON/YAML for other programs
+    pub format: OutputFormat,
 }
 
 impl Default for OutputSettings {
@@ -74,7 +102,146 @@ impl Default for OutputSettings {
             namespace: true,
             descriptionlength: 60,
             totals: true,
-            scoremultiplier: 1.0
+            scoremultiplier: 1.0,
+            format: OutputFormat::Table,
+        }
+    }
+}
+
+/// Point on the due date curve of the score algorithm
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DueCurvePoint {
+    /// Task due within this many days gets the points
+    pub days: i64,
+    /// Points given to the task
+    pub points: f64,
+}
+
+/// Coefficients of the score algorithm that orders tasks by their urgency
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScoreSettings {
+    /// Points for a task that is part of a project
+    pub project: f64,
+    /// Points for each tag of the task
+    pub tag: f64,
+    /// Points for a task that has time tracking running
+    pub running: f64,
+    /// Points for each time tracking entry of the task
+    pub timetrack: f64,
+    /// Points for a low priority task
+    pub prioritylow: f64,
+    /// Points for a medium priority task
+    pub prioritymedium: f64,
+    /// Points for a high priority task
+    pub priorityhigh: f64,
+    /// Points for a critical priority task
+    pub prioritycritical: f64,
+    /// Points for a task that is past its due date
+    pub overdue: f64,
+    /// Points for a task that is due soon. The first point of the curve whose days the due date
+    /// is within is used.
+    pub duecurve: Vec<DueCurvePoint>,
+    /// Points for a task that is due later than any point of the due date curve
+    pub duelater: f64,
+    /// Points for each day since the task was created
+    pub age: f64,
+    /// Points for a task that has the special tag "next"
+    pub next: f64,
+    /// Points removed from a task that has the special tag "hold"
+    pub hold: f64,
+    /// Points for a task that other unfinished tasks depend on
+    pub blocking: f64,
+    /// Points removed from a task that has unfinished dependencies
+    pub blocked: f64,
+    /// Additional points for a task that has the tag
+    pub tags: BTreeMap<String, f64>,
+    /// Additional points for a task that is part of the project. The points of the most specific
+    /// level of a dotted project apply.
+    pub projects: BTreeMap<String, f64>,
+}
+
+impl Default for ScoreSettings {
+    fn default() -> Self {
+        Self {
+            project: 3.0,
+            tag: 2.0,
+            running: 15.0,
+            timetrack: 1.0,
+            prioritylow: 1.0,
+            prioritymedium: 3.0,
+            priorityhigh: 8.0,
+            prioritycritical: 13.0,
+            overdue: 10.0,
+            duecurve: vec![
+                DueCurvePoint {
+                    days: 2,
+                    points: 7.0,
+                },
+                DueCurvePoint {
+                    days: 5,
+                    points: 3.0,
+                },
+            ],
+            duelater: 1.0,
+            // every seven days grants one point
+            age: 1.0 / 7.0,
+            next: 100.0,
+            hold: 20.0,
+            blocking: 8.0,
+            blocked: 5.0,
+            tags: BTreeMap::new(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+/// Direction tracked time spans are rounded to for billing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rounding {
+    /// Round up to the next full increment
+    #[default]
+    Up,
+    /// Round to the nearest increment, halves up
+    Nearest,
+    /// Round down to the previous full increment
+    Down,
+}
+
+/// Billing settings for invoicing tracked time
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BillingSettings {
+    /// Hourly rate of billable time when neither the project nor the client has a rate of its own
+    pub rate: f64,
+    /// Currency of the rates, shown along the amounts
+    pub currency: String,
+    /// Each tracked time span is rounded to a multiple of this many minutes. Zero bills the
+    /// exact time.
+    pub roundminutes: i64,
+    /// Direction the time spans are rounded to
+    pub rounding: Rounding,
+    /// If true then tasks are billable unless they are flagged to be non-billable, if false then
+    /// only tasks flagged to be billable are billed.
+    pub billable: bool,
+    /// Hourly rates of projects. The rate of a project applies to its subprojects as well and it
+    /// takes precedence over the rate of the client.
+    pub projects: BTreeMap<String, f64>,
+    /// Hourly rates of clients named in the `x-client` metadata of the tasks
+    pub clients: BTreeMap<String, f64>,
+}
+
+impl Default for BillingSettings {
+    fn default() -> Self {
+        Self {
+            rate: 0.0,
+            currency: String::new(),
+            roundminutes: 0,
+            rounding: Rounding::Up,
+            billable: true,
+            projects: BTreeMap::new(),
+            clients: BTreeMap::new(),
         }
     }
 }
@@ -114,6 +281,14 @@ pub struct DataSettings {
     pub createdir: bool,
     /// How many task and note data file backups should be rotated?
     pub rotate: usize,
+    /// Storage backend used for tasks and notes
+    pub backend: StorageBackend,
+    /// How many days deleted tasks and notes are kept in the trash before they are purged. Zero
+    /// keeps them until the trash is purged manually.
+    pub trashdays: usize,
+    /// How many of the most recent commands are kept in the journal for undo. Zero keeps all of
+    /// them.
+    pub journallimit: usize,
 }
 
 impl Default for DataSettings {
@@ -124,6 +299,9 @@ impl Default for DataSettings {
             path: String::from(proj_dirs.data_dir().to_str().unwrap()),
             createdir: true,
             rotate: 3,
+            backend: StorageBackend::Flatfile,
+            trashdays: 0,
+            journallimit: 100,
         }
     }
 }
@@ -146,6 +324,10 @@ pub struct Settings {
     pub task: TaskSettings,
     /// Display/output settings
     pub output: OutputSettings,
+    /// Score algorithm coefficients
+    pub score: ScoreSettings,
+    /// Hourly rates and rounding of billed time
+    pub billing: BillingSettings,
 }
 
 impl AsRef<Settings> for Settings {
@@ -164,7 +346,7 @@ impl Settings {
     /// Create new settings struct by creating defaults and overwriting them from either config
     /// files or environment variables.
     pub fn new(namespace: Option<String>, config_file: &str) -> Result<Self> {
-        let settings: Settings = Config::builder()
+        let mut settings: Settings = Config::builder()
             .set_override_option("namespace", namespace)?
             .add_source(config::File::with_name(config_file).required(false))
             .add_source(
@@ -177,6 +359,12 @@ impl Settings {
             .try_deserialize()
             .with_context(|| "while applying defaults to configuration")?;
 
+        if settings.namespace.is_empty() {
+            // namespace was not given so continue in the one chosen last
+            settings.namespace =
+                current_namespace(&settings).with_context(|| "while reading current namespace")?;
+        }
+
         Ok(settings)
     }
 
@@ -196,7 +384,7 @@ impl Settings {
     pub fn task_db_pathbuf(&self) -> Result<PathBuf> {
         let pathbuf = &self.db_pathbuf()?.join("tasks");
         if !pathbuf.is_dir() && self.data.createdir {
-            create_dir_all(&pathbuf).with_context(|| "while creating tasks data directory")?;
+            create_dir_all(pathbuf).with_context(|| "while creating tasks data directory")?;
         } else if !pathbuf.is_dir() && !self.data.createdir {
             bail!(SettingsError::DataDirectoryDoesNotExist);
         }
@@ -208,7 +396,7 @@ impl Settings {
     pub fn note_db_pathbuf(&self) -> Result<PathBuf> {
         let pathbuf = &self.db_pathbuf()?.join("notes");
         if !pathbuf.is_dir() && self.data.createdir {
-            create_dir_all(&pathbuf).with_context(|| "while creating notes data directory")?;
+            create_dir_all(pathbuf).with_context(|| "while creating notes data directory")?;
         } else if !pathbuf.is_dir() && !self.data.createdir {
             bail!(SettingsError::DataDirectoryDoesNotExist);
         }
@@ -216,8 +404,11 @@ impl Settings {
     }
 }
 
-/// Show active configuration. Uses Bat.
+/// Show active configuration. Uses Bat, unless the output format is JSON or YAML.
 pub fn show_config(settings: &Settings) -> Result<()> {
+    if print_structured(settings, settings)? {
+        return Ok(());
+    }
     let settings_toml = format!("{}", settings);
     PrettyPrinter::new()
         .language("toml")
@@ -0,0 +1,207 @@
+use std::{fs::remove_file, path::Path};
+
+use color_eyre::eyre::{bail, Context, Result};
+use glob::glob;
+use uuid::Uuid;
+
+use super::{
+    index::{ids_in, TaskIndex, TaskSummary},
+    Storage, StorageError,
+};
+#[cfg(feature = "note")]
+use crate::note::{note_pathbuf_from_id, Note};
+use crate::{
+    settings::Settings,
+    task::{task_pathbuf_from_id, Task},
+};
+
+/// Storage backend where each task and note is a YAML file of its own in the namespace
+/// directory. Task files are replaced atomically, note files are locked while written and the
+/// previous versions are rotated as backups.
+/// Tasks are listed through an index file in the task directory, see [TaskIndex].
+pub struct FlatfileStorage {
+    settings: Settings,
+}
+
+impl FlatfileStorage {
+    /// Create flatfile storage for the namespace in the settings
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            settings: settings.clone(),
+        }
+    }
+}
+
+/// Returns the amount of YAML files in the directory, optionally including the rotated backups
+fn count_files_in(db_pathbuf: &Path, include_backups: bool) -> Result<usize> {
+    if !include_backups {
+        return Ok(ids_in(db_pathbuf)?.len());
+    }
+    Ok(glob(db_pathbuf.join("*.yaml").to_str().unwrap())
+        .with_context(|| "while traversing data directory files")?
+        .count())
+}
+
+impl Storage for FlatfileStorage {
+    fn load_task(&self, id: &Uuid) -> Result<Task> {
+        let task_pathbuf = task_pathbuf_from_id(&id.to_string(), &self.settings)?;
+        if !task_pathbuf.is_file() {
+            bail!(StorageError::NotFound(id.to_string()));
+        }
+        Task::load_yaml_file_from(&task_pathbuf).with_context(|| "while loading task yaml file")
+    }
+
+    fn save_task(&self, task: &mut Task) -> Result<()> {
+        // the index is updated as the task file is written
+        let task_pathbuf = task_pathbuf_from_id(&task.id.to_string(), &self.settings)?;
+        task.save_yaml_file_to(&task_pathbuf, &self.settings.data.rotate)
+            .with_context(|| "while saving task yaml file")
+    }
+
+    fn delete_task(&self, id: &Uuid) -> Result<()> {
+        let task_pathbuf = task_pathbuf_from_id(&id.to_string(), &self.settings)?;
+        remove_file(task_pathbuf).with_context(|| "while removing task file")?;
+        TaskIndex::forget(id, &self.settings.task_db_pathbuf()?)
+            .with_context(|| "while updating task index")
+    }
+
+    fn task_exists(&self, id: &Uuid) -> Result<bool> {
+        Ok(task_pathbuf_from_id(&id.to_string(), &self.settings)?.is_file())
+    }
+
+    fn task_ids(&self) -> Result<Vec<Uuid>> {
+        ids_in(&self.settings.task_db_pathbuf()?)
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>> {
+        let mut tasks: Vec<Task> = vec![];
+        for id in self.task_ids()? {
+            tasks.push(self.load_task(&id)?);
+        }
+        Ok(tasks)
+    }
+
+    fn load_open_tasks(&self) -> Result<Vec<Task>> {
+        // completed tasks are skipped through the index without parsing their files
+        Ok(self.select_tasks(&|summary| !summary.done)?.1)
+    }
+
+    fn task_summaries(&self) -> Result<Vec<TaskSummary>> {
+        Ok(TaskIndex::refreshed(&self.settings.task_db_pathbuf()?)
+            .with_context(|| "while updating task index")?
+            .summaries())
+    }
+
+    fn select_tasks(
+        &self,
+        keep: &dyn Fn(&TaskSummary) -> bool,
+    ) -> Result<(Vec<TaskSummary>, Vec<Task>)> {
+        // only the files of the tasks kept by their summaries in the index are parsed
+        let summaries = self.task_summaries()?;
+        let mut tasks: Vec<Task> = vec![];
+        for summary in summaries.iter().filter(|summary| keep(summary)) {
+            tasks.push(self.load_task(&summary.id)?);
+        }
+        Ok((summaries, tasks))
+    }
+
+    fn count_tasks(&self, include_backups: bool) -> Result<usize> {
+        count_files_in(&self.settings.task_db_pathbuf()?, include_backups)
+    }
+
+    fn reindex(&self) -> Result<usize> {
+        Ok(TaskIndex::rebuild(&self.settings.task_db_pathbuf()?)
+            .with_context(|| "while rebuilding task index")?
+            .len())
+    }
+
+    #[cfg(feature = "note")]
+    fn load_note(&self, task_id: &Uuid) -> Result<Note> {
+        let note_pathbuf = note_pathbuf_from_id(&task_id.to_string(), &self.settings)?;
+        if !note_pathbuf.is_file() {
+            bail!(StorageError::NotFound(task_id.to_string()));
+        }
+        Note::load_yaml_file_from(&note_pathbuf).with_context(|| "while loading note yaml file")
+    }
+
+    #[cfg(feature = "note")]
+    fn save_note(&self, note: &mut Note) -> Result<()> {
+        let note_pathbuf = note_pathbuf_from_id(&note.task_id.to_string(), &self.settings)?;
+        note.save_yaml_file_to(&note_pathbuf, &self.settings.data.rotate)
+            .with_context(|| "while saving note yaml file")
+    }
+
+    #[cfg(feature = "note")]
+    fn delete_note(&self, task_id: &Uuid) -> Result<()> {
+        let note_pathbuf = note_pathbuf_from_id(&task_id.to_string(), &self.settings)?;
+        remove_file(note_pathbuf).with_context(|| "while removing note file")
+    }
+
+    #[cfg(feature = "note")]
+    fn note_exists(&self, task_id: &Uuid) -> Result<bool> {
+        Ok(note_pathbuf_from_id(&task_id.to_string(), &self.settings)?.is_file())
+    }
+
+    #[cfg(feature = "note")]
+    fn note_ids(&self) -> Result<Vec<Uuid>> {
+        ids_in(&self.settings.note_db_pathbuf()?)
+    }
+
+    #[cfg(feature = "note")]
+    fn load_notes(&self) -> Result<Vec<Note>> {
+        let mut notes: Vec<Note> = vec![];
+        for id in self.note_ids()? {
+            notes.push(self.load_note(&id)?);
+        }
+        Ok(notes)
+    }
+
+    #[cfg(feature = "note")]
+    fn count_notes(&self, include_backups: bool) -> Result<usize> {
+        count_files_in(&self.settings.note_db_pathbuf()?, include_backups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{write, File};
+
+    use super::*;
+    use crate::{task::save_task, testing::TestData};
+
+    #[test]
+    fn select_tasks_parses_only_kept_tasks() {
+        let settings = TestData::new();
+        let mut kept = Task::new("kept".to_string()).unwrap();
+        let mut skipped = Task::new("skipped".to_string()).unwrap();
+        save_task(&mut kept, &settings).unwrap();
+        save_task(&mut skipped, &settings).unwrap();
+
+        // garble the skipped task file behind the index, keeping its size and modification time
+        let skipped_pathbuf = task_pathbuf_from_id(&skipped.id.to_string(), &settings).unwrap();
+        let modified = File::open(&skipped_pathbuf)
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .modified()
+            .unwrap();
+        let size = skipped_pathbuf.metadata().unwrap().len() as usize;
+        write(&skipped_pathbuf, "[".repeat(size)).unwrap();
+        File::options()
+            .write(true)
+            .open(&skipped_pathbuf)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let (summaries, tasks) = FlatfileStorage::new(&settings)
+            .select_tasks(&|summary| summary.description == "kept")
+            .unwrap();
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, kept.id);
+    }
+}
+
+// eof
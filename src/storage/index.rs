@@ -0,0 +1,329 @@
+use std::{
+    collections::BTreeMap,
+    fs::{metadata, read_to_string, rename, write},
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use color_eyre::eyre::{Context, Result};
+use file_lock::{FileLock, FileOptions};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::task::Task;
+
+/// Name of the index file kept in the directory of the task files
+pub const INDEX_FILE: &str = "tsk-rs-index.json";
+
+/// Name of the file locked while the index is read and rewritten
+const INDEX_LOCK_FILE: &str = "tsk-rs-index.lock";
+
+/// Fields of a task needed to list, filter and resolve tasks without parsing the task file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskSummary {
+    /// Unique identifier of the task
+    pub id: Uuid,
+    /// Numeric short id of the task (if assigned)
+    pub short_id: Option<usize>,
+    /// Description of the task
+    pub description: String,
+    /// Is the task completed or not
+    pub done: bool,
+    /// Project of the task (if any)
+    pub project: Option<String>,
+    /// Tags of the task (if any)
+    pub tags: Option<Vec<String>>,
+    /// Due date of the task (if any) as it is in the metadata
+    pub due: Option<String>,
+    /// Priority of the task (if any) as it is in the metadata
+    pub priority: Option<String>,
+    /// Ids of the tasks (if any) the task depends on
+    pub depends: Option<Vec<Uuid>>,
+    /// Id of the parent task (if the task is a subtask)
+    pub parent: Option<Uuid>,
+}
+
+impl From<&Task> for TaskSummary {
+    fn from(task: &Task) -> Self {
+        Self {
+            id: task.id,
+            short_id: task.short_id(),
+            description: task.description.clone(),
+            done: task.done,
+            project: task.project.clone(),
+            tags: task.tags.clone(),
+            due: task.metadata.get("tsk-rs-task-due-time").cloned(),
+            priority: task.metadata.get("tsk-rs-task-priority").cloned(),
+            depends: task.depends.clone(),
+            parent: task.parent,
+        }
+    }
+}
+
+/// Summary of a task file together with the file stamp it was read from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Modification time of the task file when it was indexed
+    pub modified: SystemTime,
+    /// Size of the task file when it was indexed
+    pub size: u64,
+    /// Summary of the task as it was in the file
+    pub summary: TaskSummary,
+}
+
+/// Index of the task files of a namespace. Entries are validated against the modification time
+/// and size of the task files, so only files changed outside of the index need to be parsed.
+///
+/// The index file is only rewritten while its lock file is held, so concurrent processes do not
+/// lose each others updates.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TaskIndex {
+    entries: BTreeMap<Uuid, IndexEntry>,
+}
+
+/// Returns the ids of the YAML files in the directory. Rotated backup files are disregarded.
+pub fn ids_in(db_pathbuf: &Path) -> Result<Vec<Uuid>> {
+    let mut ids: Vec<Uuid> = vec![];
+    for filename in glob(db_pathbuf.join("*.yaml").to_str().unwrap())
+        .with_context(|| "while traversing data directory files")?
+    {
+        // if the filename is u-u-i-d.3.yaml for example it is a backup file and should be disregarded
+        let filename = filename?;
+        let stem = filename.file_stem().unwrap().to_string_lossy().to_string();
+        if let Ok(id) = Uuid::parse_str(&stem) {
+            ids.push(id);
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Returns the modification time and size of the file
+pub(crate) fn file_stamp(pathbuf: &Path) -> Result<(SystemTime, u64)> {
+    let metadata = metadata(pathbuf).with_context(|| "while reading task file metadata")?;
+    let modified = metadata
+        .modified()
+        .with_context(|| "while reading task file modification time")?;
+    Ok((modified, metadata.len()))
+}
+
+/// Lock the index of the task directory. The lock is held until the returned lock is dropped.
+fn lock_index(task_db_pathbuf: &Path) -> Result<FileLock> {
+    let options = FileOptions::new().write(true).create(true).append(true);
+    FileLock::lock(task_db_pathbuf.join(INDEX_LOCK_FILE), true, options)
+        .with_context(|| "while locking task index")
+}
+
+impl TaskIndex {
+    /// Load the index from the file. A missing or unreadable index file results in an empty
+    /// index, which is then rebuilt from the task files on [TaskIndex::refresh].
+    pub fn load(index_pathbuf: &Path) -> Self {
+        read_to_string(index_pathbuf)
+            .ok()
+            .and_then(|index_json| serde_json::from_str(&index_json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the index to the file. The index is written to a temporary file first and then moved
+    /// in place so that concurrent readers never see a partially written index.
+    pub fn save(&self, index_pathbuf: &Path) -> Result<()> {
+        let index_json =
+            serde_json::to_string(self).with_context(|| "while serializing task index")?;
+        let mut temp_pathbuf = PathBuf::from(index_pathbuf);
+        temp_pathbuf.set_extension(format!("{}.tmp", process::id()));
+        write(&temp_pathbuf, index_json).with_context(|| "while writing task index file")?;
+        rename(&temp_pathbuf, index_pathbuf).with_context(|| "while replacing task index file")
+    }
+
+    /// Update the entry of the task that was just saved to the file
+    pub fn update(&mut self, task: &Task, task_pathbuf: &Path) -> Result<()> {
+        let (modified, size) = file_stamp(task_pathbuf)?;
+        self.entries.insert(
+            task.id,
+            IndexEntry {
+                modified,
+                size,
+                summary: TaskSummary::from(task),
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove the entry of the task
+    pub fn remove(&mut self, id: &Uuid) -> bool {
+        self.entries.remove(id).is_some()
+    }
+
+    /// Bring the index up to date with the task files in the directory. Files that are new or
+    /// changed since they were indexed are parsed and entries of removed files are dropped.
+    /// Returns true if the index was changed.
+    pub fn refresh(&mut self, task_db_pathbuf: &Path) -> Result<bool> {
+        let ids = ids_in(task_db_pathbuf)?;
+        let mut changed = false;
+
+        let before = self.entries.len();
+        self.entries.retain(|id, _| ids.binary_search(id).is_ok());
+        changed |= self.entries.len() != before;
+
+        for id in ids {
+            let task_pathbuf = task_db_pathbuf.join(format!("{}.yaml", id));
+            let (modified, size) = file_stamp(&task_pathbuf)?;
+            if self
+                .entries
+                .get(&id)
+                .is_some_and(|entry| entry.modified == modified && entry.size == size)
+            {
+                continue;
+            }
+            let task = Task::load_yaml_file_from(&task_pathbuf)
+                .with_context(|| "while loading task yaml file to index")?;
+            self.entries.insert(
+                id,
+                IndexEntry {
+                    modified,
+                    size,
+                    summary: TaskSummary::from(&task),
+                },
+            );
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
+    /// Record the task that was just written to the task file in the index of its directory
+    pub fn record(task: &Task, task_pathbuf: &Path) -> Result<()> {
+        let task_db_pathbuf = task_pathbuf.parent().unwrap_or(Path::new("."));
+        let _lock = lock_index(task_db_pathbuf)?;
+        let index_pathbuf = task_db_pathbuf.join(INDEX_FILE);
+        let mut index = Self::load(&index_pathbuf);
+        index.update(task, task_pathbuf)?;
+        index.save(&index_pathbuf)
+    }
+
+    /// Remove the task from the index of the directory
+    pub fn forget(id: &Uuid, task_db_pathbuf: &Path) -> Result<()> {
+        let _lock = lock_index(task_db_pathbuf)?;
+        let index_pathbuf = task_db_pathbuf.join(INDEX_FILE);
+        let mut index = Self::load(&index_pathbuf);
+        if index.remove(id) {
+            index.save(&index_pathbuf)?;
+        }
+        Ok(())
+    }
+
+    /// Load the index of the directory and bring it up to date with the task files, saving it if
+    /// anything changed
+    pub fn refreshed(task_db_pathbuf: &Path) -> Result<Self> {
+        let _lock = lock_index(task_db_pathbuf)?;
+        let index_pathbuf = task_db_pathbuf.join(INDEX_FILE);
+        let mut index = Self::load(&index_pathbuf);
+        if index.refresh(task_db_pathbuf)? {
+            index.save(&index_pathbuf)?;
+        }
+        Ok(index)
+    }
+
+    /// Build the index of the directory from scratch and save it
+    pub fn rebuild(task_db_pathbuf: &Path) -> Result<Self> {
+        let _lock = lock_index(task_db_pathbuf)?;
+        let mut index = Self::default();
+        index.refresh(task_db_pathbuf)?;
+        index.save(&task_db_pathbuf.join(INDEX_FILE))?;
+        Ok(index)
+    }
+
+    /// Returns the summaries of the indexed tasks
+    pub fn summaries(&self) -> Vec<TaskSummary> {
+        self.entries
+            .values()
+            .map(|entry| entry.summary.clone())
+            .collect()
+    }
+
+    /// Returns the amount of indexed tasks
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no tasks in the index
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_file;
+
+    use super::*;
+    use crate::testing::TestData;
+
+    #[test]
+    fn saving_task_file_updates_index() {
+        let data = TestData::new();
+        let task_db_pathbuf = data.task_db_pathbuf().unwrap();
+        let mut task = Task::new("first".to_string()).unwrap();
+        task.save_yaml_file_to(&task_db_pathbuf.join(format!("{}.yaml", task.id)), &0)
+            .unwrap();
+
+        let index = TaskIndex::load(&task_db_pathbuf.join(INDEX_FILE));
+        assert_eq!(index.summaries(), vec![TaskSummary::from(&task)]);
+        // index is current so nothing is parsed again
+        assert!(!TaskIndex::load(&task_db_pathbuf.join(INDEX_FILE))
+            .refresh(&task_db_pathbuf)
+            .unwrap());
+    }
+
+    #[test]
+    fn saving_other_files_leaves_index_alone() {
+        let data = TestData::new();
+        let task_db_pathbuf = data.task_db_pathbuf().unwrap();
+        let mut task = Task::new("in the trash".to_string()).unwrap();
+        task.save_yaml_file_to(&task_db_pathbuf.join("task.yaml"), &0)
+            .unwrap();
+
+        assert!(!task_db_pathbuf.join(INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn refresh_follows_task_files_changed_outside() {
+        let data = TestData::new();
+        let task_db_pathbuf = data.task_db_pathbuf().unwrap();
+        let task_pathbuf = |task: &Task| task_db_pathbuf.join(format!("{}.yaml", task.id));
+        let mut first = Task::new("first".to_string()).unwrap();
+        let mut second = Task::new("second".to_string()).unwrap();
+        first.save_yaml_file_to(&task_pathbuf(&first), &0).unwrap();
+        second
+            .save_yaml_file_to(&task_pathbuf(&second), &0)
+            .unwrap();
+
+        // files changed and removed behind the index
+        remove_file(task_db_pathbuf.join(INDEX_FILE)).unwrap();
+        let mut index = TaskIndex::rebuild(&task_db_pathbuf).unwrap();
+        first.description = "first, changed outside".to_string();
+        write(task_pathbuf(&first), first.to_yaml_string().unwrap()).unwrap();
+        remove_file(task_pathbuf(&second)).unwrap();
+
+        assert!(index.refresh(&task_db_pathbuf).unwrap());
+        let summaries = index.summaries();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].description, "first, changed outside");
+    }
+
+    #[test]
+    fn forget_removes_entry() {
+        let data = TestData::new();
+        let task_db_pathbuf = data.task_db_pathbuf().unwrap();
+        let mut task = Task::new("short lived".to_string()).unwrap();
+        task.save_yaml_file_to(&task_db_pathbuf.join(format!("{}.yaml", task.id)), &0)
+            .unwrap();
+
+        TaskIndex::forget(&task.id, &task_db_pathbuf).unwrap();
+        assert!(TaskIndex::load(&task_db_pathbuf.join(INDEX_FILE)).is_empty());
+    }
+}
+
+// eof
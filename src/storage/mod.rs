@@ -0,0 +1,195 @@
+use color_eyre::eyre::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use strum::{EnumString, IntoStaticStr};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use crate::note::Note;
+use crate::{settings::Settings, task::Task};
+use index::TaskSummary;
+
+/// YAML flatfile storage backend
+pub mod flatfile;
+
+/// Index of the task files for the flatfile storage backend
+pub mod index;
+
+/// SQLite storage backend
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Errors that can occur when working with the storage backends
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StorageError {
+    /// Backend was not compiled in
+    #[error("storage backend `{0}` is not available in this build")]
+    BackendNotAvailable(String),
+    /// Data was asked to be migrated to the backend it already is in
+    #[error("source and target storage backend are the same")]
+    SameBackend,
+    /// Entry does not exist in the storage
+    #[error("no entry found with id `{0}`")]
+    NotFound(String),
+}
+
+/// Available storage backends
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, EnumString, IntoStaticStr, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum StorageBackend {
+    /// Each task and note is a YAML file of its own
+    Flatfile,
+    /// Tasks and notes are stored in a SQLite database file per namespace
+    Sqlite,
+}
+
+/// Interface for the storage of tasks and notes. Implementations persist the entries as they are,
+/// any validation is left to the caller.
+pub trait Storage {
+    /// Load the task with the id
+    fn load_task(&self, id: &Uuid) -> Result<Task>;
+    /// Save the task, replacing an existing one with the same id
+    fn save_task(&self, task: &mut Task) -> Result<()>;
+    /// Delete the task with the id
+    fn delete_task(&self, id: &Uuid) -> Result<()>;
+    /// Returns true if there is a task with the id
+    fn task_exists(&self, id: &Uuid) -> Result<bool>;
+    /// Returns the ids of all tasks
+    fn task_ids(&self) -> Result<Vec<Uuid>>;
+    /// Load all tasks
+    fn load_tasks(&self) -> Result<Vec<Task>>;
+    /// Load the tasks that have not been completed
+    fn load_open_tasks(&self) -> Result<Vec<Task>> {
+        Ok(self
+            .load_tasks()?
+            .into_iter()
+            .filter(|task| !task.done)
+            .collect())
+    }
+    /// Returns the summaries of all tasks
+    fn task_summaries(&self) -> Result<Vec<TaskSummary>> {
+        Ok(self.load_tasks()?.iter().map(TaskSummary::from).collect())
+    }
+    /// Returns the summaries of all tasks together with the tasks whose summary is kept by the
+    /// predicate. Backends that keep an index of the tasks load only the kept tasks.
+    fn select_tasks(
+        &self,
+        keep: &dyn Fn(&TaskSummary) -> bool,
+    ) -> Result<(Vec<TaskSummary>, Vec<Task>)> {
+        let tasks = self.load_tasks()?;
+        let summaries: Vec<TaskSummary> = tasks.iter().map(TaskSummary::from).collect();
+        let kept = tasks
+            .into_iter()
+            .zip(&summaries)
+            .filter(|(_, summary)| keep(summary))
+            .map(|(task, _)| task)
+            .collect();
+        Ok((summaries, kept))
+    }
+    /// Returns the amount of tasks. Backends that keep backups of the tasks can count those as
+    /// well.
+    fn count_tasks(&self, include_backups: bool) -> Result<usize>;
+    /// Rebuild the indexes the backend keeps of the tasks. Returns the amount of tasks indexed.
+    fn reindex(&self) -> Result<usize>;
+
+    /// Load the note of the task with the id
+    #[cfg(feature = "note")]
+    fn load_note(&self, task_id: &Uuid) -> Result<Note>;
+    /// Save the note, replacing an existing one of the same task
+    #[cfg(feature = "note")]
+    fn save_note(&self, note: &mut Note) -> Result<()>;
+    /// Delete the note of the task with the id
+    #[cfg(feature = "note")]
+    fn delete_note(&self, task_id: &Uuid) -> Result<()>;
+    /// Returns true if there is a note for the task with the id
+    #[cfg(feature = "note")]
+    fn note_exists(&self, task_id: &Uuid) -> Result<bool>;
+    /// Returns the task ids of all notes
+    #[cfg(feature = "note")]
+    fn note_ids(&self) -> Result<Vec<Uuid>>;
+    /// Load all notes
+    #[cfg(feature = "note")]
+    fn load_notes(&self) -> Result<Vec<Note>>;
+    /// Returns the amount of notes. Backends that keep backups of the notes can count those as
+    /// well.
+    #[cfg(feature = "note")]
+    fn count_notes(&self, include_backups: bool) -> Result<usize>;
+}
+
+/// Open the storage backend of the namespace
+pub fn open_backend(backend: &StorageBackend, settings: &Settings) -> Result<Box<dyn Storage>> {
+    match backend {
+        StorageBackend::Flatfile => Ok(Box::new(flatfile::FlatfileStorage::new(settings))),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(Box::new(
+            sqlite::SqliteStorage::open(&settings.db_pathbuf()?.join("tsk-rs.sqlite"))
+                .with_context(|| "while opening sqlite database")?,
+        )),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => bail!(StorageError::BackendNotAvailable("sqlite".to_string())),
+    }
+}
+
+/// Open the storage backend configured for the namespace
+pub fn open_storage(settings: &Settings) -> Result<Box<dyn Storage>> {
+    open_backend(&settings.data.backend, settings)
+}
+
+/// Rebuild the indexes of the storage configured for the namespace from scratch. Returns the
+/// amount of tasks indexed.
+pub fn reindex(settings: &Settings) -> Result<usize> {
+    open_storage(settings)?
+        .reindex()
+        .with_context(|| "while rebuilding storage indexes")
+}
+
+/// Result of a migration between two storage backends
+pub struct MigrationSummary {
+    /// Amount of tasks copied
+    pub tasks: usize,
+    /// Amount of notes copied
+    pub notes: usize,
+}
+
+/// Copy all tasks and notes of the namespace from one storage backend to another. Entries that
+/// already exist in the target are overwritten and the source is left intact.
+pub fn migrate(
+    from: &StorageBackend,
+    to: &StorageBackend,
+    settings: &Settings,
+) -> Result<MigrationSummary> {
+    if from == to {
+        bail!(StorageError::SameBackend);
+    }
+    let source = open_backend(from, settings).with_context(|| "while opening source storage")?;
+    let target = open_backend(to, settings).with_context(|| "while opening target storage")?;
+
+    let mut summary = MigrationSummary { tasks: 0, notes: 0 };
+    for mut task in source
+        .load_tasks()
+        .with_context(|| "while loading tasks from source storage")?
+    {
+        target
+            .save_task(&mut task)
+            .with_context(|| "while saving task to target storage")?;
+        summary.tasks += 1;
+    }
+
+    #[cfg(feature = "note")]
+    for mut note in source
+        .load_notes()
+        .with_context(|| "while loading notes from source storage")?
+    {
+        target
+            .save_note(&mut note)
+            .with_context(|| "while saving note to target storage")?;
+        summary.notes += 1;
+    }
+
+    Ok(summary)
+}
+
+// eof
@@ -0,0 +1,297 @@
+use std::path::Path;
+
+use color_eyre::eyre::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use super::{Storage, StorageError};
+#[cfg(feature = "note")]
+use crate::note::Note;
+use crate::task::Task;
+
+/// Storage backend where the tasks and notes of the namespace are kept in a single SQLite
+/// database file. Entries are stored as the same YAML documents the flatfile backend writes.
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    /// Open the SQLite database file, creating it and the tables if they do not exist yet
+    pub fn open(db_file: &Path) -> Result<Self> {
+        let connection =
+            Connection::open(db_file).with_context(|| "while opening sqlite database file")?;
+        Self::from_connection(connection)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS tasks (
+                    id TEXT PRIMARY KEY NOT NULL,
+                    document TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS notes (
+                    task_id TEXT PRIMARY KEY NOT NULL,
+                    document TEXT NOT NULL
+                );",
+            )
+            .with_context(|| "while creating sqlite database tables")?;
+        Ok(Self { connection })
+    }
+
+    fn load_document(&self, table: &str, key: &str, id: &Uuid) -> Result<String> {
+        let document: Option<String> = self
+            .connection
+            .query_row(
+                &format!("SELECT document FROM {} WHERE {} = ?1", table, key),
+                params![id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .with_context(|| "while querying sqlite database")?;
+        match document {
+            Some(document) => Ok(document),
+            None => bail!(StorageError::NotFound(id.to_string())),
+        }
+    }
+
+    fn save_document(&self, table: &str, key: &str, id: &Uuid, document: &str) -> Result<()> {
+        self.connection
+            .execute(
+                &format!(
+                    "INSERT INTO {table} ({key}, document) VALUES (?1, ?2)
+                    ON CONFLICT({key}) DO UPDATE SET document = excluded.document",
+                    table = table,
+                    key = key
+                ),
+                params![id.to_string(), document],
+            )
+            .with_context(|| "while writing to sqlite database")?;
+        Ok(())
+    }
+
+    fn delete_document(&self, table: &str, key: &str, id: &Uuid) -> Result<()> {
+        let deleted = self
+            .connection
+            .execute(
+                &format!("DELETE FROM {} WHERE {} = ?1", table, key),
+                params![id.to_string()],
+            )
+            .with_context(|| "while deleting from sqlite database")?;
+        if deleted == 0 {
+            bail!(StorageError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn ids(&self, table: &str, key: &str) -> Result<Vec<Uuid>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "SELECT {key} FROM {table} ORDER BY {key}",
+                table = table,
+                key = key
+            ))
+            .with_context(|| "while querying sqlite database")?;
+        let mut ids: Vec<Uuid> = vec![];
+        for id in statement.query_map([], |row| row.get::<_, String>(0))? {
+            ids.push(Uuid::parse_str(&id?).with_context(|| "while parsing id from database")?);
+        }
+        Ok(ids)
+    }
+
+    fn documents(&self, table: &str) -> Result<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT document FROM {}", table))
+            .with_context(|| "while querying sqlite database")?;
+        let mut documents: Vec<String> = vec![];
+        for document in statement.query_map([], |row| row.get::<_, String>(0))? {
+            documents.push(document?);
+        }
+        Ok(documents)
+    }
+
+    fn count(&self, table: &str) -> Result<usize> {
+        let count: i64 = self
+            .connection
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .with_context(|| "while querying sqlite database")?;
+        Ok(count as usize)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_task(&self, id: &Uuid) -> Result<Task> {
+        Task::from_yaml_string(&self.load_document("tasks", "id", id)?)
+            .with_context(|| "while deserializing task from database")
+    }
+
+    fn save_task(&self, task: &mut Task) -> Result<()> {
+        let document = task
+            .to_yaml_string()
+            .with_context(|| "while serializing task struct to yaml")?;
+        self.save_document("tasks", "id", &task.id, &document)
+    }
+
+    fn delete_task(&self, id: &Uuid) -> Result<()> {
+        self.delete_document("tasks", "id", id)
+    }
+
+    fn task_exists(&self, id: &Uuid) -> Result<bool> {
+        match self.load_document("tasks", "id", id) {
+            Ok(_) => Ok(true),
+            Err(error) if error.downcast_ref::<StorageError>().is_some() => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn task_ids(&self) -> Result<Vec<Uuid>> {
+        self.ids("tasks", "id")
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>> {
+        let mut tasks: Vec<Task> = vec![];
+        for document in self.documents("tasks")? {
+            tasks.push(
+                Task::from_yaml_string(&document)
+                    .with_context(|| "while deserializing task from database")?,
+            );
+        }
+        Ok(tasks)
+    }
+
+    fn count_tasks(&self, _include_backups: bool) -> Result<usize> {
+        // database does not keep backups
+        self.count("tasks")
+    }
+
+    fn reindex(&self) -> Result<usize> {
+        self.connection
+            .execute_batch("REINDEX;")
+            .with_context(|| "while rebuilding sqlite database indexes")?;
+        self.count("tasks")
+    }
+
+    #[cfg(feature = "note")]
+    fn load_note(&self, task_id: &Uuid) -> Result<Note> {
+        Note::from_yaml_string(&self.load_document("notes", "task_id", task_id)?)
+            .with_context(|| "while deserializing note from database")
+    }
+
+    #[cfg(feature = "note")]
+    fn save_note(&self, note: &mut Note) -> Result<()> {
+        let document = note
+            .to_yaml_string()
+            .with_context(|| "while serializing note struct to yaml")?;
+        self.save_document("notes", "task_id", &note.task_id, &document)
+    }
+
+    #[cfg(feature = "note")]
+    fn delete_note(&self, task_id: &Uuid) -> Result<()> {
+        self.delete_document("notes", "task_id", task_id)
+    }
+
+    #[cfg(feature = "note")]
+    fn note_exists(&self, task_id: &Uuid) -> Result<bool> {
+        match self.load_document("notes", "task_id", task_id) {
+            Ok(_) => Ok(true),
+            Err(error) if error.downcast_ref::<StorageError>().is_some() => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    #[cfg(feature = "note")]
+    fn note_ids(&self) -> Result<Vec<Uuid>> {
+        self.ids("notes", "task_id")
+    }
+
+    #[cfg(feature = "note")]
+    fn load_notes(&self) -> Result<Vec<Note>> {
+        let mut notes: Vec<Note> = vec![];
+        for document in self.documents("notes")? {
+            notes.push(
+                Note::from_yaml_string(&document)
+                    .with_context(|| "while deserializing note from database")?,
+            );
+        }
+        Ok(notes)
+    }
+
+    #[cfg(feature = "note")]
+    fn count_notes(&self, _include_backups: bool) -> Result<usize> {
+        // database does not keep backups
+        self.count("notes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> SqliteStorage {
+        SqliteStorage::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn task_roundtrip() {
+        let storage = storage();
+        let mut task =
+            Task::from_task_descriptor(&"write report @work #urgent".to_string()).unwrap();
+        storage.save_task(&mut task).unwrap();
+
+        let loaded = storage.load_task(&task.id).unwrap();
+        assert_eq!(loaded.description, "write report");
+        assert_eq!(loaded.project, Some("work".to_string()));
+        assert!(storage.task_exists(&task.id).unwrap());
+        assert_eq!(storage.task_ids().unwrap(), vec![task.id]);
+
+        // saving again replaces the task
+        task.description = "write the report".to_string();
+        storage.save_task(&mut task).unwrap();
+        assert_eq!(storage.count_tasks(true).unwrap(), 1);
+        assert_eq!(
+            storage.load_tasks().unwrap()[0].description,
+            "write the report"
+        );
+    }
+
+    #[test]
+    fn delete_task() {
+        let storage = storage();
+        let mut task = Task::new("short lived".to_string()).unwrap();
+        storage.save_task(&mut task).unwrap();
+        storage.delete_task(&task.id).unwrap();
+
+        assert!(!storage.task_exists(&task.id).unwrap());
+        assert_eq!(
+            storage
+                .load_task(&task.id)
+                .unwrap_err()
+                .downcast::<StorageError>()
+                .unwrap(),
+            StorageError::NotFound(task.id.to_string())
+        );
+    }
+
+    #[cfg(feature = "note")]
+    #[test]
+    fn note_roundtrip() {
+        let storage = storage();
+        let task = Task::new("with a note".to_string()).unwrap();
+        let mut note = Note::new(&task.id);
+        note.markdown = Some("- [ ] first".to_string());
+        storage.save_note(&mut note).unwrap();
+
+        assert_eq!(
+            storage.load_note(&task.id).unwrap().markdown,
+            Some("- [ ] first".to_string())
+        );
+        assert_eq!(storage.note_ids().unwrap(), vec![task.id]);
+        assert_eq!(storage.count_notes(false).unwrap(), 1);
+    }
+}
+
+// eof
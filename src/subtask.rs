@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local};
+use color_eyre::eyre::{Context, Result};
+use uuid::Uuid;
+
+use crate::{archive::list_tasks_with_archive, settings::Settings, task::Task};
+
+/// Task along with its subtasks
+#[derive(Debug, Clone)]
+pub struct TaskNode {
+    /// The task itself
+    pub task: Task,
+    /// Subtasks of the task, each of them can have subtasks of its own
+    pub subtasks: Vec<TaskNode>,
+}
+
+impl TaskNode {
+    /// Number of completed subtasks and number of all subtasks, counted through the whole tree
+    /// below the task
+    pub fn progress(&self) -> (usize, usize) {
+        self.subtasks.iter().fold((0, 0), |(done, total), subtask| {
+            let (subtask_done, subtask_total) = subtask.progress();
+            (
+                done + subtask_done + usize::from(subtask.task.done),
+                total + subtask_total + 1,
+            )
+        })
+    }
+
+    /// Seconds tracked to the task and all of its subtasks. Time tracking that is still running
+    /// is counted up to now.
+    pub fn tracked_seconds(&self, now: DateTime<Local>) -> i64 {
+        let own: i64 = self
+            .task
+            .timetracker
+            .iter()
+            .flatten()
+            .map(|track| (track.end_time.unwrap_or(now) - track.start_time).num_seconds())
+            .sum();
+        own + self
+            .subtasks
+            .iter()
+            .map(|subtask| subtask.tracked_seconds(now))
+            .sum::<i64>()
+    }
+
+    /// Find the task from this tree
+    pub fn find(&self, id: &Uuid) -> Option<&TaskNode> {
+        if self.task.id == *id {
+            return Some(self);
+        }
+        self.subtasks.iter().find_map(|subtask| subtask.find(id))
+    }
+}
+
+fn build_node(task: Task, children: &mut HashMap<Uuid, Vec<Task>>) -> TaskNode {
+    let subtasks = children
+        .remove(&task.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|subtask| build_node(subtask, children))
+        .collect();
+    TaskNode { task, subtasks }
+}
+
+/// Arrange the tasks to trees by their parents. Tasks whose parent is not among the tasks are
+/// the roots of the trees. The order of the tasks is kept among siblings.
+pub fn task_trees(tasks: Vec<Task>) -> Vec<TaskNode> {
+    let ids: HashSet<Uuid> = tasks.iter().map(|task| task.id).collect();
+    let mut children: HashMap<Uuid, Vec<Task>> = HashMap::new();
+    let mut roots: Vec<Task> = vec![];
+    for task in tasks {
+        match task.parent {
+            Some(parent) if ids.contains(&parent) => children.entry(parent).or_default().push(task),
+            _ => roots.push(task),
+        }
+    }
+
+    let mut trees: Vec<TaskNode> = roots
+        .into_iter()
+        .map(|root| build_node(root, &mut children))
+        .collect();
+    // tasks left over are parents of each other, saving a task refuses such cycles but files
+    // can be edited by hand
+    while let Some(parent) = children.keys().next().copied() {
+        for task in children.remove(&parent).unwrap_or_default() {
+            trees.push(build_node(task, &mut children));
+        }
+    }
+    trees
+}
+
+/// Load all tasks of the namespace, completed and archived ones included, arranged to trees by
+/// their parents and sorted by their score
+pub fn load_task_trees(settings: &Settings) -> Result<Vec<TaskNode>> {
+    let tasks =
+        list_tasks_with_archive(&None, &true, settings).with_context(|| "while loading tasks")?;
+    Ok(task_trees(tasks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TimeTrack;
+    use chrono::Duration;
+
+    /// Release with backend (having api as its subtask) and docs as its subtasks, along with an
+    /// unrelated task. Api is done, an hour is tracked to the release and docs is being tracked
+    /// since half an hour ago.
+    fn tasks(now: DateTime<Local>) -> Vec<Task> {
+        let mut root = Task::new("release".to_string()).unwrap();
+        let mut backend = Task::new("backend".to_string()).unwrap();
+        let mut api = Task::new("api".to_string()).unwrap();
+        let mut docs = Task::new("docs".to_string()).unwrap();
+        let other = Task::new("unrelated".to_string()).unwrap();
+        backend.parent = Some(root.id);
+        api.parent = Some(backend.id);
+        docs.parent = Some(root.id);
+        api.done = true;
+        root.timetracker = Some(vec![TimeTrack {
+            start_time: now - Duration::hours(1),
+            end_time: Some(now),
+            annotation: None,
+        }]);
+        docs.timetracker = Some(vec![TimeTrack {
+            start_time: now - Duration::minutes(30),
+            end_time: None,
+            annotation: None,
+        }]);
+        vec![api, root, other, backend, docs]
+    }
+
+    fn descriptions(nodes: &[TaskNode]) -> Vec<&str> {
+        nodes
+            .iter()
+            .map(|node| node.task.description.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn tasks_without_parent_are_roots() {
+        let trees = task_trees(tasks(Local::now()));
+
+        assert_eq!(descriptions(&trees), vec!["release", "unrelated"]);
+    }
+
+    #[test]
+    fn subtasks_keep_their_order() {
+        let trees = task_trees(tasks(Local::now()));
+
+        assert_eq!(descriptions(&trees[0].subtasks), vec!["backend", "docs"]);
+        assert_eq!(descriptions(&trees[0].subtasks[0].subtasks), vec!["api"]);
+    }
+
+    #[test]
+    fn progress_counts_whole_tree() {
+        let trees = task_trees(tasks(Local::now()));
+
+        assert_eq!(trees[0].progress(), (1, 3));
+        assert_eq!(trees[0].subtasks[0].progress(), (1, 1));
+        assert_eq!(trees[0].subtasks[0].subtasks[0].progress(), (0, 0));
+    }
+
+    #[test]
+    fn tracked_seconds_include_subtasks_and_running_time() {
+        let now = Local::now();
+        let trees = task_trees(tasks(now));
+
+        assert_eq!(trees[0].tracked_seconds(now), 90 * 60);
+    }
+
+    #[test]
+    fn find_searches_only_the_tree() {
+        let all = tasks(Local::now());
+        let api = all[0].id;
+        let trees = task_trees(all);
+
+        assert_eq!(trees[0].find(&api).unwrap().task.description, "api");
+        assert!(trees[1].find(&api).is_none());
+    }
+
+    #[test]
+    fn parents_of_each_other_are_kept() {
+        let mut first = Task::new("first".to_string()).unwrap();
+        let mut second = Task::new("second".to_string()).unwrap();
+        first.parent = Some(second.id);
+        second.parent = Some(first.id);
+
+        let trees = task_trees(vec![first, second]);
+
+        assert_eq!(trees.len(), 1);
+        assert_eq!(trees[0].progress(), (0, 1));
+    }
+}
+
+// eof
@@ -2,12 +2,16 @@ use std::collections::HashMap;
 
 use color_eyre::eyre::{Context, Result};
 
-use crate::{settings::Settings, task::list_tasks};
+use crate::{archive::archived_summaries, settings::Settings, storage::open_storage};
 
-/// scan all active and done tasks to find tags in use
+/// scan all active, done and archived tasks to find tags in use
 pub fn scan_tags(settings: &Settings) -> Result<HashMap<String, usize>> {
-    let tasks = list_tasks(&None, &true, settings)
+    let mut tasks = open_storage(settings)?
+        .task_summaries()
         .with_context(|| "while scanning through all tasks")?;
+    tasks.extend(
+        archived_summaries(settings).with_context(|| "while scanning through archived tasks")?,
+    );
 
     let mut collected_tags: HashMap<String, usize> = HashMap::new();
 
@@ -22,4 +26,4 @@ pub fn scan_tags(settings: &Settings) -> Result<HashMap<String, usize>> {
     Ok(collected_tags)
 }
 
-// eof
\ No newline at end of file
+// eof
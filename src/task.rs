@@ -1,19 +1,33 @@
 use crate::{
+    active::check_single_active,
+    archive::archived_summaries,
+    id::{find_ids_by_prefix, resolve_candidates},
+    journal::{record_change, JournalSubject},
     metadata::MetadataKeyValuePair,
-    parser::task_lexicon::{parse_task, Expression},
-    settings::Settings,
+    parser::{
+        due_date::local_due_date,
+        filter_lexicon::Filter,
+        task_lexicon::{parse_task, Expression},
+    },
+    project::project_hierarchy,
+    recurrence::Recurrence,
+    settings::{ScoreSettings, Settings},
+    storage::{
+        index::{TaskIndex, TaskSummary},
+        open_storage, Storage,
+    },
 };
 use chrono::{DateTime, Duration, Local, NaiveDateTime};
 use color_eyre::eyre::{bail, Context, Result};
-use file_lock::{FileLock, FileOptions};
-use glob::glob;
 use serde::{Deserialize, Serialize};
 use simple_file_rotation::FileRotation;
 use std::{
-    collections::BTreeMap,
-    fs::File,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fs::{rename, File},
     io::{Read, Write},
     path::PathBuf,
+    process, slice,
     str::FromStr,
 };
 use strum::{EnumString, IntoStaticStr};
@@ -25,7 +39,10 @@ use crate::notify::DatabaseFileType;
 
 /// Available priorities for a task
 /// Each priority level has an different effect to the overall urgency level calculations
-#[derive(EnumString, IntoStaticStr, clap::ValueEnum, Clone, Eq, PartialEq, Debug)]
+#[derive(
+    EnumString, IntoStaticStr, clap::ValueEnum, Clone, Eq, PartialEq, PartialOrd, Ord, Debug,
+)]
+#[strum(ascii_case_insensitive)]
 pub enum TaskPriority {
     /// Low priority
     Low,
@@ -49,6 +66,12 @@ pub enum TaskError {
     /// Multiple due dates were defined in the task descriptor. Not allowed.
     #[error("only one due date identifier allowed")]
     MultipleDuedatesNotAllowed,
+    /// Multiple recurrence rules were defined in the task descriptor. Not allowed.
+    #[error("only one recurrence identifier allowed")]
+    MultipleRecurrencesNotAllowed,
+    /// Multiple estimates were defined in the task descriptor. Not allowed.
+    #[error("only one estimate identifier allowed")]
+    MultipleEstimatesNotAllowed,
     /// Multiple metadata pairs with same key was defined in the task descriptor. Not allowed.
     #[error("only one instance of metadata key `{0}` is allowed")]
     IdenticalMetadataKeyNotAllowed(String),
@@ -67,6 +90,18 @@ pub enum TaskError {
     /// Task descriptor was empty
     #[error("task descriptor cant be an empty string")]
     TaskDescriptorEmpty,
+    /// Dependencies of the task would form a cycle and the task could never be started
+    #[error("dependency cycle detected through task `{0}`")]
+    DependencyCycle(String),
+    /// Task would become a subtask of itself or of one of its own subtasks
+    #[error("task `{0}` can not be a subtask of itself or of its own subtasks")]
+    ParentCycle(String),
+    /// Task has dependencies that are not completed yet
+    #[error("task is blocked by unfinished dependencies")]
+    TaskBlocked,
+    /// Another task is being tracked and only one running task is allowed
+    #[error("task `{0}` is already running. stop it first or use switch")]
+    AnotherTaskRunning(String),
     /// Conversion error from notify event kind. Needs to be Task for Task.
     #[cfg(feature = "notify")]
     #[error("notifier result kind is not for a Task")]
@@ -74,7 +109,7 @@ pub enum TaskError {
 }
 
 /// Time track entry holds information about a span of time while the task was/is being worked on.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TimeTrack {
     /// Local timestamp for the moment in time when the time tracking was started
     pub start_time: DateTime<Local>,
@@ -103,6 +138,15 @@ pub struct Task {
     pub metadata: BTreeMap<String, String>,
     /// List of optional [TimeTrack] entries.
     pub timetracker: Option<Vec<TimeTrack>>,
+    /// Ids of the tasks (if any) that have to be completed before this task can be started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends: Option<Vec<Uuid>>,
+    /// Recurrence rule (if any). When the task is completed a new instance of it is created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// Id of the task (if any) this task is a subtask of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Uuid>,
 }
 
 impl Task {
@@ -145,6 +189,97 @@ impl Task {
         false
     }
 
+    /// Returns the numeric short id of the task if one has been assigned
+    pub fn short_id(&self) -> Option<usize> {
+        self.metadata
+            .get("tsk-rs-task-short-id")
+            .and_then(|short_id| short_id.parse::<usize>().ok())
+    }
+
+    /// Returns the id of the recurrence template this task was created from (if any)
+    pub fn recurrence_parent(&self) -> Option<Uuid> {
+        self.metadata
+            .get("tsk-rs-task-recur-parent")
+            .and_then(|parent| Uuid::parse_str(parent).ok())
+    }
+
+    /// Create the next instance of a recurring task. The instance gets the due date shifted by
+    /// the recurrence rule and a link back to the template of the chain. Returns None if the task
+    /// does not recur.
+    pub fn next_recurrence(&self, now: DateTime<Local>) -> Result<Option<Task>> {
+        let recurrence = match &self.recurrence {
+            Some(recurrence) => recurrence,
+            None => return Ok(None),
+        };
+
+        let previous_due = match self.metadata.get("tsk-rs-task-due-time") {
+            Some(duedate_str) => DateTime::from_str(duedate_str)
+                .with_context(|| "while parsing due date string as a datetime")?,
+            None => now,
+        };
+        // monthly instances are counted from the due time of the first instance of the chain
+        let anchor = match self.metadata.get("tsk-rs-task-recur-anchor") {
+            Some(anchor_str) => DateTime::from_str(anchor_str)
+                .with_context(|| "while parsing recurrence anchor string as a datetime")?,
+            None => previous_due,
+        };
+
+        let mut metadata: BTreeMap<String, String> = self
+            .metadata
+            .iter()
+            .filter(|(key, _)| key.starts_with("x-") || *key == "tsk-rs-task-priority")
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        metadata.insert(
+            String::from("tsk-rs-task-create-time"),
+            now.to_rfc3339(),
+        );
+        metadata.insert(
+            String::from("tsk-rs-task-due-time"),
+            recurrence
+                .next_due(anchor, previous_due, now)
+                .with_context(|| "while calculating the due date of the next instance")?
+                .to_rfc3339(),
+        );
+        metadata.insert(
+            String::from("tsk-rs-task-recur-anchor"),
+            anchor.to_rfc3339(),
+        );
+        metadata.insert(
+            String::from("tsk-rs-task-recur-parent"),
+            self.recurrence_parent().unwrap_or(self.id).to_string(),
+        );
+
+        let tags: Vec<String> = self
+            .tags
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|tag| !["start", "next", "hold"].contains(&tag.as_str()))
+            .collect();
+
+        let mut task = Task {
+            id: Uuid::new_v4(),
+            description: self.description.clone(),
+            done: false,
+            project: self.project.clone(),
+            tags: if tags.is_empty() { None } else { Some(tags) },
+            metadata,
+            timetracker: None,
+            depends: None,
+            recurrence: Some(recurrence.clone()),
+            parent: self.parent,
+        };
+
+        // Calculate the score into metadata
+        let score = task
+            .score(&ScoreSettings::default())
+            .with_context(|| "error during task score insert into metadata")?;
+        task.metadata.insert("tsk-rs-task-score".to_owned(), format!("{}", score));
+
+        Ok(Some(task))
+    }
+
     /// Returns true if the task is running
     pub fn is_running(&self) -> bool {
         if self.timetracker.is_none() {
@@ -179,12 +314,11 @@ impl Task {
         }
         if !self.is_running() {
             let timestamp = chrono::offset::Local::now();
-            let mut timetracks: Vec<TimeTrack>;
-            if self.timetracker.is_some() {
-                timetracks = self.timetracker.as_ref().unwrap().to_vec();
+            let mut timetracks: Vec<TimeTrack> = if let Some(timetracker) = &self.timetracker {
+                timetracker.to_vec()
             } else {
-                timetracks = vec![];
-            }
+                vec![]
+            };
             tt = TimeTrack {
                 start_time: timestamp,
                 end_time: None,
@@ -236,6 +370,74 @@ impl Task {
         Some(runtime)
     }
 
+    /// Estimated effort of the task (if any)
+    pub fn estimate(&self) -> Option<Duration> {
+        self.metadata
+            .get("tsk-rs-task-estimate")
+            .and_then(|seconds| seconds.parse::<i64>().ok())
+            .map(Duration::seconds)
+    }
+
+    /// Set or remove the estimated effort of the task. Returns true if the estimate changed.
+    pub fn set_estimate(&mut self, estimate: &Option<Duration>) -> bool {
+        let key = "tsk-rs-task-estimate".to_string();
+        let old_estimate = match estimate {
+            Some(estimate) => self
+                .metadata
+                .insert(key, estimate.num_seconds().to_string()),
+            None => self.metadata.remove(&key),
+        };
+        old_estimate != estimate.map(|estimate| estimate.num_seconds().to_string())
+    }
+
+    /// Total time tracked to the task. Time tracking that is still running is counted up to now.
+    pub fn tracked_time(&self, now: DateTime<Local>) -> Duration {
+        self.timetracker
+            .iter()
+            .flatten()
+            .map(|track| track.end_time.unwrap_or(now) - track.start_time)
+            .sum()
+    }
+
+    /// Effort left of the estimate after the tracked time, negative if more time has been
+    /// tracked than estimated. None if the task has no estimate.
+    pub fn remaining_effort(&self, now: DateTime<Local>) -> Option<Duration> {
+        self.estimate()
+            .map(|estimate| estimate - self.tracked_time(now))
+    }
+
+    /// Returns true if more time has been tracked to the task than was estimated
+    pub fn is_over_estimate(&self, now: DateTime<Local>) -> bool {
+        self.remaining_effort(now)
+            .is_some_and(|remaining| remaining < Duration::zero())
+    }
+
+    /// Billable flag of the task, none if the task follows the configured default
+    pub fn billable(&self) -> Option<bool> {
+        self.metadata
+            .get("tsk-rs-task-billable")
+            .and_then(|billable| billable.parse::<bool>().ok())
+    }
+
+    /// Flag the task to be billable or non-billable, or make it follow the configured default.
+    /// Returns true if the flag changed.
+    pub fn set_billable(&mut self, billable: &Option<bool>) -> bool {
+        let key = "tsk-rs-task-billable".to_string();
+        let old_billable = match billable {
+            Some(billable) => self.metadata.insert(key, billable.to_string()),
+            None => self.metadata.remove(&key),
+        };
+        old_billable != billable.map(|billable| billable.to_string())
+    }
+
+    /// Number of pomodoros completed while focusing on the task
+    pub fn pomodoros(&self) -> usize {
+        self.metadata
+            .get("tsk-rs-task-pomodoros")
+            .and_then(|pomodoros| pomodoros.parse::<usize>().ok())
+            .unwrap_or(0)
+    }
+
     /// Load task YAML formatted file from the disk
     pub fn load_yaml_file_from(task_pathbuf: &PathBuf) -> Result<Self> {
         let mut file =
@@ -251,39 +453,36 @@ impl Task {
     pub fn save_yaml_file_to(&mut self, task_pathbuf: &PathBuf, rotate: &usize) -> Result<()> {
         // rotate existing file with same name if present
         if task_pathbuf.is_file() && rotate > &0 {
-            FileRotation::new(&task_pathbuf)
+            FileRotation::new(task_pathbuf)
                 .max_old_files(*rotate)
                 .file_extension("yaml".to_string())
                 .rotate()
                 .with_context(|| "while rotating task data file backups")?;
         }
-        // save file by locking
-        let should_we_block = true;
-        let options = FileOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .append(false);
+        // write to a temporary file first and move it in place, so readers never see a
+        // partially written task
+        let mut temp_pathbuf = task_pathbuf.clone();
+        temp_pathbuf.set_extension(format!("{}.tmp", process::id()));
         {
-            let mut filelock = FileLock::lock(task_pathbuf, should_we_block, options)
-                .with_context(|| "while opening new task yaml file")?;
-            filelock
-                .file
-                .write_all(
-                    self.to_yaml_string()
-                        .with_context(|| "while serializing task struct to yaml")?
-                        .as_bytes(),
-                )
-                .with_context(|| "while writing to task yaml file")?;
-            filelock
-                .file
-                .flush()
+            let mut file =
+                File::create(&temp_pathbuf).with_context(|| "while opening new task yaml file")?;
+            file.write_all(
+                self.to_yaml_string()
+                    .with_context(|| "while serializing task struct to yaml")?
+                    .as_bytes(),
+            )
+            .with_context(|| "while writing to task yaml file")?;
+            file.flush()
                 .with_context(|| "while flushing os caches to disk")?;
-            filelock
-                .file
-                .sync_all()
+            file.sync_all()
                 .with_context(|| "while syncing filesystem metadata")?;
         }
+        rename(&temp_pathbuf, task_pathbuf).with_context(|| "while replacing task yaml file")?;
+
+        // task files of the database are listed through the index of their directory
+        if task_pathbuf.file_name() == Some(OsStr::new(&format!("{}.yaml", self.id))) {
+            TaskIndex::record(self, task_pathbuf).with_context(|| "while updating task index")?;
+        }
 
         Ok(())
     }
@@ -323,9 +522,14 @@ impl Task {
             tags: None,
             metadata,
             timetracker: None,
+            depends: None,
+            recurrence: None,
+            parent: None,
         };
         // Calculate the score into metadata
-        let score = task.score().with_context(|| "error during task score insert into metadata")?;
+        let score = task
+            .score(&ScoreSettings::default())
+            .with_context(|| "error during task score insert into metadata")?;
         task.metadata.insert("tsk-rs-task-score".to_owned(), format!("{}", score));
         
         Ok(task)
@@ -333,27 +537,34 @@ impl Task {
 
     /// Serialize the task as YAML string
     pub fn to_yaml_string(&mut self) -> Result<String> {
-        // Calculate the score into metadata
-        let score = self.score().with_context(|| "error during task score refresh into metadata")?;
-        self.metadata.insert("tsk-rs-task-score".to_owned(), format!("{}", score));
-
         serde_yaml::to_string(self).with_context(|| "unable to serialize task struct as yaml")
     }
 
     /// Deserialize the task from YAML string
     pub fn from_yaml_string(input: &str) -> Result<Self> {
-        let mut task: Task = serde_yaml::from_str(input)
+        let task: Task = serde_yaml::from_str(input)
             .with_context(|| "unable to deserialize yaml into task struct")?;
-        // Recalculate the score into metadata
-        let score = task.score().with_context(|| "error during task score refresh into metadata")?;
-        task.metadata.insert("tsk-rs-task-score".to_owned(), format!("{}", score));
         Ok(task)
     }
 
     /// Create a new task from task descriptor string
     ///
     /// Example: `This is a prj:Project task that has to be done. due:2022-08-01T16:00:00 prio:low meta:x-fuu=bar tag:some tag:tags tag:can tag:be tag:added`
+    ///
+    /// Dependencies need to be given as full task ids. Use [Task::from_task_descriptor_with] to
+    /// resolve short ids and id prefixes.
     pub fn from_task_descriptor(input: &String) -> Result<Self> {
+        Task::from_task_descriptor_with(input, |id| {
+            Uuid::parse_str(id).with_context(|| "while parsing dependency task id")
+        })
+    }
+
+    /// Create a new task from task descriptor string and resolve the ids of the dependencies with
+    /// a resolver function e.g [resolve_task_id]
+    pub fn from_task_descriptor_with<F>(input: &String, resolve_id: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Result<Uuid>,
+    {
         if input.is_empty() {
             bail!(TaskError::TaskDescriptorEmpty);
         }
@@ -364,6 +575,8 @@ impl Task {
         let mut tags: Vec<String> = vec![];
         let mut metadata: BTreeMap<String, String> = BTreeMap::new();
         let mut project: String = String::new();
+        let mut depends: Vec<Uuid> = vec![];
+        let mut recurrence: Option<Recurrence> = None;
 
         for expr in expressions {
             match expr {
@@ -410,13 +623,34 @@ impl Task {
                     metadata.insert(key, prio_str.to_string());
                 }
                 Expression::Duedate(datetime) => {
-                    let value = datetime.and_local_timezone(Local).unwrap().to_rfc3339();
+                    let value = local_due_date(datetime)?.to_rfc3339();
                     let key = "tsk-rs-task-due-time".to_string();
                     if metadata.contains_key(&key) {
                         bail!(TaskError::MultipleDuedatesNotAllowed)
                     }
                     metadata.insert(key, value);
                 }
+                Expression::Dependency(id) => {
+                    let dependency = resolve_id(&id)
+                        .with_context(|| "while resolving dependency in task descriptor")?;
+                    if !depends.contains(&dependency) {
+                        // add the dependency only if it is not already added (drop duplicates silently)
+                        depends.push(dependency);
+                    }
+                }
+                Expression::Recurrence(rule) => {
+                    if recurrence.is_some() {
+                        bail!(TaskError::MultipleRecurrencesNotAllowed)
+                    }
+                    recurrence = Some(rule);
+                }
+                Expression::Estimate(estimate) => {
+                    let key = "tsk-rs-task-estimate".to_string();
+                    if metadata.contains_key(&key) {
+                        bail!(TaskError::MultipleEstimatesNotAllowed)
+                    }
+                    metadata.insert(key, estimate.num_seconds().to_string());
+                }
             };
         }
 
@@ -428,6 +662,10 @@ impl Task {
         if !project.is_empty() {
             ret_project = Some(project);
         }
+        let mut ret_depends = None;
+        if !depends.is_empty() {
+            ret_depends = Some(depends);
+        }
 
         let timestamp = chrono::offset::Local::now();
         metadata.insert(
@@ -443,94 +681,137 @@ impl Task {
             metadata,
             project: ret_project,
             timetracker: None,
+            depends: ret_depends,
+            recurrence,
+            parent: None,
         };
 
         // Calculate the score into metadata
-        let score = task.score().with_context(|| "error during task score insert into metadata")?;
+        let score = task
+            .score(&ScoreSettings::default())
+            .with_context(|| "error during task score insert into metadata")?;
         task.metadata.insert("tsk-rs-task-score".to_owned(), format!("{}", score));
 
         Ok(task)
     }
 
     /// Calculate the score for the task than can be used to compare urgencies of seperate tasks
-    /// and giving a priority.
-    fn score(&self) -> Result<usize> {
-        // the more "fleshed out" the task is the more higher score it should get
-        let mut score: usize = 0;
+    /// and giving a priority, see [Task::score_terms]. New tasks are scored with the default
+    /// coefficients until they are saved with [save_task], which scores them as configured.
+    fn score(&self, settings: &ScoreSettings) -> Result<usize> {
+        Ok(total_score(
+            &self.score_terms(settings, chrono::offset::Local::now())?,
+        ))
+    }
 
-        if self.project.is_some() {
-            // project is valued at 3 points
-            score += 3;
+    /// Break down the score of the task to the terms contributing to it. Terms that depend on
+    /// other tasks (dependencies) are not included, see [dependency_score_terms].
+    pub fn score_terms(
+        &self,
+        settings: &ScoreSettings,
+        now: DateTime<Local>,
+    ) -> Result<Vec<ScoreTerm>> {
+        // the more "fleshed out" the task is the more higher score it should get
+        let mut terms: Vec<ScoreTerm> = vec![];
+
+        if let Some(project) = &self.project {
+            terms.push(ScoreTerm::new("project", settings.project));
+            // the boost of the most specific level of the project applies to its subprojects
+            let boost = project_hierarchy(project)
+                .into_iter()
+                .rev()
+                .find_map(|level| {
+                    settings
+                        .projects
+                        .iter()
+                        .find(|(boosted, _)| boosted.eq_ignore_ascii_case(&level))
+                        .map(|(_, points)| (level, *points))
+                });
+            if let Some((level, points)) = boost {
+                terms.push(ScoreTerm::new(&format!("project {}", level), points));
+            }
         }
 
-        if self.tags.is_some() {
-            // each hashtag is valued at two (2) points
-            score += self.tags.as_ref().unwrap().len() * 2;
+        if let Some(tags) = &self.tags {
+            terms.push(ScoreTerm::new(
+                &format!("{} tag(s)", tags.len()),
+                tags.len() as f64 * settings.tag,
+            ));
+            for tag in tags {
+                for (boosted, points) in &settings.tags {
+                    if boosted.eq_ignore_ascii_case(tag) {
+                        terms.push(ScoreTerm::new(&format!("tag {}", tag), *points));
+                    }
+                }
+            }
         }
 
         if self.is_running() {
-            // if task is running it gains 15 points
-            score += 15;
+            terms.push(ScoreTerm::new("running", settings.running));
         }
 
-        if self.timetracker.is_some() {
-            // each timetracker entry grants 1 point
-            score += self.timetracker.as_ref().unwrap().len();
+        if let Some(timetracker) = &self.timetracker {
+            terms.push(ScoreTerm::new(
+                &format!("{} time tracking entries", timetracker.len()),
+                timetracker.len() as f64 * settings.timetrack,
+            ));
         }
 
         if let Some(priority) = self.metadata.get("tsk-rs-task-priority") {
             // priorities have different weights in the score
-            match TaskPriority::from_str(priority)
+            let points = match TaskPriority::from_str(priority)
                 .with_context(|| "while converting task priority to enum")?
             {
-                TaskPriority::Low => score += 1,
-                TaskPriority::Medium => score += 3,
-                TaskPriority::High => score += 8,
-                TaskPriority::Critical => score += 13,
-            }
+                TaskPriority::Low => settings.prioritylow,
+                TaskPriority::Medium => settings.prioritymedium,
+                TaskPriority::High => settings.priorityhigh,
+                TaskPriority::Critical => settings.prioritycritical,
+            };
+            terms.push(ScoreTerm::new(&format!("priority {}", priority), points));
         }
 
-        let timestamp = chrono::offset::Local::now();
-
         if let Some(duedate_str) = self.metadata.get("tsk-rs-task-due-time") {
             // if due date is present then WHEN has a different score
-            let duedate = DateTime::from_str(duedate_str)
+            let duedate: DateTime<Local> = DateTime::from_str(duedate_str)
                 .with_context(|| "while parsing due date string as a datetime")?;
-            let diff = duedate - timestamp;
+            let days = (duedate - now).num_days();
 
-            match diff.num_days() {
-                n if n < 0 => score += 10,
-                0..=2 => score += 7,
-                3..=5 => score += 3,
-                _ => score += 1,
+            let term = if duedate < now {
+                ScoreTerm::new("overdue", settings.overdue)
+            } else {
+                match settings.duecurve.iter().find(|point| days <= point.days) {
+                    Some(point) => {
+                        ScoreTerm::new(&format!("due within {} day(s)", point.days), point.points)
+                    }
+                    None => ScoreTerm::new("due later", settings.duelater),
+                }
             };
+            terms.push(term);
         }
 
-        let create_date = DateTime::from_str(self.metadata.get("tsk-rs-task-create-time").unwrap())
-            .with_context(|| "while reading task creation date")?;
-        let create_diff = timestamp - create_date;
-        // as the task gets older each day gives 0.14285715 worth of weight to score. this is rounded when
-        //  returned as usize, but this means that every seven days grants one point
-        score += (create_diff.num_days() as f32 * 0.142_857_15) as usize;
+        let create_date: DateTime<Local> =
+            DateTime::from_str(self.metadata.get("tsk-rs-task-create-time").unwrap())
+                .with_context(|| "while reading task creation date")?;
+        let age = (now - create_date).num_days();
+        terms.push(ScoreTerm::new(
+            &format!("age {} day(s)", age),
+            age as f64 * settings.age,
+        ));
 
-        // special tags (applied last) reduce the or add to the score
+        // special tags reduce or add to the score
         if let Some(tags) = &self.tags {
             if tags.contains(&"next".to_string()) {
                 // just like in taskwarrior special tag "next" gives a huge boost
-                score += 100;
+                terms.push(ScoreTerm::new("special tag next", settings.next));
             }
 
             if tags.contains(&"hold".to_string()) {
-                // hold will reduce score
-                if score >= 20 {
-                    score -= 20;
-                } else {
-                    score = 0;
-                }
+                terms.push(ScoreTerm::new("special tag hold", -settings.hold));
             }
         }
 
-        Ok(score)
+        terms.retain(|term| term.points != 0.0);
+        Ok(terms)
     }
 
     /// Remove task characteristics
@@ -541,6 +822,7 @@ impl Task {
         tags: &Option<Vec<String>>,
         project: &bool,
         metadata: &Option<Vec<String>>,
+        depends: &Option<Vec<Uuid>>,
     ) -> bool {
         let mut modified = false;
 
@@ -559,11 +841,7 @@ impl Task {
         }
 
         if let Some(tags) = tags {
-            let mut task_tags = if let Some(task_tags) = self.tags.clone() {
-                task_tags
-            } else {
-                vec![]
-            };
+            let mut task_tags = self.tags.clone().unwrap_or_default();
 
             let mut tags_modified = false;
             for remove_tag in tags {
@@ -593,10 +871,31 @@ impl Task {
             }
         }
 
+        if let Some(depends) = depends {
+            let mut task_depends = self.depends.clone().unwrap_or_default();
+
+            let mut depends_modified = false;
+            for remove_dependency in depends {
+                if let Some(index) = task_depends.iter().position(|r| r == remove_dependency) {
+                    task_depends.remove(index);
+                    depends_modified = true;
+                }
+            }
+
+            if depends_modified {
+                self.depends = if task_depends.is_empty() {
+                    None
+                } else {
+                    Some(task_depends)
+                };
+                modified = true;
+            }
+        }
+
         modified
     }
 
-    /// Set task characteristics
+    /// Set task characteristics. Fails if the due date does not exist in the local time zone.
     pub fn set_characteristic(
         &mut self,
         priority: &Option<TaskPriority>,
@@ -604,7 +903,8 @@ impl Task {
         tags: &Option<Vec<String>>,
         project: &Option<String>,
         metadata: &Option<Vec<MetadataKeyValuePair>>,
-    ) -> bool {
+        depends: &Option<Vec<Uuid>>,
+    ) -> Result<bool> {
         let mut modified = false;
 
         if let Some(priority) = priority {
@@ -618,17 +918,13 @@ impl Task {
         if let Some(due_date) = due_date {
             self.metadata.insert(
                 "tsk-rs-task-due-time".to_string(),
-                due_date.and_local_timezone(Local).unwrap().to_rfc3339(),
+                local_due_date(*due_date)?.to_rfc3339(),
             );
             modified = true;
         }
 
         if let Some(tags) = tags {
-            let mut task_tags = if let Some(task_tags) = self.tags.clone() {
-                task_tags
-            } else {
-                vec![]
-            };
+            let mut task_tags = self.tags.clone().unwrap_or_default();
 
             let mut tags_modified = false;
             for new_tag in tags {
@@ -657,7 +953,40 @@ impl Task {
             }
         }
 
-        modified
+        if let Some(depends) = depends {
+            let mut task_depends = self.depends.clone().unwrap_or_default();
+
+            let mut depends_modified = false;
+            for new_dependency in depends {
+                if !task_depends.contains(new_dependency) {
+                    task_depends.push(*new_dependency);
+                    depends_modified = true;
+                }
+            }
+
+            if depends_modified {
+                self.depends = Some(task_depends);
+                modified = true;
+            }
+        }
+
+        Ok(modified)
+    }
+
+    /// Returns true if any of the tasks this task depends on is still open. Dependencies that do
+    /// not exist anymore are considered to be completed.
+    pub fn is_blocked(&self, settings: &Settings) -> Result<bool> {
+        if self.depends.is_none() {
+            return Ok(false);
+        }
+        Ok(self.is_blocked_by(&open_task_ids(settings)?))
+    }
+
+    /// Returns true if any of the tasks this task depends on is among the open tasks
+    pub fn is_blocked_by(&self, open_task_ids: &HashSet<Uuid>) -> bool {
+        self.depends
+            .as_ref()
+            .is_some_and(|depends| depends.iter().any(|id| open_task_ids.contains(id)))
     }
 }
 
@@ -674,27 +1003,235 @@ pub fn task_pathbuf_from_task(task: &Task, settings: &Settings) -> Result<PathBu
     task_pathbuf_from_id(&task.id.to_string(), settings)
 }
 
-/// Load task from file, identified by id
-pub fn load_task(id: &String, settings: &Settings) -> Result<Task> {
-    let task_pathbuf =
-        task_pathbuf_from_id(id, settings).with_context(|| "while building path of the file")?;
-    let task = Task::load_yaml_file_from(&task_pathbuf)
-        .with_context(|| "while loading task yaml file for editing")?;
+/// Find the task that has been assigned the numeric short id (if any)
+pub fn task_id_from_short_id(short_id: &str, settings: &Settings) -> Result<Option<Uuid>> {
+    let short_id = match short_id.parse::<usize>() {
+        Ok(short_id) => short_id,
+        Err(_) => return Ok(None),
+    };
+
+    // resolved through the task summaries, which the flatfile storage serves from its index
+    Ok(open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while looking up short ids")?
+        .iter()
+        .find(|summary| summary.short_id == Some(short_id))
+        .map(|summary| summary.id))
+}
+
+/// Resolve the full task id from a full UUID, from the numeric short id or from an unique prefix
+/// of the UUID. An input that is both a short id in use and a prefix of some UUID, like `12`,
+/// resolves to the task with the short id. A longer prefix, or the full UUID, picks the other
+/// task.
+pub fn resolve_task_id(id: &str, settings: &Settings) -> Result<Uuid> {
+    if let Ok(uuid) = Uuid::parse_str(id) {
+        return Ok(uuid);
+    }
+
+    if let Some(uuid) = task_id_from_short_id(id, settings)? {
+        return Ok(uuid);
+    }
+
+    let task_ids = open_storage(settings)?.task_ids()?;
+    resolve_candidates(id, find_ids_by_prefix(id, &task_ids))
+}
+
+/// Load task from the storage, identified by id, short id or an unique id prefix
+pub fn load_task(id: &str, settings: &Settings) -> Result<Task> {
+    let uuid = resolve_task_id(id, settings).with_context(|| "while resolving task id")?;
+    let task = open_storage(settings)?
+        .load_task(&uuid)
+        .with_context(|| "while loading task from storage")?;
     Ok(task)
 }
 
-/// Save task to disk, identified by the id in its metadata
+/// Delete task from the storage permanently, identified by id, short id or an unique id prefix
+pub fn delete_task(id: &str, settings: &Settings) -> Result<Task> {
+    let mut task = load_task(id, settings)?;
+    open_storage(settings)?
+        .delete_task(&task.id)
+        .with_context(|| "while deleting task from storage")?;
+    let before = task.to_yaml_string()?;
+    record_change(JournalSubject::Task, &task.id, Some(before), None, settings)
+    .with_context(|| "while recording task deletion to journal")?;
+    Ok(task)
+}
+
+/// Assign the next free short id of the namespace to the task, unless it already has one
+fn assign_short_id(task: &mut Task, settings: &Settings) -> Result<()> {
+    if task.short_id().is_some() {
+        return Ok(());
+    }
+
+    // archived tasks keep their short ids, so those are not given out again
+    let stored_short_ids = open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while looking up short ids in use")?
+        .iter()
+        .filter_map(|summary| summary.short_id)
+        .max();
+    let archived_short_ids = archived_summaries(settings)
+        .with_context(|| "while looking up archived short ids")?
+        .iter()
+        .filter_map(|summary| summary.short_id)
+        .max();
+    let next_short_id = stored_short_ids.max(archived_short_ids).unwrap_or(0) + 1;
+    task.metadata.insert(
+        "tsk-rs-task-short-id".to_string(),
+        next_short_id.to_string(),
+    );
+
+    Ok(())
+}
+
+/// Returns true if following the dependencies from the task leads back to the task itself
+fn has_dependency_cycle(id: &Uuid, dependency_graph: &HashMap<Uuid, Vec<Uuid>>) -> bool {
+    let mut visited: HashSet<Uuid> = HashSet::new();
+    let mut unvisited: Vec<Uuid> = dependency_graph.get(id).cloned().unwrap_or_default();
+
+    while let Some(current) = unvisited.pop() {
+        if current == *id {
+            return true;
+        }
+        if visited.insert(current) {
+            if let Some(dependencies) = dependency_graph.get(&current) {
+                unvisited.extend(dependencies);
+            }
+        }
+    }
+
+    false
+}
+
+/// Make sure that the dependencies of the tasks do not lead back to the tasks themselves. The
+/// tasks are checked together as if all of them were saved, since they might differ from their
+/// versions in the storage.
+pub(crate) fn check_dependency_cycles(tasks: &[Task], settings: &Settings) -> Result<()> {
+    if tasks.iter().all(|task| task.depends.is_none()) {
+        return Ok(());
+    }
+
+    let mut dependency_graph: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for summary in open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while building dependency graph of tasks")?
+    {
+        dependency_graph.insert(summary.id, summary.depends.unwrap_or_default());
+    }
+    for task in tasks {
+        dependency_graph.insert(task.id, task.depends.clone().unwrap_or_default());
+    }
+
+    for task in tasks {
+        if has_dependency_cycle(&task.id, &dependency_graph) {
+            bail!(TaskError::DependencyCycle(task.id.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Make sure that none of the tasks is a subtask of itself through its parents. The tasks are
+/// checked together as if all of them were saved.
+pub(crate) fn check_parent_cycles(tasks: &[Task], settings: &Settings) -> Result<()> {
+    if tasks.iter().all(|task| task.parent.is_none()) {
+        return Ok(());
+    }
+
+    let mut parents: HashMap<Uuid, Uuid> = HashMap::new();
+    for summary in open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while building task hierarchy")?
+    {
+        if let Some(parent) = summary.parent {
+            parents.insert(summary.id, parent);
+        }
+    }
+    for task in tasks {
+        match task.parent {
+            Some(parent) => parents.insert(task.id, parent),
+            None => parents.remove(&task.id),
+        };
+    }
+
+    for task in tasks {
+        let mut visited: HashSet<Uuid> = HashSet::new();
+        let mut current = task.parent;
+        while let Some(parent) = current {
+            if parent == task.id {
+                bail!(TaskError::ParentCycle(task.id.to_string()));
+            }
+            if !visited.insert(parent) {
+                // a cycle further up that does not involve this task
+                break;
+            }
+            current = parents.get(&parent).copied();
+        }
+    }
+
+    Ok(())
+}
+
+/// Store the score of the task in its metadata, scored as configured and against the other
+/// tasks in the storage the same way listings score it
+fn refresh_score(task: &mut Task, storage: &dyn Storage, settings: &Settings) -> Result<()> {
+    let mut summaries = storage
+        .task_summaries()
+        .with_context(|| "while resolving dependencies")?;
+    // the task being saved might differ from the version in the storage
+    summaries.retain(|summary| summary.id != task.id);
+    summaries.push(TaskSummary::from(&*task));
+    let (open_task_ids, blocking_task_ids) = dependency_sets(&summaries);
+
+    let score = total_score(&full_score_terms(
+        task,
+        &open_task_ids,
+        &blocking_task_ids,
+        &settings.score,
+        chrono::offset::Local::now(),
+    )?);
+    task.metadata
+        .insert("tsk-rs-task-score".to_owned(), format!("{}", score));
+    Ok(())
+}
+
+/// Save task to the storage, identified by the id in its metadata. The task is checked for
+/// dependency and parent cycles first.
 pub fn save_task(task: &mut Task, settings: &Settings) -> Result<()> {
-    let task_pathbuf = task_pathbuf_from_task(task, settings)?;
-    task.save_yaml_file_to(&task_pathbuf, &settings.data.rotate)
-        .with_context(|| "while saving task yaml file")?;
+    check_dependency_cycles(slice::from_ref(task), settings)?;
+    check_parent_cycles(slice::from_ref(task), settings)?;
+    store_task(task, settings)
+}
+
+/// Save the task to the storage without checking it for dependency and parent cycles. The
+/// caller has checked the task already, possibly together with other tasks saved along.
+pub(crate) fn store_task(task: &mut Task, settings: &Settings) -> Result<()> {
+    assign_short_id(task, settings)?;
+    let storage = open_storage(settings)?;
+    refresh_score(task, storage.as_ref(), settings)?;
+    // snapshot of the previous version is kept in the journal for undo
+    let before = if storage.task_exists(&task.id)? {
+        Some(storage.load_task(&task.id)?.to_yaml_string()?)
+    } else {
+        None
+    };
+    storage
+        .save_task(task)
+        .with_context(|| "while saving task to storage")?;
+    let after = task.to_yaml_string()?;
+    record_change(JournalSubject::Task, &task.id, before, Some(after), settings)
+    .with_context(|| "while recording task change to journal")?;
     Ok(())
 }
 
-/// Create a new task
-pub fn new_task(descriptor: String, settings: &Settings) -> Result<Task> {
+/// Create a new task, optionally as a subtask of an existing task
+pub fn new_task(descriptor: String, parent: &Option<String>, settings: &Settings) -> Result<Task> {
     let mut task =
-        Task::from_task_descriptor(&descriptor).with_context(|| "while parsing task descriptor")?;
+        Task::from_task_descriptor_with(&descriptor, |id| resolve_task_id(id, settings))
+            .with_context(|| "while parsing task descriptor")?;
+    if let Some(parent) = parent {
+        task.parent = Some(load_task(parent, settings)?.id);
+    }
 
     // once the task file has been created check for special tags that should take immediate action
     if let Some(tags) = task.tags.clone() {
@@ -712,8 +1249,12 @@ pub fn new_task(descriptor: String, settings: &Settings) -> Result<Task> {
 }
 
 /// Start tracking the task, load & save the file on disk
-pub fn start_task(id: &String, annotation: &Option<String>, settings: &Settings) -> Result<Task> {
+pub fn start_task(id: &str, annotation: &Option<String>, settings: &Settings) -> Result<Task> {
     let mut task = load_task(id, settings)?;
+    if !settings.task.blockedstart && task.is_blocked(settings)? {
+        bail!(TaskError::TaskBlocked);
+    }
+    check_single_active(&task, settings)?;
     task.start(annotation)
         .with_context(|| "while starting time tracking")?;
 
@@ -725,6 +1266,7 @@ pub fn start_task(id: &String, annotation: &Option<String>, settings: &Settings)
             &Some(vec!["hold".to_string()]),
             &false,
             &None,
+            &None,
         );
     }
 
@@ -733,7 +1275,7 @@ pub fn start_task(id: &String, annotation: &Option<String>, settings: &Settings)
 }
 
 /// Stop tracking the task, load & save the file on disk
-pub fn stop_task(id: &String, done: &bool, settings: &Settings) -> Result<Task> {
+pub fn stop_task(id: &str, done: &bool, settings: &Settings) -> Result<Task> {
     let mut task = load_task(id, settings)?;
     task.stop()
         .with_context(|| "while stopping time tracking")?;
@@ -747,8 +1289,11 @@ pub fn stop_task(id: &String, done: &bool, settings: &Settings) -> Result<Task>
     Ok(task)
 }
 
-/// Mark the task completed, load & save the file on disk
-pub fn complete_task(task: &mut Task, settings: &Settings) -> Result<()> {
+/// Mark the task completed, load & save the file on disk. If the task recurs then the next
+/// instance is created and returned.
+pub fn complete_task(task: &mut Task, settings: &Settings) -> Result<Option<Task>> {
+    let was_done = task.done;
+
     if task.is_running() && settings.task.stopondone {
         // task is running, so first stop it
         stop_task(&task.id.to_string(), &false, settings)?;
@@ -766,6 +1311,7 @@ pub fn complete_task(task: &mut Task, settings: &Settings) -> Result<()> {
             ]),
             &false,
             &None,
+            &None,
         );
     }
 
@@ -773,80 +1319,182 @@ pub fn complete_task(task: &mut Task, settings: &Settings) -> Result<()> {
         .with_context(|| "while completing task")?;
     save_task(task, settings)?;
 
-    Ok(())
+    // only spawn the next instance once, even if a completed task is completed again
+    if was_done {
+        return Ok(None);
+    }
+    let mut next_instance = task.next_recurrence(chrono::offset::Local::now())?;
+    if let Some(next_instance) = next_instance.as_mut() {
+        save_task(next_instance, settings)
+            .with_context(|| "while saving next instance of a recurring task")?;
+    }
+
+    Ok(next_instance)
 }
 
-/// Load all tasks and return the sum of tasks.
+/// Return the amount of tasks in the storage
 pub fn amount_of_tasks(settings: &Settings, include_backups: bool) -> Result<usize> {
-    let mut tasks: usize = 0;
-    let task_pathbuf: PathBuf = task_pathbuf_from_id(&"*".to_string(), settings)?;
-    for task_filename in glob(task_pathbuf.to_str().unwrap())
-        .with_context(|| "while traversing task data directory files")?
-    {
-        // if the filename is u-u-i-d.3.yaml for example it is a backup file and should be disregarded
-        if task_filename
-            .as_ref()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .split('.')
-            .collect::<Vec<_>>()[1]
-            != "yaml"
-            && !include_backups
-        {
-            continue;
+    open_storage(settings)?.count_tasks(include_backups)
+}
+
+/// Single term contributing to the score of a task
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreTerm {
+    /// What the points were given for
+    pub reason: String,
+    /// Points added to the score, or removed from it if negative
+    pub points: f64,
+}
+
+impl ScoreTerm {
+    fn new(reason: &str, points: f64) -> Self {
+        Self {
+            reason: reason.to_string(),
+            points,
         }
-        tasks += 1;
     }
-    Ok(tasks)
 }
 
-/// List all tasks that match an optional search criteria
+/// Sum the score terms to a score. Score never goes below zero.
+pub fn total_score(terms: &[ScoreTerm]) -> usize {
+    terms.iter().map(|term| term.points).sum::<f64>().max(0.0) as usize
+}
+
+/// Tasks that are blocked by unfinished dependencies lose urgency, while tasks that block others
+/// gain urgency so that they get done first
+pub fn dependency_score_terms(
+    blocked: bool,
+    blocking: bool,
+    settings: &ScoreSettings,
+) -> Vec<ScoreTerm> {
+    let mut terms: Vec<ScoreTerm> = vec![];
+    if blocking {
+        terms.push(ScoreTerm::new("blocking other tasks", settings.blocking));
+    }
+    if blocked {
+        terms.push(ScoreTerm::new("blocked by dependencies", -settings.blocked));
+    }
+    terms
+}
+
+/// Returns the ids of the unfinished tasks and the ids of the tasks they depend on
+fn dependency_sets(tasks: &[TaskSummary]) -> (HashSet<Uuid>, HashSet<Uuid>) {
+    let open_task_ids: HashSet<Uuid> = tasks
+        .iter()
+        .filter(|task| !task.done)
+        .map(|task| task.id)
+        .collect();
+    let blocking_task_ids: HashSet<Uuid> = tasks
+        .iter()
+        .filter(|task| !task.done)
+        .flat_map(|task| task.depends.clone().unwrap_or_default())
+        .collect();
+    (open_task_ids, blocking_task_ids)
+}
+
+/// Ids of the open tasks of the namespace, read from the task summaries. Tasks depending on any
+/// of them are blocked.
+pub fn open_task_ids(settings: &Settings) -> Result<HashSet<Uuid>> {
+    let summaries = open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while loading task summaries from storage")?;
+    Ok(dependency_sets(&summaries).0)
+}
+
+/// Break down the score of the task including the terms that depend on other tasks
+fn full_score_terms(
+    task: &Task,
+    open_task_ids: &HashSet<Uuid>,
+    blocking_task_ids: &HashSet<Uuid>,
+    settings: &ScoreSettings,
+    now: DateTime<Local>,
+) -> Result<Vec<ScoreTerm>> {
+    let blocked = task.is_blocked_by(open_task_ids);
+    let blocking = !task.done && blocking_task_ids.contains(&task.id);
+
+    let mut terms = task.score_terms(settings, now)?;
+    terms.append(&mut dependency_score_terms(blocked, blocking, settings));
+    Ok(terms)
+}
+
+/// Explain the score of the task by listing all the terms contributing to it
+pub fn explain_score(id: &str, settings: &Settings) -> Result<(Task, Vec<ScoreTerm>)> {
+    let task = load_task(id, settings)?;
+    let summaries = open_storage(settings)?
+        .task_summaries()
+        .with_context(|| "while resolving dependencies")?;
+    let (open_task_ids, blocking_task_ids) = dependency_sets(&summaries);
+
+    let terms = full_score_terms(
+        &task,
+        &open_task_ids,
+        &blocking_task_ids,
+        &settings.score,
+        chrono::offset::Local::now(),
+    )?;
+    Ok((task, terms))
+}
+
+/// List all tasks that match an optional search criteria. The search is parsed as a filter
+/// expression, so a plain word matches loosely against description, project and tags.
 pub fn list_tasks(
     search: &Option<String>,
     include_done: &bool,
     settings: &Settings,
 ) -> Result<Vec<Task>> {
-    let task_pathbuf: PathBuf = task_pathbuf_from_id(&"*".to_string(), settings)?;
-
-    let mut found_tasks: Vec<Task> = vec![];
-    for task_filename in glob(task_pathbuf.to_str().unwrap())
-        .with_context(|| "while traversing task data directory files")?
-    {
-        // if the filename is u-u-i-d.3.yaml for example it is a backup file and should be disregarded
-        if task_filename
-            .as_ref()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .split('.')
-            .collect::<Vec<_>>()[1]
-            != "yaml"
-        {
-            continue;
-        }
-
-        let task = Task::load_yaml_file_from(&task_filename?)
-            .with_context(|| "while loading task from yaml file")?;
+    let filter = match search {
+        Some(search) => Some(Filter::from_str(search)?),
+        None => None,
+    };
+    list_tasks_matching(&filter, include_done, settings)
+}
 
-        if !task.done || *include_done {
-            if let Some(search) = search {
-                if task.loose_match(search) {
-                    // a part of key information matches search term, so the task is included
-                    found_tasks.push(task);
-                }
-            } else {
-                // search term is empty so everything matches
-                found_tasks.push(task);
+/// List tasks that match the filter expression, sorted by their score
+pub fn list_tasks_matching(
+    filter: &Option<Filter>,
+    include_done: &bool,
+    settings: &Settings,
+) -> Result<Vec<Task>> {
+    // only the tasks that can match by their summaries are loaded from the storage
+    let (summaries, candidate_tasks) = open_storage(settings)?
+        .select_tasks(&|summary| {
+            (*include_done || !summary.done)
+                && filter
+                    .as_ref()
+                    .and_then(|filter| filter.matches_summary(summary))
+                    != Some(false)
+        })
+        .with_context(|| "while loading tasks from storage")?;
+
+    // dependencies are resolved against all open tasks, not just the ones that end up in the
+    // listing
+    let (open_task_ids, blocking_task_ids) = dependency_sets(&summaries);
+    let now = chrono::offset::Local::now();
+
+    let mut found_tasks: Vec<(usize, Task)> = vec![];
+    for mut task in candidate_tasks {
+        if let Some(filter) = filter {
+            if !filter.matches(&task) {
+                continue;
             }
         }
+
+        let score = total_score(&full_score_terms(
+            &task,
+            &open_task_ids,
+            &blocking_task_ids,
+            &settings.score,
+            now,
+        )?);
+        task.metadata
+            .insert("tsk-rs-task-score".to_owned(), format!("{}", score));
+
+        found_tasks.push((score, task));
     }
-    found_tasks.sort_by_key(|k| k.score().unwrap());
+    found_tasks.sort_by_key(|(score, _)| *score);
     found_tasks.reverse();
 
-    Ok(found_tasks)
+    Ok(found_tasks.into_iter().map(|(_, task)| task).collect())
 }
 
 #[cfg(test)]
@@ -854,6 +1502,7 @@ mod tests {
     use chrono::{DateTime, Datelike};
 
     use super::*;
+    use crate::{archive::archive_tasks, testing::TestData};
 
     static FULLTESTCASEINPUT: &str = "some task description here @project-here #taghere #a-second-tag %x-meta=data %x-fuu=bar additional text at the end";
     static FULLTESTCASEINPUT2: &str = "some task description here PRJ:project-here #taghere TAG:a-second-tag META:x-meta=data %x-fuu=bar DUE:2022-08-16T16:56:00 PRIO:medium and some text at the end";
@@ -901,7 +1550,7 @@ mod tests {
                 task.id,
                 task.description,
                 task.project.unwrap(),
-                task.tags.clone().unwrap().get(0).unwrap(),
+                task.tags.clone().unwrap().first().unwrap(),
                 task.tags.clone().unwrap().get(1).unwrap(),
                 task.metadata.clone().get("tsk-rs-task-create-time").unwrap(),
                 task.metadata.clone().get("x-fuu").unwrap(),
@@ -948,6 +1597,45 @@ mod tests {
         //assert_eq!(task.metadata.get("tsk-rs-task-due-time"), );
     }
 
+    #[test]
+    fn estimate_against_tracked_time() {
+        let now = Local::now();
+        let mut task = Task::from_task_descriptor(&"write docs est:2h".to_string()).unwrap();
+        assert_eq!(task.description, "write docs");
+        assert_eq!(task.estimate(), Some(Duration::hours(2)));
+
+        task.timetracker = Some(vec![
+            TimeTrack {
+                start_time: now - Duration::hours(3),
+                end_time: Some(now - Duration::hours(2)),
+                annotation: None,
+            },
+            TimeTrack {
+                start_time: now - Duration::minutes(30),
+                end_time: None,
+                annotation: None,
+            },
+        ]);
+        assert_eq!(task.tracked_time(now), Duration::minutes(90));
+        assert_eq!(task.remaining_effort(now), Some(Duration::minutes(30)));
+        assert!(!task.is_over_estimate(now));
+
+        assert!(task.set_estimate(&Some(Duration::hours(1))));
+        assert!(!task.set_estimate(&Some(Duration::hours(1))));
+        assert!(task.is_over_estimate(now));
+        assert!(task.set_estimate(&None));
+        assert!(!task.set_estimate(&None));
+        assert_eq!(task.remaining_effort(now), None);
+
+        assert_eq!(
+            Task::from_task_descriptor(&"twice est:1h est:2h".to_string())
+                .unwrap_err()
+                .downcast::<TaskError>()
+                .unwrap(),
+            TaskError::MultipleEstimatesNotAllowed
+        );
+    }
+
     #[test]
     fn parse_no_expressions() {
         let task = Task::from_task_descriptor(&NOEXPRESSIONSINPUT.to_string()).unwrap();
@@ -956,7 +1644,175 @@ mod tests {
         assert_eq!(task.description, NOEXPRESSIONSINPUT);
         assert_eq!(task.tags, None);
 
-        assert!(task.metadata.get("tsk-rs-task-create-time").is_some());
+        assert!(task.metadata.contains_key("tsk-rs-task-create-time"));
+    }
+
+    #[test]
+    fn parse_dependencies() {
+        let dependency = "bd6f75aa-8c8d-47fb-b905-d9f7b15c782d";
+        let task =
+            Task::from_task_descriptor(&format!("do this after dep:{0} dep:{0}", dependency))
+                .unwrap();
+
+        assert_eq!(task.description, "do this after");
+        assert_eq!(
+            task.depends,
+            Some(vec![Uuid::parse_str(dependency).unwrap()])
+        );
+    }
+
+    #[test]
+    fn detect_dependency_cycle() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let third = Uuid::new_v4();
+        let mut graph: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        graph.insert(first, vec![second]);
+        graph.insert(second, vec![third]);
+        assert!(!has_dependency_cycle(&first, &graph));
+
+        graph.insert(third, vec![first]);
+        assert!(has_dependency_cycle(&first, &graph));
+    }
+
+    #[test]
+    fn dependency_score_adjustment() {
+        let settings = ScoreSettings::default();
+        let base = vec![ScoreTerm::new("base", 10.0)];
+        let with_dependencies = |blocked: bool, blocking: bool| {
+            let mut terms = base.clone();
+            terms.append(&mut dependency_score_terms(blocked, blocking, &settings));
+            total_score(&terms)
+        };
+        assert_eq!(with_dependencies(false, false), 10);
+        assert_eq!(with_dependencies(false, true), 18);
+        assert_eq!(with_dependencies(true, false), 5);
+        assert_eq!(
+            total_score(&dependency_score_terms(true, false, &settings)),
+            0
+        );
+    }
+
+    #[test]
+    fn configured_score_terms() {
+        let mut settings = ScoreSettings::default();
+        settings.tags.insert("urgent".to_string(), 5.0);
+        settings.projects.insert("work".to_string(), 4.0);
+        settings.prioritymedium = 6.0;
+
+        let task = Task::from_task_descriptor(
+            &"report @Work #urgent #hold prio:medium due:2022-08-16T16:56:00".to_string(),
+        )
+        .unwrap();
+        let now =
+            DateTime::<Local>::from_str(task.metadata.get("tsk-rs-task-create-time").unwrap())
+                .unwrap();
+
+        let terms = task.score_terms(&settings, now).unwrap();
+        let reasons: Vec<&str> = terms.iter().map(|term| term.reason.as_str()).collect();
+        assert!(reasons.contains(&"project Work"));
+        assert!(reasons.contains(&"tag urgent"));
+        assert!(reasons.contains(&"special tag hold"));
+        // 3 (project) + 4 (work) + 4 (tags) + 5 (urgent) + 6 (priority) + 10 (overdue) - 20 (hold)
+        assert_eq!(total_score(&terms), 12);
+    }
+
+    #[test]
+    fn project_boost_applies_to_subprojects() {
+        let mut settings = ScoreSettings::default();
+        settings.projects.insert("customer".to_string(), 4.0);
+        settings
+            .projects
+            .insert("customer.internal".to_string(), 1.0);
+        let boost = |descriptor: &str| {
+            let task = Task::from_task_descriptor(&descriptor.to_string()).unwrap();
+            task.score_terms(&settings, Local::now())
+                .unwrap()
+                .into_iter()
+                .find(|term| term.reason.starts_with("project "))
+                .map(|term| term.points)
+        };
+        assert_eq!(boost("report @customer.web"), Some(4.0));
+        // the most specific level wins
+        assert_eq!(boost("report @customer.internal.wiki"), Some(1.0));
+        assert_eq!(boost("report @customers"), None);
+    }
+
+    #[test]
+    fn due_curve_score() {
+        let task =
+            Task::from_task_descriptor(&"report due:2022-08-16T16:56:00".to_string()).unwrap();
+        let settings = ScoreSettings::default();
+        let due = |days: i64| {
+            let now = DateTime::<Local>::from_str("2022-08-16T16:56:00+00:00").unwrap()
+                - Duration::days(days)
+                - Duration::minutes(1);
+            task.score_terms(&settings, now)
+                .unwrap()
+                .into_iter()
+                .find(|term| term.reason.starts_with("due") || term.reason == "overdue")
+                .unwrap()
+                .points
+        };
+        assert_eq!(due(-1), 10.0);
+        assert_eq!(due(1), 7.0);
+        assert_eq!(due(4), 3.0);
+        assert_eq!(due(30), 1.0);
+    }
+
+    #[test]
+    fn spawn_next_recurrence() {
+        let mut task = Task::from_task_descriptor(
+            &"weekly report @reports #next %x-client=acme recur:weekly due:2024-05-01T09:00:00"
+                .to_string(),
+        )
+        .unwrap();
+        task.mark_as_completed().unwrap();
+        let now = DateTime::from_str("2024-05-02T10:00:00+00:00").unwrap();
+
+        let next = task.next_recurrence(now).unwrap().unwrap();
+
+        assert_ne!(next.id, task.id);
+        assert!(!next.done);
+        assert_eq!(next.description, "weekly report");
+        assert_eq!(next.project, Some("reports".to_string()));
+        assert_eq!(next.tags, None);
+        assert_eq!(next.recurrence, Some(Recurrence::Weekly));
+        assert_eq!(next.recurrence_parent(), Some(task.id));
+        assert_eq!(next.metadata.get("x-client"), Some(&"acme".to_string()));
+        let due: DateTime<Local> =
+            DateTime::from_str(next.metadata.get("tsk-rs-task-due-time").unwrap()).unwrap();
+        assert_eq!(due.naive_local().to_string(), "2024-05-08 09:00:00");
+    }
+
+    #[test]
+    fn monthly_recurrence_keeps_anchor_day() {
+        let mut task = Task::from_task_descriptor(
+            &"invoicing recur:monthly due:2024-01-31T09:00:00".to_string(),
+        )
+        .unwrap();
+        let due = |task: &Task| {
+            DateTime::<Local>::from_str(task.metadata.get("tsk-rs-task-due-time").unwrap())
+                .unwrap()
+                .date_naive()
+                .to_string()
+        };
+
+        let mut dues: Vec<String> = vec![];
+        for _ in 0..3 {
+            task.mark_as_completed().unwrap();
+            let now = DateTime::from_str(task.metadata.get("tsk-rs-task-due-time").unwrap())
+                .unwrap();
+            task = task.next_recurrence(now).unwrap().unwrap();
+            dues.push(due(&task));
+        }
+        assert_eq!(dues, vec!["2024-02-29", "2024-03-31", "2024-04-30"]);
+    }
+
+    #[test]
+    fn no_recurrence_without_rule() {
+        let task = Task::from_task_descriptor(&NOEXPRESSIONSINPUT.to_string()).unwrap();
+        assert!(task.next_recurrence(Local::now()).unwrap().is_none());
     }
 
     #[test]
@@ -988,6 +1844,50 @@ mod tests {
             TaskError::MetadataPrefixInvalid(String::from("invalid"))
         );
     }
+
+    #[test]
+    fn short_id_wins_over_uuid_prefix() {
+        let settings = TestData::new();
+        let mut first = Task::new("first".to_string()).unwrap();
+        first.id = Uuid::parse_str("2d6f75aa-8c8d-47fb-b905-d9f7b15c782d").unwrap();
+        let mut second = Task::new("second".to_string()).unwrap();
+        save_task(&mut first, &settings).unwrap();
+        save_task(&mut second, &settings).unwrap();
+        assert_eq!(second.short_id(), Some(2));
+
+        assert_eq!(resolve_task_id("2", &settings).unwrap(), second.id);
+        assert_eq!(resolve_task_id("2d", &settings).unwrap(), first.id);
+    }
+
+    #[test]
+    fn archived_short_ids_are_not_reused() {
+        let settings = TestData::new();
+        let mut archived = Task::new("archived".to_string()).unwrap();
+        archived.done = true;
+        save_task(&mut archived, &settings).unwrap();
+        archive_tasks(Duration::zero(), false, &settings).unwrap();
+
+        let mut task = Task::new("new".to_string()).unwrap();
+        save_task(&mut task, &settings).unwrap();
+
+        assert_eq!(task.short_id(), Some(2));
+    }
+
+    #[test]
+    fn blocked_by_open_dependencies_only() {
+        let settings = TestData::new();
+        let mut dependency = Task::new("dependency".to_string()).unwrap();
+        save_task(&mut dependency, &settings).unwrap();
+        let mut task = Task::new("task".to_string()).unwrap();
+        task.depends = Some(vec![dependency.id, Uuid::new_v4()]);
+        save_task(&mut task, &settings).unwrap();
+        assert!(task.is_blocked(&settings).unwrap());
+
+        // missing dependencies count as completed
+        dependency.done = true;
+        save_task(&mut dependency, &settings).unwrap();
+        assert!(!task.is_blocked(&settings).unwrap());
+    }
 }
 
 // eof
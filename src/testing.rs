@@ -0,0 +1,50 @@
+use std::{fs::remove_dir_all, ops::Deref};
+
+use uuid::Uuid;
+
+use crate::settings::{DataSettings, Settings};
+
+/// Settings of a test pointing to a data directory of its own. The directory is removed when the
+/// value is dropped, also when the test fails.
+pub(crate) struct TestData {
+    settings: Settings,
+}
+
+impl TestData {
+    /// Settings for the default namespace in a new temporary data directory
+    pub(crate) fn new() -> Self {
+        Self::with(|_| {})
+    }
+
+    /// Settings in a new temporary data directory, adjusted by the closure
+    pub(crate) fn with(configure: impl FnOnce(&mut Settings)) -> Self {
+        let path = std::env::temp_dir().join(format!("tsk-rs-test-{}", Uuid::new_v4()));
+        let mut settings = Settings {
+            namespace: "default".to_string(),
+            data: DataSettings {
+                path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        configure(&mut settings);
+        Self { settings }
+    }
+}
+
+impl Deref for TestData {
+    type Target = Settings;
+
+    fn deref(&self) -> &Self::Target {
+        &self.settings
+    }
+}
+
+impl Drop for TestData {
+    fn drop(&mut self) {
+        // nothing to remove if the test never wrote anything
+        let _ = remove_dir_all(&self.settings.data.path);
+    }
+}
+
+// eof
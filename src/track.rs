@@ -0,0 +1,438 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use color_eyre::eyre::{bail, Result};
+use thiserror::Error;
+
+use crate::{
+    parser::track_time::TRACKED_YEARS,
+    task::{Task, TimeTrack},
+};
+
+/// Errors that can occur when logging or editing tracked time
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TrackError {
+    /// There is no time span with the number
+    #[error("task has no tracked time span {0}")]
+    NotFound(usize),
+    /// Time span would end before it starts
+    #[error("time span must end after it starts")]
+    EndBeforeStart,
+    /// Two time spans of the task would overlap
+    #[error("time span starting {0} would overlap with the one starting {1}")]
+    Overlap(String, String),
+    /// Time span would start or end in the future
+    #[error("tracked time can not be in the future")]
+    InFuture,
+    /// Time span that is still running is not the latest one
+    #[error("only the latest time span can be running")]
+    RunningNotLatest,
+    /// Split point is not inside the time span
+    #[error("time span can only be split at a moment inside it")]
+    SplitOutside,
+    /// The latest time span has no following span to merge with
+    #[error("time span {0} is the latest one, there is nothing to merge it with")]
+    NothingToMerge(usize),
+    /// Time does not exist in the local time zone, e.g it is skipped by daylight saving
+    #[error("time {0} does not exist in the local time zone")]
+    InvalidLocalTime(NaiveDateTime),
+    /// Time span would not fall in the years tracked time can be in
+    #[error("time span must fall between years {} and {}", TRACKED_YEARS.start(), TRACKED_YEARS.end())]
+    OutOfRange,
+}
+
+/// Local time of the naive date time. Times repeated by daylight saving resolve to the earlier
+/// moment.
+pub fn local_time(datetime: NaiveDateTime) -> Result<DateTime<Local>> {
+    match Local.from_local_datetime(&datetime).earliest() {
+        Some(local) => Ok(local),
+        None => bail!(TrackError::InvalidLocalTime(datetime)),
+    }
+}
+
+fn format_time(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Sort the time spans by their start and check the spans that are not among the unchanged
+/// ones: every such span ends after it starts and not in the future, does not overlap any other
+/// span and is the only one still running if it is the latest. Spans left unchanged are not
+/// checked against each other, so spans recorded before these checks existed do not prevent
+/// changing the other spans.
+pub fn validate_spans(spans: &mut [TimeTrack], unchanged: &[TimeTrack]) -> Result<()> {
+    spans.sort_by_key(|span| span.start_time);
+    let now = Local::now();
+    // each unchanged span accounts for one span only, so a duplicate of it is checked
+    let mut remaining = unchanged.to_vec();
+    let changed: Vec<usize> = (0..spans.len())
+        .filter(
+            |index| match remaining.iter().position(|span| *span == spans[*index]) {
+                Some(position) => {
+                    remaining.swap_remove(position);
+                    false
+                }
+                None => true,
+            },
+        )
+        .collect();
+    for span in changed.iter().map(|index| &spans[*index]) {
+        if span.start_time.year() < *TRACKED_YEARS.start() {
+            bail!(TrackError::OutOfRange);
+        }
+        if span
+            .end_time
+            .is_some_and(|end_time| end_time <= span.start_time)
+        {
+            bail!(TrackError::EndBeforeStart);
+        }
+        if span.start_time > now || span.end_time.is_some_and(|end_time| end_time > now) {
+            bail!(TrackError::InFuture);
+        }
+    }
+    for index in changed {
+        for other in (0..spans.len()).filter(|other| *other != index) {
+            let (earlier, later) = (&spans[index.min(other)], &spans[index.max(other)]);
+            match earlier.end_time {
+                None => bail!(TrackError::RunningNotLatest),
+                Some(end_time) if end_time > later.start_time => bail!(TrackError::Overlap(
+                    format_time(&earlier.start_time),
+                    format_time(&later.start_time)
+                )),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply the change to a copy of the time spans of the task, numbered from one. The task is
+/// changed only if the spans are valid afterwards.
+fn change_spans<F, T>(task: &mut Task, change: F) -> Result<T>
+where
+    F: FnOnce(&mut Vec<TimeTrack>) -> Result<T>,
+{
+    let unchanged = task.timetracker.clone().unwrap_or_default();
+    let mut spans = unchanged.clone();
+    let result = change(&mut spans)?;
+    validate_spans(&mut spans, &unchanged)?;
+    task.timetracker = if spans.is_empty() { None } else { Some(spans) };
+    Ok(result)
+}
+
+/// Index of the time span by its number, which starts from one
+fn span_index(spans: &[TimeTrack], number: usize) -> Result<usize> {
+    if number == 0 || number > spans.len() {
+        bail!(TrackError::NotFound(number));
+    }
+    Ok(number - 1)
+}
+
+/// Record time spent on the task after the fact
+pub fn log_span(
+    task: &mut Task,
+    start_time: DateTime<Local>,
+    end_time: DateTime<Local>,
+    annotation: &Option<String>,
+) -> Result<()> {
+    change_spans(task, |spans| {
+        spans.push(TimeTrack {
+            start_time,
+            end_time: Some(end_time),
+            annotation: annotation.clone(),
+        });
+        Ok(())
+    })
+}
+
+/// Change the start, end and/or annotation of the time span. Setting the end of a running span
+/// stops it.
+pub fn edit_span(
+    task: &mut Task,
+    number: usize,
+    start_time: &Option<DateTime<Local>>,
+    end_time: &Option<DateTime<Local>>,
+    annotation: &Option<String>,
+) -> Result<()> {
+    change_spans(task, |spans| {
+        let index = span_index(spans, number)?;
+        let span = &mut spans[index];
+        if let Some(start_time) = start_time {
+            span.start_time = *start_time;
+        }
+        if end_time.is_some() {
+            span.end_time = *end_time;
+        }
+        if annotation.is_some() {
+            span.annotation = annotation.clone();
+        }
+        Ok(())
+    })
+}
+
+/// Remove the time span from the task and return it
+pub fn delete_span(task: &mut Task, number: usize) -> Result<TimeTrack> {
+    change_spans(task, |spans| Ok(spans.remove(span_index(spans, number)?)))
+}
+
+/// Split the time span in two at the given moment, both halves keep the annotation. A running
+/// span is split to a finished span and a running one.
+pub fn split_span(task: &mut Task, number: usize, at: DateTime<Local>) -> Result<()> {
+    change_spans(task, |spans| {
+        let index = span_index(spans, number)?;
+        let span = spans[index].clone();
+        if at <= span.start_time || at >= span.end_time.unwrap_or_else(Local::now) {
+            bail!(TrackError::SplitOutside);
+        }
+        spans[index].end_time = Some(at);
+        spans.insert(
+            index + 1,
+            TimeTrack {
+                start_time: at,
+                ..span
+            },
+        );
+        Ok(())
+    })
+}
+
+/// Merge the time span with the one following it. The merged span covers both spans along with
+/// the time between them and has the annotations of both.
+pub fn merge_spans(task: &mut Task, number: usize) -> Result<()> {
+    change_spans(task, |spans| {
+        let index = span_index(spans, number)?;
+        if index + 1 >= spans.len() {
+            bail!(TrackError::NothingToMerge(number));
+        }
+        let next = spans.remove(index + 1);
+        let span = &mut spans[index];
+        span.end_time = next.end_time;
+        span.annotation = match (span.annotation.take(), next.annotation) {
+            (Some(first), Some(second)) if first != second => {
+                Some(format!("{}; {}", first, second))
+            }
+            (first, second) => first.or(second),
+        };
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn hours_ago(now: DateTime<Local>, hours: i64) -> DateTime<Local> {
+        now - Duration::hours(hours)
+    }
+
+    /// Task with a span five to four hours ago and an annotated one two to one hours ago
+    fn logged_task(now: DateTime<Local>) -> Task {
+        let mut task = Task::new("meeting".to_string()).unwrap();
+        log_span(
+            &mut task,
+            hours_ago(now, 2),
+            hours_ago(now, 1),
+            &Some("review".to_string()),
+        )
+        .unwrap();
+        log_span(&mut task, hours_ago(now, 5), hours_ago(now, 4), &None).unwrap();
+        task
+    }
+
+    fn track_error(result: Result<()>) -> TrackError {
+        result.unwrap_err().downcast::<TrackError>().unwrap()
+    }
+
+    #[test]
+    fn logged_spans_are_kept_in_order() {
+        let now = Local::now();
+        let task = logged_task(now);
+
+        let spans = task.timetracker.unwrap();
+        assert_eq!(spans[0].start_time, hours_ago(now, 5));
+        assert_eq!(spans[1].start_time, hours_ago(now, 2));
+    }
+
+    #[test]
+    fn overlapping_span_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert!(matches!(
+            track_error(log_span(
+                &mut task,
+                hours_ago(now, 3),
+                hours_ago(now, 1),
+                &None
+            )),
+            TrackError::Overlap(_, _)
+        ));
+        // failed changes leave the spans as they were
+        assert_eq!(task.timetracker.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_span_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert!(matches!(
+            track_error(log_span(
+                &mut task,
+                hours_ago(now, 5),
+                hours_ago(now, 4),
+                &None
+            )),
+            TrackError::Overlap(_, _)
+        ));
+    }
+
+    #[test]
+    fn earlier_overlaps_do_not_prevent_editing() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+        // recorded before the spans were checked
+        task.timetracker.as_mut().unwrap().push(TimeTrack {
+            start_time: hours_ago(now, 3),
+            end_time: Some(hours_ago(now, 1)),
+            annotation: None,
+        });
+
+        edit_span(&mut task, 1, &None, &None, &Some("planning".to_string())).unwrap();
+        assert_eq!(
+            task.timetracker.as_ref().unwrap()[0].annotation,
+            Some("planning".to_string())
+        );
+        // the changed span is still checked against the others
+        assert!(matches!(
+            track_error(edit_span(
+                &mut task,
+                1,
+                &None,
+                &Some(hours_ago(now, 2)),
+                &None
+            )),
+            TrackError::Overlap(_, _)
+        ));
+    }
+
+    #[test]
+    fn span_ending_before_start_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert_eq!(
+            track_error(edit_span(
+                &mut task,
+                2,
+                &None,
+                &Some(hours_ago(now, 3)),
+                &None
+            )),
+            TrackError::EndBeforeStart
+        );
+    }
+
+    #[test]
+    fn span_in_future_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert_eq!(
+            track_error(log_span(
+                &mut task,
+                hours_ago(now, 1),
+                now + Duration::hours(1),
+                &None
+            )),
+            TrackError::InFuture
+        );
+    }
+
+    #[test]
+    fn span_before_year_zero_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+        let start_time = now.checked_sub_signed(Duration::days(3_000_000)).unwrap();
+
+        assert_eq!(
+            track_error(log_span(&mut task, start_time, hours_ago(now, 6), &None)),
+            TrackError::OutOfRange
+        );
+    }
+
+    #[test]
+    fn missing_span_is_not_found() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert_eq!(
+            delete_span(&mut task, 3)
+                .unwrap_err()
+                .downcast::<TrackError>()
+                .unwrap(),
+            TrackError::NotFound(3)
+        );
+    }
+
+    #[test]
+    fn split_keeps_annotation_on_both_halves() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        split_span(&mut task, 2, now - Duration::minutes(90)).unwrap();
+
+        let spans = task.timetracker.unwrap();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[1].end_time, Some(spans[2].start_time));
+        assert_eq!(spans[1].annotation, Some("review".to_string()));
+        assert_eq!(spans[2].annotation, Some("review".to_string()));
+    }
+
+    #[test]
+    fn split_outside_span_is_refused() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert_eq!(
+            track_error(split_span(&mut task, 1, hours_ago(now, 6))),
+            TrackError::SplitOutside
+        );
+    }
+
+    #[test]
+    fn merge_covers_both_spans_and_the_gap() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        merge_spans(&mut task, 1).unwrap();
+
+        let spans = task.timetracker.unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].start_time, hours_ago(now, 5));
+        assert_eq!(spans[0].end_time, Some(hours_ago(now, 1)));
+        assert_eq!(spans[0].annotation, Some("review".to_string()));
+    }
+
+    #[test]
+    fn latest_span_has_nothing_to_merge() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert_eq!(
+            track_error(merge_spans(&mut task, 2)),
+            TrackError::NothingToMerge(2)
+        );
+    }
+
+    #[test]
+    fn deleting_every_span_clears_timetracker() {
+        let now = Local::now();
+        let mut task = logged_task(now);
+
+        assert!(delete_span(&mut task, 1).unwrap().end_time.is_some());
+        delete_span(&mut task, 1).unwrap();
+
+        assert!(task.timetracker.is_none());
+    }
+}
+
+// eof
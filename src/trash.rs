@@ -0,0 +1,443 @@
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, rename, write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Local};
+use color_eyre::eyre::{bail, Context, Result};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[cfg(feature = "note")]
+use crate::note::{delete_note, load_note, save_note, Note};
+use crate::{
+    id::{find_ids_by_prefix, resolve_candidates},
+    settings::Settings,
+    storage::open_storage,
+    task::{delete_task, load_task, save_task, Task},
+};
+
+/// Errors that can occur when working with the trash
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TrashError {
+    /// Entry in the trash exists in the storage as well, for example after an undo
+    #[error("entry `{0}` exists already, it was restored by other means")]
+    AlreadyExists(String),
+}
+
+/// Information stored along the trashed task and note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashInfo {
+    deleted: DateTime<Local>,
+}
+
+/// Task and note of the same task in the trash
+pub struct TrashEntry {
+    /// Id of the task
+    pub id: Uuid,
+    /// When the entry was moved to the trash
+    pub deleted: DateTime<Local>,
+    /// Deleted task, none if only the note was deleted
+    pub task: Option<Task>,
+    /// Deleted note (if any)
+    #[cfg(feature = "note")]
+    pub note: Option<Note>,
+}
+
+/// Returns the directory holding the trash of the namespace
+fn trash_pathbuf(settings: &Settings) -> Result<PathBuf> {
+    let pathbuf = settings.db_pathbuf()?.join("trash");
+    if !pathbuf.is_dir() {
+        create_dir_all(&pathbuf).with_context(|| "while creating trash directory")?;
+    }
+    Ok(pathbuf)
+}
+
+/// Returns the directory of the trash entry. Each entry keeps the task, note and their backups.
+fn entry_pathbuf(id: &Uuid, settings: &Settings) -> Result<PathBuf> {
+    Ok(trash_pathbuf(settings)?.join(id.to_string()))
+}
+
+/// Move the rotated backup files of the entry from one directory to another
+fn move_backups(id: &Uuid, from: &Path, to: &Path) -> Result<()> {
+    let pattern = from.join(format!("{}.*.yaml", id));
+    for backup in glob(pattern.to_str().unwrap()).with_context(|| "while searching backups")? {
+        let backup = backup?;
+        create_dir_all(to).with_context(|| "while creating backup directory")?;
+        rename(&backup, to.join(backup.file_name().unwrap()))
+            .with_context(|| "while moving backup file")?;
+    }
+    Ok(())
+}
+
+/// Create the trash entry directory and stamp it with the time of deletion
+fn open_entry(id: &Uuid, settings: &Settings) -> Result<PathBuf> {
+    let pathbuf = entry_pathbuf(id, settings)?;
+    create_dir_all(&pathbuf).with_context(|| "while creating trash entry directory")?;
+    let info = TrashInfo {
+        deleted: chrono::offset::Local::now(),
+    };
+    write(
+        pathbuf.join("trash.yaml"),
+        serde_yaml::to_string(&info).with_context(|| "while serializing trash info")?,
+    )
+    .with_context(|| "while writing trash info")?;
+    Ok(pathbuf)
+}
+
+/// Move the note of the task to the trash. Backups of the note are moved along.
+#[cfg(feature = "note")]
+pub fn trash_note(id: &str, settings: &Settings) -> Result<Note> {
+    purge_expired(settings)?;
+    let mut note = load_note(id, settings)?;
+    let pathbuf = open_entry(&note.task_id, settings)?;
+    note.save_yaml_file_to(&pathbuf.join("note.yaml"), &0)
+        .with_context(|| "while saving note to trash")?;
+    delete_note(&note.task_id.to_string(), settings)?;
+    move_backups(
+        &note.task_id,
+        &settings.note_db_pathbuf()?,
+        &pathbuf.join("notes"),
+    )?;
+    Ok(note)
+}
+
+/// Move the task and its note (if any) to the trash. Backups of both are moved along.
+pub fn trash_task(id: &str, settings: &Settings) -> Result<Task> {
+    purge_expired(settings)?;
+    let mut task = load_task(id, settings)?;
+    let pathbuf = open_entry(&task.id, settings)?;
+    task.save_yaml_file_to(&pathbuf.join("task.yaml"), &0)
+        .with_context(|| "while saving task to trash")?;
+
+    #[cfg(feature = "note")]
+    if open_storage(settings)?.note_exists(&task.id)? {
+        let mut note = load_note(&task.id.to_string(), settings)?;
+        note.save_yaml_file_to(&pathbuf.join("note.yaml"), &0)
+            .with_context(|| "while saving note to trash")?;
+        delete_note(&task.id.to_string(), settings)?;
+        move_backups(
+            &task.id,
+            &settings.note_db_pathbuf()?,
+            &pathbuf.join("notes"),
+        )?;
+    }
+
+    delete_task(&task.id.to_string(), settings)?;
+    move_backups(
+        &task.id,
+        &settings.task_db_pathbuf()?,
+        &pathbuf.join("tasks"),
+    )?;
+    Ok(task)
+}
+
+/// Load the trash entry from its directory
+fn load_entry(id: &Uuid, pathbuf: &Path) -> Result<TrashEntry> {
+    let info: TrashInfo = serde_yaml::from_str(
+        &read_to_string(pathbuf.join("trash.yaml")).with_context(|| "while reading trash info")?,
+    )
+    .with_context(|| "while deserializing trash info")?;
+    let task_pathbuf = pathbuf.join("task.yaml");
+    let task = if task_pathbuf.is_file() {
+        Some(Task::load_yaml_file_from(&task_pathbuf)?)
+    } else {
+        None
+    };
+    #[cfg(feature = "note")]
+    let note_pathbuf = pathbuf.join("note.yaml");
+    Ok(TrashEntry {
+        id: *id,
+        deleted: info.deleted,
+        task,
+        #[cfg(feature = "note")]
+        note: if note_pathbuf.is_file() {
+            Some(Note::load_yaml_file_from(&note_pathbuf)?)
+        } else {
+            None
+        },
+    })
+}
+
+/// Load all entries of the trash, including the ones already restored by other means
+fn load_entries(settings: &Settings) -> Result<Vec<TrashEntry>> {
+    let mut entries: Vec<TrashEntry> = vec![];
+    for dir_entry in
+        read_dir(trash_pathbuf(settings)?).with_context(|| "while reading trash directory")?
+    {
+        let dir_entry = dir_entry?;
+        if let Ok(id) = Uuid::parse_str(&dir_entry.file_name().to_string_lossy()) {
+            entries.push(load_entry(&id, &dir_entry.path())?);
+        }
+    }
+    entries.sort_by_key(|entry| entry.deleted);
+    entries.reverse();
+    Ok(entries)
+}
+
+/// List the entries in the trash, most recently deleted first. Entries whose task or note has
+/// been brought back by other means, like undo, are left out.
+pub fn list_trash(settings: &Settings) -> Result<Vec<TrashEntry>> {
+    purge_expired(settings)?;
+    let storage = open_storage(settings)?;
+    let mut entries: Vec<TrashEntry> = vec![];
+    for entry in load_entries(settings)? {
+        if entry.task.is_some() && storage.task_exists(&entry.id)? {
+            continue;
+        }
+        #[cfg(feature = "note")]
+        if entry.task.is_none() && entry.note.is_some() && storage.note_exists(&entry.id)? {
+            continue;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Resolve the id of an entry in the trash from a full id, short id or an unique id prefix
+pub fn resolve_trash_id(id: &str, settings: &Settings) -> Result<Uuid> {
+    let entries = load_entries(settings)?;
+    if let Some(entry) = entries.iter().find(|entry| {
+        entry
+            .task
+            .as_ref()
+            .and_then(|task| task.short_id())
+            .is_some_and(|short_id| short_id.to_string() == id)
+    }) {
+        return Ok(entry.id);
+    }
+    let ids: Vec<Uuid> = entries.iter().map(|entry| entry.id).collect();
+    resolve_candidates(id, find_ids_by_prefix(id, &ids))
+}
+
+/// Restore the task and note of the trash entry along with their backups
+pub fn restore_from_trash(id: &str, settings: &Settings) -> Result<TrashEntry> {
+    let id = resolve_trash_id(id, settings)?;
+    let pathbuf = entry_pathbuf(&id, settings)?;
+    let mut entry = load_entry(&id, &pathbuf)?;
+    let storage = open_storage(settings)?;
+    if entry.task.is_some() && storage.task_exists(&id)? {
+        bail!(TrashError::AlreadyExists(id.to_string()));
+    }
+    #[cfg(feature = "note")]
+    if entry.note.is_some() && storage.note_exists(&id)? {
+        bail!(TrashError::AlreadyExists(id.to_string()));
+    }
+
+    if let Some(task) = entry.task.as_mut() {
+        // the short id might have been given to another task in the meanwhile
+        let short_id_taken = storage
+            .task_summaries()
+            .with_context(|| "while looking up short ids in use")?
+            .iter()
+            .any(|other| other.short_id.is_some() && other.short_id == task.short_id());
+        if short_id_taken {
+            task.metadata.remove("tsk-rs-task-short-id");
+        }
+        move_backups(&id, &pathbuf.join("tasks"), &settings.task_db_pathbuf()?)?;
+        save_task(task, settings).with_context(|| "while restoring task")?;
+    }
+
+    #[cfg(feature = "note")]
+    if let Some(note) = entry.note.as_mut() {
+        move_backups(&id, &pathbuf.join("notes"), &settings.note_db_pathbuf()?)?;
+        save_note(note, settings).with_context(|| "while restoring note")?;
+    }
+
+    remove_dir_all(pathbuf).with_context(|| "while removing trash entry")?;
+    Ok(entry)
+}
+
+/// Remove an entry from the trash permanently, or all of them if no id is given. Returns the
+/// amount of entries removed.
+pub fn purge_trash(id: &Option<String>, settings: &Settings) -> Result<usize> {
+    let ids: Vec<Uuid> = match id {
+        Some(id) => vec![resolve_trash_id(id, settings)?],
+        None => load_entries(settings)?
+            .iter()
+            .map(|entry| entry.id)
+            .collect(),
+    };
+    for id in &ids {
+        remove_dir_all(entry_pathbuf(id, settings)?)
+            .with_context(|| "while removing trash entry")?;
+    }
+    Ok(ids.len())
+}
+
+/// Remove the entries that have been in the trash longer than the retention period set in the
+/// configuration. Returns the amount of entries removed.
+pub fn purge_expired(settings: &Settings) -> Result<usize> {
+    if settings.data.trashdays == 0 {
+        return Ok(0);
+    }
+    // nothing can have been deleted before the earliest time there is
+    let Some(oldest) = i64::try_from(settings.data.trashdays)
+        .ok()
+        .and_then(Duration::try_days)
+        .and_then(|retention| chrono::offset::Local::now().checked_sub_signed(retention))
+    else {
+        return Ok(0);
+    };
+    let mut purged: usize = 0;
+    for entry in load_entries(settings)? {
+        if entry.deleted < oldest {
+            remove_dir_all(entry_pathbuf(&entry.id, settings)?)
+                .with_context(|| "while removing expired trash entry")?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::StorageBackend, testing::TestData};
+
+    fn backends() -> Vec<StorageBackend> {
+        vec![
+            StorageBackend::Flatfile,
+            #[cfg(feature = "sqlite")]
+            StorageBackend::Sqlite,
+        ]
+    }
+
+    fn settings(backend: StorageBackend, trashdays: usize) -> TestData {
+        TestData::with(|settings| {
+            settings.data.backend = backend;
+            settings.data.trashdays = trashdays;
+        })
+    }
+
+    fn new_task(description: &str, settings: &Settings) -> Task {
+        let mut task = Task::new(description.to_string()).unwrap();
+        save_task(&mut task, settings).unwrap();
+        task
+    }
+
+    /// Pretend the entry was moved to the trash the given amount of days ago
+    fn age_entry(id: &Uuid, days: i64, settings: &Settings) {
+        let info = TrashInfo {
+            deleted: chrono::offset::Local::now() - Duration::days(days),
+        };
+        write(
+            entry_pathbuf(id, settings).unwrap().join("trash.yaml"),
+            serde_yaml::to_string(&info).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn trashed_ids(settings: &Settings) -> Vec<Uuid> {
+        list_trash(settings)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    #[test]
+    fn trash_moves_task_to_trash() {
+        for backend in backends() {
+            let settings = settings(backend, 0);
+            let task = new_task("to be deleted", &settings);
+
+            trash_task(&task.id.to_string(), &settings).unwrap();
+
+            assert!(!open_storage(&settings)
+                .unwrap()
+                .task_exists(&task.id)
+                .unwrap());
+            let entries = list_trash(&settings).unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(
+                entries[0].task.as_ref().unwrap().description,
+                "to be deleted"
+            );
+        }
+    }
+
+    #[test]
+    fn restore_brings_task_back() {
+        for backend in backends() {
+            let settings = settings(backend, 0);
+            let task = new_task("to be restored", &settings);
+            trash_task(&task.id.to_string(), &settings).unwrap();
+
+            restore_from_trash(&task.id.to_string(), &settings).unwrap();
+
+            let restored = load_task(&task.id.to_string(), &settings).unwrap();
+            assert_eq!(restored.description, "to be restored");
+            assert_eq!(restored.short_id(), task.short_id());
+            assert!(trashed_ids(&settings).is_empty());
+        }
+    }
+
+    #[test]
+    fn purge_removes_entry() {
+        for backend in backends() {
+            let settings = settings(backend, 0);
+            let purged = new_task("to be purged", &settings);
+            let kept = new_task("to be kept", &settings);
+            trash_task(&purged.id.to_string(), &settings).unwrap();
+            trash_task(&kept.id.to_string(), &settings).unwrap();
+
+            assert_eq!(
+                purge_trash(&Some(purged.id.to_string()), &settings).unwrap(),
+                1
+            );
+
+            assert_eq!(trashed_ids(&settings), vec![kept.id]);
+            assert!(restore_from_trash(&purged.id.to_string(), &settings).is_err());
+        }
+    }
+
+    #[test]
+    fn retention_expires_only_old_entries() {
+        for backend in backends() {
+            let settings = settings(backend, 7);
+            let old = new_task("deleted long ago", &settings);
+            let recent = new_task("deleted recently", &settings);
+            trash_task(&old.id.to_string(), &settings).unwrap();
+            trash_task(&recent.id.to_string(), &settings).unwrap();
+            age_entry(&old.id, 8, &settings);
+            age_entry(&recent.id, 6, &settings);
+
+            assert_eq!(purge_expired(&settings).unwrap(), 1);
+
+            assert_eq!(trashed_ids(&settings), vec![recent.id]);
+        }
+    }
+
+    #[test]
+    fn huge_trashdays_keeps_entries() {
+        for trashdays in [1_000_000_000, usize::MAX] {
+            let settings = settings(StorageBackend::Flatfile, trashdays);
+            let task = new_task("deleted ages ago", &settings);
+            trash_task(&task.id.to_string(), &settings).unwrap();
+            age_entry(&task.id, 10000, &settings);
+
+            assert_eq!(purge_expired(&settings).unwrap(), 0);
+
+            assert_eq!(trashed_ids(&settings), vec![task.id]);
+        }
+    }
+
+    #[test]
+    fn zero_trashdays_keeps_entries_forever() {
+        for backend in backends() {
+            let settings = settings(backend, 0);
+            let task = new_task("deleted ages ago", &settings);
+            trash_task(&task.id.to_string(), &settings).unwrap();
+            age_entry(&task.id, 10000, &settings);
+
+            assert_eq!(purge_expired(&settings).unwrap(), 0);
+
+            assert_eq!(trashed_ids(&settings), vec![task.id]);
+        }
+    }
+}
+
+// eof
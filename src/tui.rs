@@ -0,0 +1,622 @@
+use std::{
+    str::FromStr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use chrono::Local;
+use color_eyre::eyre::{Context, Result};
+use hhmmss::Hhmmss;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table, TableState, Wrap},
+    DefaultTerminal, Frame,
+};
+#[cfg(feature = "note")]
+use uuid::Uuid;
+
+use crate::{
+    bulk::modify_tasks,
+    journal::begin_operation,
+    notify::{DatabaseFileType, FileHandler, FilesystemMonitor},
+    parser::filter_lexicon::Filter,
+    settings::Settings,
+    storage::StorageBackend,
+    task::{
+        complete_task, list_tasks_matching, load_task, start_task, stop_task, Task, TaskPriority,
+    },
+};
+#[cfg(feature = "note")]
+use crate::{
+    note::{load_note, Note},
+    storage::open_storage,
+};
+
+/// How often the screen is redrawn when nothing happens, keeps the runtimes ticking
+const TICK: Duration = Duration::from_millis(250);
+
+/// What the key presses are for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Moving around the list and acting on the selected task
+    Normal,
+    /// Typing a filter expression to the filter bar
+    Filter,
+    /// Waiting for the priority to set to the selected task
+    Priority,
+}
+
+/// Sends a notice of every changed task or note file to the user interface
+struct ChangeNotifier {
+    sender: Sender<()>,
+}
+
+impl FileHandler for ChangeNotifier {
+    fn handle(&self, _file: DatabaseFileType, _settings: Settings) {
+        // the user interface is gone if sending fails, nothing to refresh then
+        self.sender.send(()).unwrap_or(());
+    }
+}
+
+/// State of the terminal user interface
+struct App {
+    settings: Settings,
+    tasks: Vec<Task>,
+    table_state: TableState,
+    filter: String,
+    input: String,
+    include_done: bool,
+    mode: Mode,
+    status: String,
+    #[cfg(feature = "note")]
+    note: Option<Note>,
+    quit: bool,
+}
+
+impl App {
+    fn new(settings: &Settings) -> Self {
+        let mut app = App {
+            settings: settings.clone(),
+            tasks: vec![],
+            table_state: TableState::default(),
+            filter: String::new(),
+            input: String::new(),
+            include_done: false,
+            mode: Mode::Normal,
+            status: String::new(),
+            #[cfg(feature = "note")]
+            note: None,
+            quit: false,
+        };
+        app.refresh();
+        app
+    }
+
+    fn selected_task(&self) -> Option<&Task> {
+        self.table_state
+            .selected()
+            .and_then(|index| self.tasks.get(index))
+    }
+
+    /// Load the tasks again, keeping the same task selected if it is still listed
+    fn refresh(&mut self) {
+        let selected_id = self.selected_task().map(|task| task.id);
+        match self.load_tasks() {
+            Ok(tasks) => self.tasks = tasks,
+            Err(error) => self.status = format!("Error: {}", error),
+        }
+        let index = selected_id
+            .and_then(|id| self.tasks.iter().position(|task| task.id == id))
+            .or_else(|| {
+                self.table_state
+                    .selected()
+                    .map(|index| index.min(self.tasks.len().saturating_sub(1)))
+            })
+            .or(Some(0));
+        self.select(index);
+    }
+
+    fn load_tasks(&self) -> Result<Vec<Task>> {
+        let filter = if self.filter.trim().is_empty() {
+            None
+        } else {
+            Some(Filter::from_str(&self.filter)?)
+        };
+        list_tasks_matching(&filter, &self.include_done, &self.settings)
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        let index = index.filter(|_| !self.tasks.is_empty());
+        self.table_state.select(index);
+        #[cfg(feature = "note")]
+        {
+            self.note = match self.selected_task().map(|task| task.id) {
+                Some(id) => self.load_note(&id).unwrap_or(None),
+                None => None,
+            };
+        }
+    }
+
+    #[cfg(feature = "note")]
+    fn load_note(&self, id: &Uuid) -> Result<Option<Note>> {
+        if !open_storage(&self.settings)?.note_exists(id)? {
+            return Ok(None);
+        }
+        Ok(Some(load_note(&id.to_string(), &self.settings)?))
+    }
+
+    /// Move the selection by the amount of rows, up if negative
+    fn move_selection(&mut self, rows: isize) {
+        if self.tasks.is_empty() {
+            return;
+        }
+        let index = self
+            .table_state
+            .selected()
+            .unwrap_or(0)
+            .saturating_add_signed(rows)
+            .min(self.tasks.len() - 1);
+        self.select(Some(index));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        match self.mode {
+            Mode::Normal => self.handle_normal_key(key),
+            Mode::Filter => self.handle_filter_key(key),
+            Mode::Priority => self.handle_priority_key(key),
+        }
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::Home | KeyCode::Char('g') => self.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => {
+                self.select(Some(self.tasks.len().saturating_sub(1)))
+            }
+            KeyCode::Char('/') => {
+                self.input = self.filter.clone();
+                self.mode = Mode::Filter;
+            }
+            KeyCode::Char('a') => {
+                self.include_done = !self.include_done;
+                self.refresh();
+            }
+            KeyCode::Char('r') => {
+                self.status.clear();
+                self.refresh();
+            }
+            KeyCode::Char('s') if self.selected_task().is_some_and(Task::is_running) => {
+                self.act("stop", |task, settings| {
+                    stop_task(&task.id.to_string(), &false, settings)?;
+                    Ok("stopped")
+                })
+            }
+            KeyCode::Char('s') => self.act("start", |task, settings| {
+                start_task(&task.id.to_string(), &None, settings)?;
+                Ok("started")
+            }),
+            KeyCode::Char('d') => self.act("done", |task, settings| {
+                complete_task(&mut load_task(&task.id.to_string(), settings)?, settings)?;
+                Ok("completed")
+            }),
+            KeyCode::Char('h') => {
+                self.act("hold", |task, settings| toggle_tag(task, "hold", settings))
+            }
+            KeyCode::Char('n') => {
+                self.act("next", |task, settings| toggle_tag(task, "next", settings))
+            }
+            KeyCode::Char('p') if self.selected_task().is_some() => self.mode = Mode::Priority,
+            _ => {}
+        }
+    }
+
+    fn handle_filter_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                // a filter that does not parse is kept in the bar for fixing
+                if self.input.trim().is_empty() || Filter::from_str(&self.input).is_ok() {
+                    self.filter = self.input.trim().to_string();
+                    self.mode = Mode::Normal;
+                    self.status.clear();
+                    self.refresh();
+                } else {
+                    self.status = format!("Error: invalid filter expression '{}'", self.input);
+                }
+            }
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.status.clear();
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(character) => self.input.push(character),
+            _ => {}
+        }
+    }
+
+    fn handle_priority_key(&mut self, key: KeyEvent) {
+        self.mode = Mode::Normal;
+        let priority = match key.code {
+            KeyCode::Char('l') => Some(TaskPriority::Low),
+            KeyCode::Char('m') => Some(TaskPriority::Medium),
+            KeyCode::Char('h') => Some(TaskPriority::High),
+            KeyCode::Char('c') => Some(TaskPriority::Critical),
+            KeyCode::Char('x') => None,
+            _ => return,
+        };
+        self.act("priority", |task, settings| {
+            modify_tasks(
+                vec![load_task(&task.id.to_string(), settings)?],
+                |task| match &priority {
+                    Some(_) => {
+                        task.set_characteristic(&priority, &None, &None, &None, &None, &None)
+                    }
+                    None => {
+                        Ok(task.unset_characteristic(&true, &false, &None, &false, &None, &None))
+                    }
+                },
+                settings,
+            )?;
+            Ok("prioritized")
+        });
+    }
+
+    /// Run the action on the selected task as an operation of its own, so that it can be undone
+    /// separately, and show the outcome in the status line
+    fn act<F>(&mut self, action: &str, run: F)
+    where
+        F: FnOnce(&Task, &Settings) -> Result<&'static str>,
+    {
+        let Some(task) = self.selected_task().cloned() else {
+            return;
+        };
+        let id = task
+            .short_id()
+            .map(|short_id| short_id.to_string())
+            .unwrap_or_else(|| task.id.to_string());
+        begin_operation(&format!("tsk tui {} {}", action, id));
+        self.status = match run(&task, &self.settings) {
+            Ok(outcome) => format!("Task '{}' {}.", task.description, outcome),
+            Err(error) => format!("Error: {}", error),
+        };
+        self.refresh();
+    }
+}
+
+/// Add the tag to the task, or remove it if the task already has it
+fn toggle_tag(task: &Task, tag: &str, settings: &Settings) -> Result<&'static str> {
+    let tags = Some(vec![tag.to_string()]);
+    let tagged = task
+        .tags
+        .as_ref()
+        .is_some_and(|task_tags| task_tags.iter().any(|task_tag| task_tag == tag));
+    modify_tasks(
+        vec![load_task(&task.id.to_string(), settings)?],
+        |task| {
+            if tagged {
+                Ok(task.unset_characteristic(&false, &false, &tags, &false, &None, &None))
+            } else {
+                task.set_characteristic(&None, &None, &tags, &None, &None, &None)
+            }
+        },
+        settings,
+    )?;
+    Ok(if tagged { "untagged" } else { "tagged" })
+}
+
+/// Render the markdown of a note line by line. Headings are emphasized and list items, action
+/// points included, get their bullets and check boxes.
+fn markdown_lines(markdown: &str) -> Vec<Line<'static>> {
+    markdown
+        .lines()
+        .map(|line| {
+            let item = line.trim_start();
+            let indent = &line[..line.len() - item.len()];
+            if item.starts_with('#') {
+                Line::styled(
+                    item.trim_start_matches('#').trim().to_string(),
+                    Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                )
+            } else if let Some(text) = ["- [ ] ", "* [ ] "]
+                .iter()
+                .find_map(|marker| item.strip_prefix(marker))
+            {
+                Line::from(format!("{}☐ {}", indent, text))
+            } else if let Some(text) = ["- [x] ", "* [x] ", "- [X] ", "* [X] "]
+                .iter()
+                .find_map(|marker| item.strip_prefix(marker))
+            {
+                Line::styled(
+                    format!("{}🗹 {}", indent, text),
+                    Style::new().add_modifier(Modifier::DIM),
+                )
+            } else if let Some(text) = ["- ", "* "]
+                .iter()
+                .find_map(|marker| item.strip_prefix(marker))
+            {
+                Line::from(format!("{}• {}", indent, text))
+            } else {
+                Line::from(line.to_string())
+            }
+        })
+        .collect()
+}
+
+fn task_rows(app: &App) -> Vec<Row<'static>> {
+    app.tasks
+        .iter()
+        .map(|task| {
+            let score = task
+                .metadata
+                .get("tsk-rs-task-score")
+                .and_then(|score| score.parse::<f64>().ok())
+                .map(|score| (score * app.settings.output.scoremultiplier) as usize)
+                .unwrap_or_default();
+            let runtime = task
+                .current_runtime()
+                .map(|runtime| Hhmmss::hhmmss(&runtime))
+                .unwrap_or_default();
+            let style = if task.done {
+                Style::new().add_modifier(Modifier::DIM)
+            } else if task.is_running() && app.settings.output.colors {
+                Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)
+            } else {
+                Style::new()
+            };
+            Row::new(vec![
+                task.short_id()
+                    .map(|short_id| short_id.to_string())
+                    .unwrap_or_default(),
+                task.description.clone(),
+                task.project.clone().unwrap_or_default(),
+                task.metadata
+                    .get("tsk-rs-task-priority")
+                    .cloned()
+                    .unwrap_or_default(),
+                score.to_string(),
+                runtime,
+            ])
+            .style(style)
+        })
+        .collect()
+}
+
+fn detail_lines(app: &App) -> Vec<Line<'static>> {
+    let Some(task) = app.selected_task() else {
+        return vec![Line::from("No tasks")];
+    };
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(
+                format!("{:<10}", name),
+                Style::new().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(value),
+        ])
+    };
+    let mut lines = vec![
+        Line::styled(
+            task.description.clone(),
+            Style::new().add_modifier(Modifier::BOLD),
+        ),
+        Line::default(),
+        field("Task ID", task.id.to_string()),
+        field("Project", task.project.clone().unwrap_or_default()),
+        field("Tags", task.tags.clone().unwrap_or_default().join(", ")),
+    ];
+    for (name, key) in [
+        ("Priority", "tsk-rs-task-priority"),
+        ("Due", "tsk-rs-task-due-time"),
+        ("Score", "tsk-rs-task-score"),
+    ] {
+        if let Some(value) = task.metadata.get(key) {
+            lines.push(field(name, value.clone()));
+        }
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::styled(
+        "Time tracking",
+        Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+    ));
+    let now = Local::now();
+    let mut total = chrono::Duration::zero();
+    for track in task.timetracker.iter().flatten() {
+        let runtime = track.end_time.unwrap_or(now) - track.start_time;
+        total += runtime;
+        lines.push(Line::from(format!(
+            "{} - {} {} {}",
+            track.start_time.format("%Y-%m-%d %H:%M"),
+            track
+                .end_time
+                .map(|end_time| end_time.format("%H:%M").to_string())
+                .unwrap_or_else(|| "running".to_string()),
+            Hhmmss::hhmmss(&runtime),
+            track.annotation.clone().unwrap_or_default(),
+        )));
+    }
+    lines.push(field("Total", Hhmmss::hhmmss(&total)));
+    if let Some(remaining) = task.remaining_effort(now) {
+        lines.push(field(
+            "Remaining",
+            if remaining < chrono::Duration::zero() {
+                format!("over estimate by {}", Hhmmss::hhmmss(&-remaining))
+            } else {
+                Hhmmss::hhmmss(&remaining)
+            },
+        ));
+    }
+
+    #[cfg(feature = "note")]
+    if let Some(note) = &app.note {
+        lines.push(Line::default());
+        let action_points = note.get_action_points().ok().flatten().unwrap_or_default();
+        lines.push(Line::styled(
+            format!(
+                "Note, {}/{} action points done",
+                action_points.iter().filter(|point| point.checked).count(),
+                action_points.len()
+            ),
+            Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        ));
+        lines.append(&mut markdown_lines(
+            note.markdown.as_deref().unwrap_or_default(),
+        ));
+    }
+    lines
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [main_area, filter_area, status_area] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [list_area, detail_area] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .areas(main_area);
+
+    let title = format!(
+        " {} | {} task(s){} ",
+        app.settings.namespace,
+        app.tasks.len(),
+        if app.include_done {
+            ", completed included"
+        } else {
+            ""
+        }
+    );
+    let table = Table::new(
+        task_rows(app),
+        [
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "ID",
+            "Description",
+            "Project",
+            "Priority",
+            "Score",
+            "Runtime",
+        ])
+        .style(Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)),
+    )
+    .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .block(Block::bordered().title(title));
+    frame.render_stateful_widget(table, list_area, &mut app.table_state);
+
+    let details = Paragraph::new(detail_lines(app))
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title(" Details "));
+    frame.render_widget(details, detail_area);
+
+    let filter_line = match app.mode {
+        Mode::Filter => Line::from(vec![
+            Span::styled("Filter: ", Style::new().add_modifier(Modifier::BOLD)),
+            Span::raw(app.input.clone()),
+            Span::styled("_", Style::new().add_modifier(Modifier::SLOW_BLINK)),
+        ]),
+        _ => Line::from(vec![
+            Span::styled("Filter: ", Style::new().add_modifier(Modifier::BOLD)),
+            Span::raw(app.filter.clone()),
+        ]),
+    };
+    frame.render_widget(Paragraph::new(filter_line), filter_area);
+
+    let help = match app.mode {
+        Mode::Normal => "q quit | / filter | a completed | s start/stop | d done | h hold | n next | p priority | r refresh",
+        Mode::Filter => "enter apply | esc cancel",
+        Mode::Priority => "priority: l low | m medium | h high | c critical | x none | other key cancels",
+    };
+    let status = if app.status.is_empty() || app.mode == Mode::Priority {
+        Span::styled(help, Style::new().add_modifier(Modifier::DIM))
+    } else {
+        Span::raw(app.status.clone())
+    };
+    frame.render_widget(Paragraph::new(Line::from(status)), status_area);
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, changes: &Receiver<()>) -> Result<()> {
+    while !app.quit {
+        terminal
+            .draw(|frame| draw(frame, app))
+            .with_context(|| "while drawing the user interface")?;
+        if event::poll(TICK).with_context(|| "while waiting for input")? {
+            if let Event::Key(key) = event::read().with_context(|| "while reading input")? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key);
+                }
+            }
+        }
+        // a burst of changes, like the ones made by the user interface itself, is refreshed once
+        if changes.try_iter().count() > 0 {
+            app.refresh();
+        }
+    }
+    Ok(())
+}
+
+fn on_watch_error(_msg: String) {
+    // watching is best effort, the list can always be refreshed by hand
+}
+
+/// Run the interactive terminal user interface until the user quits. The task list is refreshed
+/// whenever task or note files change on the disk, which works with the flatfile storage only.
+pub fn run_tui(settings: &Settings) -> Result<()> {
+    let mut app = App::new(settings);
+
+    let (sender, changes) = channel();
+    let mut monitor = FilesystemMonitor::new();
+    if settings.data.backend == StorageBackend::Flatfile {
+        monitor.watch(
+            settings,
+            Arc::new(Mutex::new(ChangeNotifier { sender })),
+            on_watch_error,
+        );
+    }
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &changes);
+    ratatui::restore();
+    monitor.stop();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markdown() {
+        let lines = markdown_lines("# Meeting\nnotes\n- [ ] call back\n  - [x] send mail\n* item");
+        let texts: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        assert_eq!(
+            texts,
+            vec!["Meeting", "notes", "☐ call back", "  🗹 send mail", "• item"]
+        );
+        assert!(lines[0].style.add_modifier.contains(Modifier::BOLD));
+    }
+}
+
+// eof